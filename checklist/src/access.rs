@@ -0,0 +1,357 @@
+//! Users, ownership, and sharing.
+//!
+//! A [`Db`] opened with [`Db::new`] acts as nobody in particular. [`Db::as_user`] gives a handle
+//! which acts as one user instead, and [`Db::with_full_access`] one which may do anything, as the
+//! only user of a personal database would. Checklists created through a handle belong to its user,
+//! if it has one, and every operation on an existing checklist or its items first checks that the
+//! handle may perform it, failing with [`Error::Forbidden`] otherwise. Listings and searches
+//! silently leave out what the handle may not read.
+//!
+//! The owner of a checklist may do anything with it, including deleting it and sharing it; others
+//! have whatever [`Access`] they were granted. Checklists without an owner, such as those created
+//! before there were users, are open to everyone, with or without a user.
+//!
+//! Users and grants are local to a database: syncing does not carry them to other devices. Owners
+//! are synced, so a checklist someone owns is closed on a device where they are not a user, other
+//! than to handles with full access.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use surrealdb::{RecordId, RecordIdKey};
+
+use crate::{Checklist, ChecklistId, Db, Error, ItemId, Result, datetime, txn::Transaction};
+
+pub(crate) const USER_TABLE: &str = "user";
+const SHARE_TABLE: &str = "share";
+
+/// Access level of the owner, above any which can be granted.
+pub(crate) const OWNER: u8 = 4;
+
+/// What a user other than the owner may do with a shared checklist.
+///
+/// Each level includes those before it.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    /// See the checklist and its items.
+    Read = 1,
+    /// Also check and uncheck items.
+    Check = 2,
+    /// Also change the checklist and add, change, and delete items.
+    Edit = 3,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    derive_more::Constructor,
+    derive_more::Deref,
+    derive_more::From,
+    derive_more::Into,
+    derive_more::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(try_from = "RecordId", into = "RecordId")]
+pub struct UserId(RecordIdKey);
+
+impl FromStr for UserId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(s.into()))
+    }
+}
+
+impl From<UserId> for RecordId {
+    fn from(value: UserId) -> Self {
+        RecordId::from_table_key(USER_TABLE, value.0)
+    }
+}
+
+impl TryFrom<RecordId> for UserId {
+    type Error = Error;
+
+    fn try_from(value: RecordId) -> std::result::Result<Self, Self::Error> {
+        if value.table() != USER_TABLE {
+            return Err(Error::WrongRecordId {
+                expected: USER_TABLE,
+                got: value.table().to_owned(),
+            });
+        }
+        Ok(Self(value.key().to_owned()))
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct InsertUser {
+    name: String,
+}
+
+/// Someone who owns checklists and can be granted access to others'.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct User {
+    pub id: UserId,
+    /// Unique within the database.
+    pub name: String,
+    #[serde(with = "datetime::required")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl User {
+    /// Fails if a user with this name already exists.
+    pub async fn new(db: &Db, name: impl Into<String>) -> Result<Self> {
+        db.inner
+            .create(USER_TABLE)
+            .content(InsertUser { name: name.into() })
+            .await
+            .map_err(Error::surreal("creating user"))?
+            .ok_or(Error::FailedCreate {
+                resource: USER_TABLE,
+            })
+    }
+
+    pub async fn load(db: &Db, id: UserId) -> Result<Option<Self>> {
+        db.inner
+            .select(RecordId::from(id))
+            .await
+            .map_err(Error::surreal("loading user"))
+    }
+
+    pub async fn by_name(db: &Db, name: &str) -> Result<Option<Self>> {
+        db.inner
+            .query(format!("SELECT * FROM {USER_TABLE} WHERE name = $name"))
+            .bind(("name", name.to_owned()))
+            .await
+            .map_err(Error::surreal("loading user"))?
+            .take::<Option<Self>>(0)
+            .map_err(Error::surreal("loading user"))
+    }
+
+    /// All users, in alphabetical order.
+    pub async fn all(db: &Db) -> Result<Vec<Self>> {
+        db.inner
+            .query(format!("SELECT * FROM {USER_TABLE} ORDER BY name"))
+            .await
+            .map_err(Error::surreal("loading users"))?
+            .take(0)
+            .map_err(Error::surreal("loading users"))
+    }
+}
+
+/// Access to a checklist granted to someone other than its owner.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Grant {
+    pub user: UserId,
+    pub access: Access,
+}
+
+impl Db {
+    /// A handle to the same database which acts as `user`.
+    pub fn as_user(&self, user: UserId) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            kek: self.kek.clone(),
+            attachment_key: self.attachment_key.clone(),
+            user: Some(user),
+            full_access: false,
+        }
+    }
+
+    /// A handle to the same database which may do anything, whoever owns what.
+    ///
+    /// Syncing needs such a handle, since it carries every change.
+    pub fn with_full_access(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            kek: self.kek.clone(),
+            attachment_key: self.attachment_key.clone(),
+            user: None,
+            full_access: true,
+        }
+    }
+
+    /// The user this handle acts as, if any.
+    pub fn user(&self) -> Option<&UserId> {
+        self.user.as_ref()
+    }
+
+    /// The acting user, bound as `$user` in queries; `NONE` if there is none.
+    pub(crate) fn user_record(&self) -> Option<RecordId> {
+        self.user.clone().map(RecordId::from)
+    }
+
+    /// Fail unless this handle has full access.
+    pub(crate) fn require_full_access(&self) -> Result<()> {
+        match self.full_access {
+            true => Ok(()),
+            false => Err(Error::Forbidden),
+        }
+    }
+
+    /// Fail unless the acting user has at least `access` to `checklist`.
+    pub(crate) async fn authorize(&self, checklist: &ChecklistId, access: Access) -> Result<()> {
+        self.require_level(RecordId::from(checklist.clone()), access as u8)
+            .await
+    }
+
+    /// Fail unless the acting user owns `checklist`.
+    pub(crate) async fn authorize_owner(&self, checklist: &ChecklistId) -> Result<()> {
+        self.require_level(RecordId::from(checklist.clone()), OWNER)
+            .await
+    }
+
+    /// Fail unless the acting user has at least `access` to the checklist containing `item`.
+    pub(crate) async fn authorize_item(&self, item: &ItemId, access: Access) -> Result<()> {
+        self.require_level(RecordId::from(item.clone()), access as u8)
+            .await
+    }
+
+    /// Fail unless the acting user has at least `level` to `checklist`, whether or not it is
+    /// locked; for changing whether it is locked or archived.
+    pub(crate) async fn authorize_despite_lock(
+        &self,
+        checklist: &ChecklistId,
+        level: u8,
+    ) -> Result<()> {
+        self.check_level(RecordId::from(checklist.clone()), level, false)
+            .await
+    }
+
+    /// Fail unless the acting user's access level to `record`, a checklist or an item, is at least
+    /// `level`.
+    ///
+    /// Anything beyond reading also fails with [`Error::Locked`] if the checklist is locked, even
+    /// with full access. Records which do not exist pass, so that the operation itself reports them
+    /// missing.
+    pub(crate) async fn require_level(&self, record: RecordId, level: u8) -> Result<()> {
+        self.check_level(record, level, true).await
+    }
+
+    async fn check_level(&self, record: RecordId, level: u8, respect_lock: bool) -> Result<()> {
+        let respect_lock = respect_lock && level > Access::Read as u8;
+        if self.full_access && !respect_lock {
+            return Ok(());
+        }
+        let found = self
+            .inner
+            .query(
+                "LET $checklist = IF record::tb($record) = 'item' THEN $record.checklist ELSE $record END;
+                 RETURN {
+                     level: fn::access_level($checklist, $user),
+                     locked: $checklist.locked = true,
+                 };",
+            )
+            .bind(("record", record))
+            .bind(("user", self.user_record()))
+            .await
+            .map_err(Error::surreal("checking access"))?
+            .take::<Option<Found>>(1)
+            .map_err(Error::surreal("checking access"))?
+            .unwrap_or(Found {
+                level: OWNER,
+                locked: false,
+            });
+        if !self.full_access && found.level < level {
+            return Err(Error::Forbidden);
+        }
+        if respect_lock && found.locked {
+            return Err(Error::Locked);
+        }
+        Ok(())
+    }
+}
+
+/// What [`Db::check_level`] finds out about a checklist.
+#[derive(Debug, serde::Deserialize)]
+struct Found {
+    level: u8,
+    locked: bool,
+}
+
+/// A query condition which holds for records to which `db` has at least `access`, given the
+/// expression for the checklist each record is in.
+///
+/// Queries using it must bind `$user` to [`Db::user_record`].
+pub(crate) fn permits(db: &Db, checklist: &str, access: Access) -> String {
+    match db.full_access {
+        true => "true".to_owned(),
+        false => format!("fn::access_level({checklist}, $user) >= {}", access as u8),
+    }
+}
+
+impl Checklist {
+    /// Give `user` `access` to a checklist, replacing any access they had before.
+    ///
+    /// Only the owner may share a checklist.
+    pub async fn share(db: &Db, id: ChecklistId, user: UserId, access: Access) -> Result<()> {
+        db.authorize_owner(&id).await?;
+        let mut txn = Transaction::begin(db);
+        let checklist = txn.bind(RecordId::from(id));
+        let user = txn.bind(RecordId::from(user));
+        let granted = txn.bind(access);
+        txn.push(format!(
+            "DELETE {SHARE_TABLE} WHERE checklist = {checklist} AND user = {user}"
+        ));
+        txn.push(format!(
+            "CREATE {SHARE_TABLE} SET checklist = {checklist}, user = {user}, access = {granted}"
+        ));
+        txn.commit("sharing checklist").await
+    }
+
+    /// Take away any access `user` was granted to a checklist.
+    ///
+    /// Only the owner may unshare a checklist.
+    pub async fn unshare(db: &Db, id: ChecklistId, user: UserId) -> Result<()> {
+        db.authorize_owner(&id).await?;
+        db.inner
+            .query(format!(
+                "DELETE {SHARE_TABLE} WHERE checklist = $checklist AND user = $user"
+            ))
+            .bind(("checklist", RecordId::from(id)))
+            .bind(("user", RecordId::from(user)))
+            .await
+            .map_err(Error::surreal("unsharing checklist"))?
+            .check()
+            .map_err(Error::surreal("unsharing checklist"))?;
+        Ok(())
+    }
+
+    /// Everyone other than the owner who has access to a checklist.
+    pub async fn grants(db: &Db, id: ChecklistId) -> Result<Vec<Grant>> {
+        db.authorize(&id, Access::Read).await?;
+        db.inner
+            .query(format!(
+                "SELECT user, access FROM {SHARE_TABLE} WHERE checklist = $checklist ORDER BY user"
+            ))
+            .bind(("checklist", RecordId::from(id)))
+            .await
+            .map_err(Error::surreal("loading grants"))?
+            .take(0)
+            .map_err(Error::surreal("loading grants"))
+    }
+}
+
+/// Delete every grant on `checklist` as part of `txn`.
+pub(crate) fn delete_grants_in(txn: &mut Transaction<'_>, checklist: &ChecklistId) {
+    let record = txn.bind(RecordId::from(checklist.clone()));
+    txn.push(format!("DELETE {SHARE_TABLE} WHERE checklist = {record}"));
+}
+
+pub(crate) async fn delete_grants(db: &Db, checklist: &ChecklistId) -> Result<()> {
+    db.inner
+        .query(format!("DELETE {SHARE_TABLE} WHERE checklist = $checklist"))
+        .bind(("checklist", RecordId::from(checklist.clone())))
+        .await
+        .and_then(surrealdb::Response::check)
+        .map_err(Error::surreal("deleting grants"))?;
+    Ok(())
+}
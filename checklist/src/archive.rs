@@ -0,0 +1,91 @@
+//! Archiving checklists, and locking them against changes.
+//!
+//! Archived checklists work as before, but are left out of [`Checklist::all`] and
+//! [`ChecklistQuery`][crate::ChecklistQuery] unless asked for. A checklist can archive itself as
+//! soon as its last item is checked; see [`Checklist::set_auto_archive`].
+//!
+//! Nothing about a locked checklist or its items can be changed, not even with full access: such
+//! changes fail with [`Error::Locked`][crate::Error::Locked]. Only whether it is archived or locked
+//! can still change.
+
+use surrealdb::RecordId;
+
+use crate::{Access, Checklist, Db, Result, access, revision::update_if_current};
+
+#[derive(Debug, serde::Serialize)]
+struct SetArchived {
+    archived: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetLocked {
+    locked: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetAutoArchive {
+    auto_archive: bool,
+}
+
+impl Checklist {
+    async fn update(
+        &mut self,
+        db: &Db,
+        changes: impl serde::Serialize + 'static,
+        context: &'static str,
+    ) -> Result<()> {
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            changes,
+            context,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Hide this checklist from listings.
+    ///
+    /// Fails with [`Error::Conflict`][crate::Error::Conflict] if the checklist has changed since
+    /// it was loaded.
+    pub async fn archive(&mut self, db: &Db) -> Result<()> {
+        db.authorize_despite_lock(&self.id, Access::Edit as u8)
+            .await?;
+        self.update(db, SetArchived { archived: true }, "archiving checklist")
+            .await
+    }
+
+    /// List this checklist again.
+    pub async fn unarchive(&mut self, db: &Db) -> Result<()> {
+        db.authorize_despite_lock(&self.id, Access::Edit as u8)
+            .await?;
+        self.update(db, SetArchived { archived: false }, "unarchiving checklist")
+            .await
+    }
+
+    /// Make this checklist and its items read-only until it is unlocked.
+    ///
+    /// Only the owner may lock or unlock a checklist.
+    pub async fn lock(&mut self, db: &Db) -> Result<()> {
+        db.authorize_despite_lock(&self.id, access::OWNER).await?;
+        self.update(db, SetLocked { locked: true }, "locking checklist")
+            .await
+    }
+
+    pub async fn unlock(&mut self, db: &Db) -> Result<()> {
+        db.authorize_despite_lock(&self.id, access::OWNER).await?;
+        self.update(db, SetLocked { locked: false }, "unlocking checklist")
+            .await
+    }
+
+    /// Whether to archive this checklist once all of its items are checked.
+    ///
+    /// Checking the last item archives the checklist; setting this on a checklist whose items are
+    /// all checked already does not.
+    pub async fn set_auto_archive(&mut self, db: &Db, auto_archive: bool) -> Result<()> {
+        db.authorize(&self.id, Access::Edit).await?;
+        self.update(db, SetAutoArchive { auto_archive }, "setting auto-archive")
+            .await
+    }
+}
@@ -0,0 +1,44 @@
+//! Assigning items to users.
+//!
+//! An item has at most one assignee. Assignment says who should do an item; it grants no access, so
+//! an item may be assigned to someone who cannot yet see its checklist. Like users, assignments are
+//! local to a database and are not synced.
+
+use surrealdb::RecordId;
+
+use crate::{Access, Db, Item, ItemQuery, ItemSort, Result, UserId, revision::update_if_current};
+
+#[derive(Debug, serde::Serialize)]
+struct SetAssignee {
+    assignee: Option<UserId>,
+}
+
+impl Item {
+    /// Assign this item to `assignee`, or unassign it.
+    ///
+    /// Fails with [`Error::Conflict`][crate::Error::Conflict] if the item has changed since it was
+    /// loaded.
+    pub async fn assign(&mut self, db: &Db, assignee: Option<UserId>) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            SetAssignee { assignee },
+            "assigning item",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Unchecked items across all checklists which are assigned to `user`, oldest first.
+    pub async fn assigned_to(db: &Db, user: UserId) -> Result<Vec<Self>> {
+        ItemQuery::new()
+            .checked(false)
+            .assigned_to(user)
+            .sort(ItemSort::Created)
+            .fetch(db)
+            .await
+            .map(|page| page.items)
+    }
+}
@@ -0,0 +1,223 @@
+//! Files attached to items.
+//!
+//! Attachments are meant for small files such as screenshots and logs, up to
+//! [`Attachment::MAX_SIZE`]. Their contents are stored in the database as encrypted blobs, named
+//! by a keyed hash of the contents (see [`AttachmentKey`][crate::keys::AttachmentKey]), so a file
+//! attached many times, to however many items, is stored once. A blob is deleted along with the
+//! last attachment which uses it, and attachments are deleted along with their item.
+//!
+//! Attachments are neither copied with their checklist nor synced.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use surrealdb::{RecordId, RecordIdKey};
+
+use crate::{
+    Access, Db, Error, Item, ItemId, Result, UserId, datetime,
+    txn::{Transaction, new_key},
+};
+
+pub(crate) const ATTACHMENT_TABLE: &str = "attachment";
+const BLOB_TABLE: &str = "blob";
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    derive_more::Constructor,
+    derive_more::Deref,
+    derive_more::From,
+    derive_more::Into,
+    derive_more::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(try_from = "RecordId", into = "RecordId")]
+pub struct AttachmentId(RecordIdKey);
+
+impl FromStr for AttachmentId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(s.into()))
+    }
+}
+
+impl From<AttachmentId> for RecordId {
+    fn from(value: AttachmentId) -> Self {
+        RecordId::from_table_key(ATTACHMENT_TABLE, value.0)
+    }
+}
+
+impl TryFrom<RecordId> for AttachmentId {
+    type Error = Error;
+
+    fn try_from(value: RecordId) -> std::result::Result<Self, Self::Error> {
+        if value.table() != ATTACHMENT_TABLE {
+            return Err(Error::WrongRecordId {
+                expected: ATTACHMENT_TABLE,
+                got: value.table().to_owned(),
+            });
+        }
+        Ok(Self(value.key().to_owned()))
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct InsertBlob {
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StoredBlob {
+    digest: String,
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct InsertAttachment {
+    item: ItemId,
+    blob: RecordId,
+    name: String,
+    size: usize,
+    uploader: Option<UserId>,
+}
+
+/// A file attached to an item.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Attachment {
+    pub id: AttachmentId,
+    pub item: ItemId,
+    /// The file name it was attached under; not necessarily unique.
+    pub name: String,
+    /// Size of the contents in bytes.
+    pub size: u64,
+    /// The user who attached the file, if any.
+    #[serde(default)]
+    pub uploader: Option<UserId>,
+    #[serde(with = "datetime::required")]
+    pub created_at: DateTime<Utc>,
+    blob: RecordId,
+}
+
+impl Attachment {
+    /// The largest file which can be attached, in bytes.
+    pub const MAX_SIZE: usize = 1024 * 1024;
+
+    /// Attach a file called `name` with the given contents to `item`, uploaded by the acting user.
+    ///
+    /// Anyone who may check the item may attach files to it.
+    pub async fn add(
+        db: &Db,
+        item: ItemId,
+        name: impl Into<String>,
+        contents: &[u8],
+    ) -> Result<Self> {
+        if contents.len() > Self::MAX_SIZE {
+            return Err(Error::AttachmentTooLarge {
+                size: contents.len(),
+                limit: Self::MAX_SIZE,
+            });
+        }
+        db.authorize_item(&item, Access::Check).await?;
+        if Item::load(db, item.clone()).await?.is_none() {
+            return Err(Error::MissingItem);
+        }
+
+        let digest = db.attachment_key.digest(contents);
+        let blob = RecordId::from_table_key(BLOB_TABLE, digest.as_str());
+        let id = AttachmentId::new(new_key());
+
+        let mut txn = Transaction::begin(db);
+        let blob_record = txn.bind(blob.clone());
+        let blob_content = txn.bind(InsertBlob {
+            data: db.attachment_key.encrypt(contents, &digest),
+        });
+        txn.push(format!(
+            "IF !record::exists({blob_record}) {{ CREATE {blob_record} CONTENT {blob_content} }}"
+        ));
+        let record = txn.bind(RecordId::from(id.clone()));
+        let content = txn.bind(InsertAttachment {
+            item,
+            blob,
+            name: name.into(),
+            size: contents.len(),
+            uploader: db.user().cloned(),
+        });
+        txn.push(format!("CREATE {record} CONTENT {content}"));
+        txn.commit("adding attachment").await?;
+
+        Self::load(db, id).await?.ok_or(Error::FailedCreate {
+            resource: ATTACHMENT_TABLE,
+        })
+    }
+
+    pub async fn load(db: &Db, id: AttachmentId) -> Result<Option<Self>> {
+        let attachment = db
+            .inner
+            .select::<Option<Self>>(RecordId::from(id))
+            .await
+            .map_err(Error::surreal("loading attachment"))?;
+        if let Some(attachment) = &attachment {
+            db.authorize_item(&attachment.item, Access::Read).await?;
+        }
+        Ok(attachment)
+    }
+
+    /// Files attached to `item`, oldest first.
+    pub async fn of(db: &Db, item: ItemId) -> Result<Vec<Self>> {
+        db.authorize_item(&item, Access::Read).await?;
+        db.inner
+            .query(format!(
+                "SELECT * FROM {ATTACHMENT_TABLE} WHERE item = $item ORDER BY created_at, id"
+            ))
+            .bind(("item", RecordId::from(item)))
+            .await
+            .map_err(Error::surreal("loading attachments"))?
+            .take(0)
+            .map_err(Error::surreal("loading attachments"))
+    }
+
+    /// The contents of this attachment.
+    pub async fn read(&self, db: &Db) -> Result<Vec<u8>> {
+        db.authorize_item(&self.item, Access::Read).await?;
+        let blob = db
+            .inner
+            .query("SELECT record::id(id) AS digest, data FROM ONLY $blob")
+            .bind(("blob", self.blob.clone()))
+            .await
+            .map_err(Error::surreal("loading attachment contents"))?
+            .take::<Option<StoredBlob>>(0)
+            .map_err(Error::surreal("loading attachment contents"))?
+            .ok_or(Error::MissingItem)?;
+        db.attachment_key.decrypt(&blob.data, &blob.digest)
+    }
+
+    /// Remove an attachment from its item.
+    ///
+    /// Uploaders may remove their own attachments as long as they may still check the item;
+    /// removing anyone else's needs edit access.
+    pub async fn remove(db: &Db, id: AttachmentId) -> Result<()> {
+        let Some(attachment) = Self::load(db, id.clone()).await? else {
+            return Ok(());
+        };
+        let access = if attachment.uploader.is_some() && attachment.uploader.as_ref() == db.user() {
+            Access::Check
+        } else {
+            Access::Edit
+        };
+        db.authorize_item(&attachment.item, access).await?;
+        db.inner
+            .delete::<Option<Self>>(RecordId::from(id))
+            .await
+            .map_err(Error::surreal("removing attachment"))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Many changes applied at once.
+//!
+//! Each change made through a [`Batch`] is only recorded; nothing reaches the database until
+//! [`Batch::commit`], which sends every change in one round-trip and one transaction. If any change
+//! fails, none of them are applied. The acting user's permissions for every change are checked
+//! before anything is sent.
+//!
+//! Changes given a loaded record are conditional, as they are outside a batch: if the record has
+//! changed since it was loaded, the batch fails with [`Error::Conflict`].
+
+use std::borrow::Cow;
+
+use surrealdb::RecordId;
+
+use crate::{
+    Access, ChecklistId, Db, Error, Item, ItemId, Result, access, copy, end_position,
+    keys::DataKey,
+    txn::{Transaction, new_key},
+};
+
+/// A set of changes which are applied together or not at all.
+///
+/// Created by [`Db::batch`]. Records created in a batch get their ids up front, so later changes
+/// in the same batch can refer to them; they do not exist until the batch is committed.
+pub struct Batch<'db> {
+    db: &'db Db,
+    txn: Transaction<'db>,
+    /// Access level the acting user needs to each existing checklist or item the batch changes.
+    required: Vec<(RecordId, u8)>,
+    /// Revision each loaded record the batch changes must still be at.
+    expected: Vec<(RecordId, u64)>,
+}
+
+impl Db {
+    /// Start a batch of changes.
+    pub fn batch(&self) -> Batch<'_> {
+        Batch {
+            db: self,
+            txn: Transaction::begin(self),
+            required: Vec::new(),
+            expected: Vec::new(),
+        }
+    }
+
+    /// Run `changes` against a new batch, then commit it.
+    ///
+    /// If `changes` returns an error, nothing is committed.
+    pub async fn transaction<T>(
+        &self,
+        changes: impl FnOnce(&mut Batch<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let mut batch = self.batch();
+        let out = changes(&mut batch)?;
+        batch.commit().await?;
+        Ok(out)
+    }
+}
+
+impl Batch<'_> {
+    /// Fail the batch unless `record` exists when this point is reached.
+    fn require(&mut self, record: &str) {
+        self.txn.push(format!(
+            "IF !record::exists({record}) {{ THROW \"no such record: \" + <string> {record} }}"
+        ));
+    }
+
+    /// Fail the batch unless `record`, bound as `param`, is still at revision `expected` when this
+    /// point is reached.
+    fn require_revision(&mut self, param: &str, record: RecordId, expected: u64) {
+        self.txn.push(format!(
+            "IF ({param}.revision ?? 0) != {expected} {{ THROW \"changed since it was loaded: \" + <string> {param} }}"
+        ));
+        self.expected.push((record, expected));
+    }
+
+    fn require_access(&mut self, record: impl Into<RecordId>, access: Access) {
+        self.required.push((record.into(), access as u8));
+    }
+
+    /// Create a checklist owned by the acting user, returning the id it will have.
+    pub fn new_checklist(&mut self, name: impl Into<Cow<'static, str>>) -> ChecklistId {
+        let id = ChecklistId::new(new_key());
+        let record = self.txn.bind(RecordId::from(id.clone()));
+        let name = self.txn.bind(name.into().into_owned());
+        let owner = self.txn.bind(self.db.user_record());
+        self.txn.push(format!(
+            "CREATE {record} SET name = {name}, owner = {owner}"
+        ));
+        DataKey::create_in(&mut self.txn, self.db, &id);
+        id
+    }
+
+    /// Add an item to the end of `checklist`, returning the id it will have.
+    pub fn new_item(
+        &mut self,
+        checklist: ChecklistId,
+        item: impl Into<Cow<'static, str>>,
+    ) -> ItemId {
+        self.require_access(checklist.clone(), Access::Edit);
+        let checklist = self.txn.bind(RecordId::from(checklist));
+        self.require(&checklist);
+        self.create_item(&checklist, "NONE", item.into())
+    }
+
+    /// Add an item nested under `parent`, in the parent's checklist, returning the id it will have.
+    pub fn new_child(&mut self, parent: ItemId, item: impl Into<Cow<'static, str>>) -> ItemId {
+        self.require_access(parent.clone(), Access::Edit);
+        let parent = self.txn.bind(RecordId::from(parent));
+        self.require(&parent);
+        self.create_item(&format!("{parent}.checklist"), &parent, item.into())
+    }
+
+    fn create_item(&mut self, checklist: &str, parent: &str, item: Cow<'static, str>) -> ItemId {
+        let id = ItemId::new(new_key());
+        let record = self.txn.bind(RecordId::from(id.clone()));
+        let item = self.txn.bind(item.into_owned());
+        self.txn.push(format!(
+            "CREATE {record} SET
+                 checklist = {checklist},
+                 parent = {parent},
+                 item = {item},
+                 position = {}",
+            end_position(checklist)
+        ));
+        id
+    }
+
+    /// Change the text of `item`, unless it has changed since it was loaded.
+    pub fn set_text(&mut self, item: &Item, text: impl Into<Cow<'static, str>>) {
+        self.require_access(item.id.clone(), Access::Edit);
+        let record = self.txn.bind(RecordId::from(item.id.clone()));
+        self.require(&record);
+        self.require_revision(&record, item.id.clone().into(), item.revision);
+        let text = self.txn.bind(text.into().into_owned());
+        self.txn.push(format!("UPDATE {record} SET item = {text}"));
+    }
+
+    /// Check or uncheck `item`, unless it has changed since it was loaded.
+    pub fn set_checked(&mut self, item: &Item, checked: bool) {
+        self.require_access(item.id.clone(), Access::Check);
+        let record = self.txn.bind(RecordId::from(item.id.clone()));
+        self.require(&record);
+        self.require_revision(&record, item.id.clone().into(), item.revision);
+        let checked = self.txn.bind(checked);
+        self.txn
+            .push(format!("UPDATE {record} SET checked = {checked}"));
+    }
+
+    /// Delete `item`, along with any items nested under it.
+    pub fn delete_item(&mut self, item: ItemId) {
+        self.require_access(item.clone(), Access::Edit);
+        let record = self.txn.bind(RecordId::from(item));
+        self.txn.push(format!("DELETE {record}"));
+    }
+
+    /// Delete `checklist`, which the acting user must own.
+    pub fn delete_checklist(&mut self, checklist: ChecklistId) {
+        self.required
+            .push((checklist.clone().into(), access::OWNER));
+        copy::delete_in(&mut self.txn, &checklist);
+    }
+
+    /// Apply every change, or none of them if any fails.
+    pub async fn commit(self) -> Result<()> {
+        for (record, level) in self.required {
+            self.db.require_level(record, level).await?;
+        }
+        let Err(err) = self.txn.commit("committing batch").await else {
+            return Ok(());
+        };
+        // a record which changed is the likeliest cause, and the most useful one to report
+        for (record, expected) in self.expected {
+            let actual = self
+                .db
+                .inner
+                .query("SELECT VALUE revision ?? 0 FROM ONLY $record")
+                .bind(("record", record))
+                .await
+                .map_err(Error::surreal("committing batch"))?
+                .take::<Option<u64>>(0)
+                .map_err(Error::surreal("committing batch"))?;
+            if let Some(actual) = actual.filter(|actual| *actual != expected) {
+                return Err(Error::Conflict { expected, actual });
+            }
+        }
+        Err(err)
+    }
+}
@@ -0,0 +1,385 @@
+//! Duplicating checklists, merging one checklist into another, and moving items between checklists.
+//!
+//! These operations touch many records, so each runs as a single [`Transaction`]: either every
+//! record is copied or moved, or none is.
+
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Access, CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, ITEM_TABLE, Item, ItemId, Priority,
+    Recurrence, Result, UserId, access, datetime, end_position,
+    keys::DataKey,
+    recurrence::RUN_TABLE,
+    txn::{Transaction, new_key},
+};
+
+/// Everything stored for an item, so that it can be copied faithfully.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct ItemRecord {
+    id: ItemId,
+    checklist: ChecklistId,
+    #[serde(default)]
+    parent: Option<ItemId>,
+    item: String,
+    checked: bool,
+    position: i64,
+    #[serde(default, with = "datetime::option")]
+    due_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "datetime::option")]
+    remind_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    reminded: bool,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    assignee: Option<UserId>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TagEdge {
+    #[serde(rename = "in")]
+    pub(crate) tagged: RecordId,
+    pub(crate) out: RecordId,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ChecklistRecord {
+    name: String,
+    is_template: bool,
+    recurrence: Option<Recurrence>,
+    #[serde(with = "datetime::option")]
+    period_start: Option<DateTime<Utc>>,
+    owner: Option<UserId>,
+    auto_archive: bool,
+}
+
+/// The id which `id` maps to in `mapping`.
+fn mapped<'a>(mapping: &'a [(ItemId, ItemId)], id: &ItemId) -> Option<&'a ItemId> {
+    mapping
+        .iter()
+        .find_map(|(from, to)| (from == id).then_some(to))
+}
+
+/// Items of `checklist`, in checklist order.
+async fn items_of(db: &Db, checklist: &ChecklistId) -> Result<Vec<ItemRecord>> {
+    db.inner
+        .query(format!(
+            "SELECT id, checklist, parent, item, checked, position, due_at, remind_at, reminded,
+                 priority, assignee
+             FROM {ITEM_TABLE} WHERE checklist = $checklist ORDER BY position"
+        ))
+        .bind(("checklist", RecordId::from(checklist.clone())))
+        .await
+        .map_err(Error::surreal("loading checklist items"))?
+        .take(0)
+        .map_err(Error::surreal("loading checklist items"))
+}
+
+/// Tag edges of `checklist` and of its items.
+pub(crate) async fn tags_of(db: &Db, checklist: &ChecklistId) -> Result<Vec<TagEdge>> {
+    db.inner
+        .query("SELECT in, out FROM tagged WHERE in = $checklist OR in.checklist = $checklist")
+        .bind(("checklist", RecordId::from(checklist.clone())))
+        .await
+        .map_err(Error::surreal("loading checklist tags"))?
+        .take(0)
+        .map_err(Error::surreal("loading checklist tags"))
+}
+
+/// Delete `checklist` and everything which belongs to it other than its items, as part of `txn`.
+pub(crate) fn delete_in(txn: &mut Transaction<'_>, checklist: &ChecklistId) {
+    DataKey::delete_in(txn, checklist);
+    access::delete_grants_in(txn, checklist);
+    let record = txn.bind(RecordId::from(checklist.clone()));
+    txn.push(format!("DELETE {RUN_TABLE} WHERE checklist = {record}"));
+    txn.push(format!("DELETE tagged WHERE in = {record}"));
+    txn.push(format!("DELETE {record}"));
+}
+
+/// Tag `target` with `tag` as part of `txn`, unless it already is.
+fn tag_in(txn: &mut Transaction<'_>, target: RecordId, tag: RecordId) {
+    let target = txn.bind(target);
+    let tag = txn.bind(tag);
+    txn.push(format!(
+        "IF (SELECT VALUE id FROM tagged WHERE in = {target} AND out = {tag}).len() = 0 {{ RELATE {target}->tagged->{tag} }}"
+    ));
+}
+
+impl Checklist {
+    /// Create a deep copy of checklist `id`: its items, nesting, order, tags, due dates,
+    /// priorities, assignees and schedule.
+    ///
+    /// Everything gets new ids, and the copy belongs to the acting user. When `reset_checked` is
+    /// set, no item of the copy is checked.
+    pub async fn duplicate(db: &Db, id: ChecklistId, reset_checked: bool) -> Result<Self> {
+        let source = Self::load(db, id.clone())
+            .await?
+            .ok_or(Error::MissingItem)?;
+        let items = items_of(db, &id).await?;
+        let tags = tags_of(db, &id).await?;
+
+        let checklist = ChecklistId::new(new_key());
+        let new_ids = items
+            .iter()
+            .map(|item| (item.id.clone(), ItemId::new(new_key())))
+            .collect::<Vec<_>>();
+
+        let items = items
+            .into_iter()
+            .map(|item| ItemRecord {
+                id: mapped(&new_ids, &item.id)
+                    .expect("every item has a new id")
+                    .clone(),
+                checklist: checklist.clone(),
+                parent: item
+                    .parent
+                    .and_then(|parent| mapped(&new_ids, &parent).cloned()),
+                checked: item.checked && !reset_checked,
+                reminded: item.reminded && !reset_checked,
+                ..item
+            })
+            .collect::<Vec<_>>();
+        let tags = tags
+            .into_iter()
+            .filter_map(|edge| {
+                let tagged = if edge.tagged == RecordId::from(id.clone()) {
+                    checklist.clone().into()
+                } else {
+                    mapped(&new_ids, &ItemId::try_from(edge.tagged).ok()?)?
+                        .clone()
+                        .into()
+                };
+                Some(TagEdge {
+                    tagged,
+                    out: edge.out,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut txn = Transaction::begin(db);
+        let record = txn.bind(RecordId::from(checklist.clone()));
+        let content = txn.bind(ChecklistRecord {
+            name: source.name.into_owned(),
+            is_template: source.is_template,
+            recurrence: source.recurrence,
+            period_start: source.period_start,
+            owner: db.user.clone(),
+            auto_archive: source.auto_archive,
+        });
+        txn.push(format!("CREATE {record} CONTENT {content}"));
+        DataKey::create_in(&mut txn, db, &checklist);
+        if !items.is_empty() {
+            let items = txn.bind(items);
+            txn.push(format!("INSERT INTO {ITEM_TABLE} {items}"));
+        }
+        if !tags.is_empty() {
+            let tags = txn.bind(tags);
+            txn.push(format!("INSERT RELATION INTO tagged {tags}"));
+        }
+        txn.commit("duplicating checklist").await?;
+
+        Self::load(db, checklist).await?.ok_or(Error::FailedCreate {
+            resource: CHECKLIST_TABLE,
+        })
+    }
+
+    /// Move every item of checklist `source` into checklist `target`, then delete `source`.
+    ///
+    /// Moved items go after the target's items, keeping their order and nesting. An item whose
+    /// text is identical to one of its new siblings is dropped in favour of that sibling, which is
+    /// kept as it is; its children move under that sibling instead. The source's tags are added
+    /// to the target. Returns the target.
+    ///
+    /// Since the source is deleted, only its owner may merge it.
+    pub async fn merge_into(db: &Db, source: ChecklistId, target: ChecklistId) -> Result<Self> {
+        if source == target {
+            return Err(Error::MergeIntoSelf);
+        }
+        db.authorize_owner(&source).await?;
+        db.authorize(&target, Access::Edit).await?;
+        Self::load(db, source.clone())
+            .await?
+            .ok_or(Error::MissingItem)?;
+        Self::load(db, target.clone())
+            .await?
+            .ok_or(Error::MissingItem)?;
+        let source_items = items_of(db, &source).await?;
+        let source_tags = tags_of(db, &source).await?;
+        let target_items = items_of(db, &target).await?;
+
+        // (parent, text) of every item in the merged checklist, and the item which has it
+        let mut siblings = target_items
+            .iter()
+            .map(|item| ((item.parent.clone(), item.item.clone()), item.id.clone()))
+            .collect::<Vec<_>>();
+        let mut position = target_items
+            .iter()
+            .map(|item| item.position + 1)
+            .max()
+            .unwrap_or(0);
+
+        // items are in checklist order, but a parent may come after its children; place parents first
+        let mut pending = source_items;
+        let mut placed = Vec::<(ItemId, ItemId)>::new();
+        let mut moves = Vec::new();
+        let mut drops = Vec::new();
+        while !pending.is_empty() {
+            let (ready, waiting) = pending.into_iter().partition::<Vec<_>, _>(|item| {
+                item.parent
+                    .as_ref()
+                    .is_none_or(|parent| mapped(&placed, parent).is_some())
+            });
+            if ready.is_empty() {
+                // only possible if the source's nesting is inconsistent; flatten what remains
+                moves.extend(waiting.into_iter().map(|item| (item, None)));
+                break;
+            }
+            for item in ready {
+                let parent = item
+                    .parent
+                    .as_ref()
+                    .and_then(|parent| mapped(&placed, parent).cloned());
+                let key = (parent.clone(), item.item.clone());
+                match siblings.iter().find(|(existing, _)| *existing == key) {
+                    Some((_, existing)) => {
+                        placed.push((item.id.clone(), existing.clone()));
+                        drops.push(item.id);
+                    }
+                    None => {
+                        placed.push((item.id.clone(), item.id.clone()));
+                        siblings.push((key, item.id.clone()));
+                        moves.push((item, parent));
+                    }
+                }
+            }
+            pending = waiting;
+        }
+        moves.sort_by_key(|(item, _)| item.position);
+
+        let mut txn = Transaction::begin(db);
+        let target_record = RecordId::from(target.clone());
+        let checklist = txn.bind(target_record.clone());
+        for (item, parent) in moves {
+            let id = txn.bind(RecordId::from(item.id));
+            let parent = txn.bind(parent.map(RecordId::from));
+            let new_position = txn.bind(position);
+            position += 1;
+            txn.push(format!(
+                "UPDATE {id} SET checklist = {checklist}, parent = {parent}, position = {new_position}"
+            ));
+        }
+        // children of dropped items have been moved away already, so these deletes do not cascade
+        for id in drops {
+            let id = txn.bind(RecordId::from(id));
+            txn.push(format!("DELETE {id}"));
+        }
+        let source_record = RecordId::from(source.clone());
+        for edge in source_tags {
+            if edge.tagged == source_record {
+                tag_in(&mut txn, target_record.clone(), edge.out);
+            }
+        }
+        delete_in(&mut txn, &source);
+        txn.commit("merging checklists").await?;
+
+        Self::load(db, target).await?.ok_or(Error::MissingItem)
+    }
+}
+
+impl Item {
+    /// Move this item, along with everything nested under it, to the end of checklist `target`.
+    ///
+    /// It becomes a top-level item there. An item already in `target` stays where it is, and is
+    /// only reloaded.
+    pub async fn move_to_checklist(&mut self, db: &Db, target: ChecklistId) -> Result<()> {
+        let id = self.id.clone();
+        let moved = Self::move_many_to_checklist(db, vec![id.clone()], target)
+            .await?
+            .into_iter()
+            .find(|item| item.id == id);
+        *self = match moved {
+            Some(item) => item,
+            None => Self::load(db, id).await?.ok_or(Error::MissingItem)?,
+        };
+        Ok(())
+    }
+
+    /// Move each of `ids`, along with everything nested under them, to the end of checklist
+    /// `target`, all at once.
+    ///
+    /// Moved items keep their relative order. An item stays nested under its parent if that moves
+    /// too, and otherwise becomes a top-level item. Items already in `target` stay where they are.
+    /// Returns every moved item, including nested ones, in their new order.
+    pub async fn move_many_to_checklist(
+        db: &Db,
+        ids: Vec<ItemId>,
+        target: ChecklistId,
+    ) -> Result<Vec<Self>> {
+        db.authorize(&target, Access::Edit).await?;
+        Checklist::load(db, target.clone())
+            .await?
+            .ok_or(Error::MissingItem)?;
+
+        let mut sources = Vec::<ChecklistId>::new();
+        for id in &ids {
+            db.authorize_item(id, Access::Edit).await?;
+            let item = Self::load(db, id.clone())
+                .await?
+                .ok_or(Error::MissingItem)?;
+            if item.checklist != target && !sources.contains(&item.checklist) {
+                sources.push(item.checklist);
+            }
+        }
+
+        // everything under a moved item moves with it; parents may come after their children,
+        // so repeat until nothing more is found
+        let mut moving = Vec::new();
+        for source in &sources {
+            let mut items = items_of(db, source).await?;
+            let mut found = true;
+            while found {
+                let (now_moving, rest) = items.into_iter().partition::<Vec<_>, _>(|item| {
+                    ids.contains(&item.id)
+                        || item.parent.as_ref().is_some_and(|parent| {
+                            moving.iter().any(|moved: &ItemRecord| moved.id == *parent)
+                        })
+                });
+                found = !now_moving.is_empty();
+                moving.extend(now_moving);
+                items = rest;
+            }
+        }
+        // keep the order of source checklists, then of positions within each
+        moving.sort_by_key(|item| {
+            let source = sources.iter().position(|source| *source == item.checklist);
+            (source, item.position)
+        });
+
+        let mut txn = Transaction::begin(db);
+        let checklist = txn.bind(RecordId::from(target));
+        txn.push(format!("LET $end = {}", end_position(&checklist)));
+        let mut moved = Vec::with_capacity(moving.len());
+        for (offset, item) in moving.iter().enumerate() {
+            let parent = item
+                .parent
+                .clone()
+                .filter(|parent| moving.iter().any(|moved| moved.id == *parent));
+            let id = txn.bind(RecordId::from(item.id.clone()));
+            let parent = txn.bind(parent.map(RecordId::from));
+            txn.push(format!(
+                "UPDATE {id} SET checklist = {checklist}, parent = {parent}, position = $end + {offset}"
+            ));
+            moved.push(RecordId::from(item.id.clone()));
+        }
+        txn.commit("moving items").await?;
+
+        db.inner
+            .query("SELECT * FROM $items ORDER BY position")
+            .bind(("items", moved))
+            .await
+            .map_err(Error::surreal("loading moved items"))?
+            .take(0)
+            .map_err(Error::surreal("loading moved items"))
+    }
+}
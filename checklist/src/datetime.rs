@@ -0,0 +1,47 @@
+//! Serde adaptors for storing [`chrono`] datetimes as surrealdb datetimes.
+//!
+//! `chrono` serializes to strings, which schemafull tables reject for `datetime` fields.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize as _, Deserializer, Serialize as _, Serializer};
+
+/// Use as `#[serde(default, with = "crate::datetime::option")]`.
+///
+/// The `default` is required because surrealdb omits fields which are `NONE`.
+pub(crate) mod option {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value
+            .map(surrealdb::sql::Datetime::from)
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<surrealdb::sql::Datetime>::deserialize(deserializer)
+            .map(|datetime| datetime.map(|datetime| datetime.0))
+    }
+}
+
+/// Use as `#[serde(with = "crate::datetime::required")]`.
+pub(crate) mod required {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        surrealdb::sql::Datetime::from(*value).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        surrealdb::sql::Datetime::deserialize(deserializer).map(|datetime| datetime.0)
+    }
+}
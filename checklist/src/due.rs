@@ -0,0 +1,130 @@
+//! Due dates and reminders on items.
+//!
+//! A reminder fires once: after it has been delivered it is marked as such, and it only fires
+//! again if a new reminder time is set. Checked items neither become overdue nor fire reminders.
+
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Access, Db, Error, ITEM_TABLE, Item, ItemQuery, ItemSort, Result, access, datetime,
+    revision::update_if_current,
+};
+
+#[derive(Debug, serde::Serialize)]
+struct SetDue {
+    #[serde(with = "datetime::option")]
+    due_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetReminder {
+    #[serde(with = "datetime::option")]
+    remind_at: Option<DateTime<Utc>>,
+    reminded: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MarkReminded {
+    reminded: bool,
+}
+
+impl Item {
+    async fn merge(&self, db: &Db, data: impl serde::Serialize + 'static) -> Result<Self> {
+        db.inner
+            .update::<Option<Self>>(RecordId::from(self.id.clone()))
+            .merge(data)
+            .await
+            .map_err(Error::surreal("updating item"))?
+            .ok_or(Error::FailedUpdate {
+                resource: ITEM_TABLE,
+            })
+    }
+
+    /// Set or clear when this item is due.
+    ///
+    /// Fails with [`Error::Conflict`] if the item has changed since it was loaded.
+    pub async fn set_due(&mut self, db: &Db, due_at: Option<DateTime<Utc>>) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            SetDue { due_at },
+            "setting due date",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Set or clear when to be reminded of this item.
+    ///
+    /// Setting a reminder re-arms it, even if an earlier reminder already fired. Fails with
+    /// [`Error::Conflict`] if the item has changed since it was loaded.
+    pub async fn set_reminder(&mut self, db: &Db, remind_at: Option<DateTime<Utc>>) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        let reminder = SetReminder {
+            remind_at,
+            reminded: false,
+        };
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            reminder,
+            "setting reminder",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Record that this item's reminder has been delivered, so that it does not fire again.
+    pub async fn mark_reminded(&self, db: &Db) -> Result<()> {
+        db.authorize_item(&self.id, Access::Check).await?;
+        self.merge(db, MarkReminded { reminded: true }).await?;
+        Ok(())
+    }
+
+    /// Unchecked items across all checklists which were due before `now`, most overdue first.
+    pub async fn overdue(db: &Db, now: DateTime<Utc>) -> Result<Vec<Self>> {
+        ItemQuery::new()
+            .checked(false)
+            .due_before(now)
+            .sort(ItemSort::Due)
+            .fetch(db)
+            .await
+            .map(|page| page.items)
+    }
+
+    /// Unchecked items across all checklists which fall due from `now` until `until`, soonest first.
+    pub async fn upcoming(db: &Db, now: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<Self>> {
+        ItemQuery::new()
+            .checked(false)
+            .due_after(now)
+            .due_before(until)
+            .sort(ItemSort::Due)
+            .fetch(db)
+            .await
+            .map(|page| page.items)
+    }
+
+    /// Unchecked items whose reminder time has passed by `now` but which have not yet been reminded.
+    ///
+    /// Items in locked checklists are left out, as they could not be marked reminded.
+    pub async fn pending_reminders(db: &Db, now: DateTime<Utc>) -> Result<Vec<Self>> {
+        db.inner
+            .query(format!(
+                "SELECT * FROM {ITEM_TABLE}
+                 WHERE remind_at != NONE AND remind_at <= $now AND !reminded AND !checked
+                     AND checklist.locked != true AND {}
+                 ORDER BY remind_at",
+                access::permits(db, "checklist", Access::Read)
+            ))
+            .bind(("now", surrealdb::sql::Value::from(now)))
+            .bind(("user", db.user_record()))
+            .await
+            .map_err(Error::surreal("querying reminders"))?
+            .take(0)
+            .map_err(Error::surreal("querying reminders"))
+    }
+}
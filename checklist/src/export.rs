@@ -0,0 +1,176 @@
+//! Exporting single checklists for another party.
+//!
+//! An export is encrypted under the checklist's own data key, and carries that data key wrapped
+//! for the recipient. Nothing in an export depends on the exporter's master key.
+
+use surrealdb::RecordId;
+
+use crate::{
+    CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, ITEM_TABLE, ItemId, NewChecklist, Result,
+    keys::{DataKey, KeyEncryptionKey, WrappedKey, checklist_aad},
+    txn::{Transaction, new_key},
+};
+
+/// A checklist and its items, encrypted for a recipient.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExportedChecklist {
+    /// Record key of the checklist in the exporting database.
+    ///
+    /// Bound into both the wrapped key and the contents; the importing database assigns a new id.
+    pub checklist: String,
+    /// The checklist's data key, wrapped under the recipient's key.
+    pub key: WrappedKey,
+    /// The checklist's contents, encrypted under its data key.
+    #[serde(with = "serde_bytes")]
+    pub contents: Vec<u8>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Contents {
+    name: String,
+    items: Vec<ItemContents>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ItemContents {
+    item: String,
+    checked: bool,
+    /// Index of this item's parent within `Contents::items`.
+    #[serde(default)]
+    parent: Option<usize>,
+}
+
+/// An imported item, as inserted.
+#[derive(Debug, serde::Serialize)]
+struct NewItem {
+    id: ItemId,
+    checklist: ChecklistId,
+    parent: Option<ItemId>,
+    item: String,
+    checked: bool,
+    position: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ItemRow {
+    id: ItemId,
+    parent: Option<ItemId>,
+    item: String,
+    checked: bool,
+}
+
+impl Checklist {
+    /// Re-wrap this checklist's data key under `recipient_key`.
+    ///
+    /// Only the owner may hand out a checklist's key.
+    pub async fn export_key(db: &Db, id: ChecklistId, recipient_key: &[u8]) -> Result<WrappedKey> {
+        db.authorize_owner(&id).await?;
+        let key = DataKey::load(db, &id).await?;
+        Ok(key.wrap(&KeyEncryptionKey::derive(recipient_key), &id))
+    }
+
+    /// Export this checklist and its items such that they can be imported by the holder of `recipient_key`.
+    ///
+    /// Since the export carries the checklist's key, only the owner may export a checklist.
+    pub async fn export(
+        db: &Db,
+        id: ChecklistId,
+        recipient_key: &[u8],
+    ) -> Result<ExportedChecklist> {
+        db.authorize_owner(&id).await?;
+        let checklist = Self::load(db, id.clone())
+            .await?
+            .ok_or(Error::MissingItem)?;
+        let rows = db
+            .inner
+            .query("SELECT id, parent, item, checked, position FROM item WHERE checklist = $checklist ORDER BY position")
+            .bind(("checklist", RecordId::from(id.clone())))
+            .await
+            .map_err(Error::surreal("loading items for export"))?
+            .take::<Vec<ItemRow>>(0)
+            .map_err(Error::surreal("loading items for export"))?;
+        let items = rows
+            .iter()
+            .map(|row| ItemContents {
+                item: row.item.clone(),
+                checked: row.checked,
+                parent: row
+                    .parent
+                    .as_ref()
+                    .and_then(|parent| rows.iter().position(|row| &row.id == parent)),
+            })
+            .collect();
+
+        let contents = Contents {
+            name: checklist.name.into_owned(),
+            items,
+        };
+        let contents = serde_json::to_vec(&contents).map_err(Error::serialization("export"))?;
+
+        let key = DataKey::load(db, &id).await?;
+        let checklist = checklist_aad(&id);
+        Ok(ExportedChecklist {
+            contents: key.encrypt(&contents, checklist.as_bytes()),
+            key: key.wrap(&KeyEncryptionKey::derive(recipient_key), &id),
+            checklist,
+        })
+    }
+
+    /// Import a checklist exported for the holder of `recipient_key`.
+    ///
+    /// The imported checklist keeps its data key, now wrapped under this database's master key, and
+    /// belongs to the acting user. Either the whole checklist is imported, or nothing is.
+    pub async fn import(
+        db: &Db,
+        exported: &ExportedChecklist,
+        recipient_key: &[u8],
+    ) -> Result<Self> {
+        let key = DataKey::unwrap_bound(
+            &exported.key,
+            &KeyEncryptionKey::derive(recipient_key),
+            &exported.checklist,
+        )?;
+        let contents = key.decrypt(&exported.contents, exported.checklist.as_bytes())?;
+        let contents = serde_json::from_slice::<Contents>(&contents)
+            .map_err(Error::serialization("import"))?;
+
+        let checklist = ChecklistId::new(new_key());
+        let ids = contents
+            .items
+            .iter()
+            .map(|_| ItemId::new(new_key()))
+            .collect::<Vec<_>>();
+        let items = contents
+            .items
+            .into_iter()
+            .zip(&ids)
+            .zip(0..)
+            .map(|((contents, id), position)| NewItem {
+                id: id.clone(),
+                checklist: checklist.clone(),
+                parent: contents.parent.and_then(|parent| ids.get(parent).cloned()),
+                item: contents.item,
+                checked: contents.checked,
+                position,
+            })
+            .collect::<Vec<_>>();
+
+        let mut txn = Transaction::begin(db);
+        let record = txn.bind(RecordId::from(checklist.clone()));
+        let content = txn.bind(NewChecklist {
+            name: contents.name.into(),
+            owner: db.user.clone(),
+        });
+        txn.push(format!("CREATE {record} CONTENT {content}"));
+        key.store_in(&mut txn, db, &checklist);
+        if !items.is_empty() {
+            let items = txn.bind(items);
+            txn.push(format!("INSERT INTO {ITEM_TABLE} {items}"));
+        }
+        txn.commit("importing checklist").await?;
+
+        Self::load(db, checklist).await?.ok_or(Error::FailedCreate {
+            resource: CHECKLIST_TABLE,
+        })
+    }
+}
@@ -0,0 +1,419 @@
+//! Envelope encryption.
+//!
+//! Every checklist has its own data encryption key. Data keys are never stored in the clear:
+//! they are wrapped under a key encryption key derived from the master key passed to [`Db::new`],
+//! and the wrapped keys live in the `keys` table. Sharing a checklist therefore means handing over
+//! only its data key, re-wrapped for the recipient.
+//!
+//! Attachments are the exception: identical files are stored once however many checklists they
+//! are attached to, so they are encrypted under an [`AttachmentKey`] derived from the master key.
+//!
+//! The database also holds a check value: a known plaintext encrypted under the key encryption key,
+//! so that [`Db::new`] fails on the wrong master key instead of wrapping new data keys under it.
+
+use chacha20poly1305::{
+    AeadCore as _, KeyInit as _, XChaCha20Poly1305, XNonce,
+    aead::{Aead as _, OsRng, Payload},
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use surrealdb::RecordId;
+
+use crate::{ChecklistId, Db, Error, Result, txn::Transaction};
+
+pub(crate) const KEYS_TABLE: &str = "keys";
+const KEY_CHECK_TABLE: &str = "key_check";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const KEK_INFO: &[u8] = b"checklist key encryption key v1";
+const ATTACHMENT_INFO: &[u8] = b"checklist attachment key v1";
+const KEY_CHECK: &[u8] = b"checklist key check v1";
+
+/// Current version of the [`WrappedKey`] format.
+const WRAPPED_KEY_VERSION: u8 = 1;
+
+/// Key encryption key, which wraps and unwraps data keys.
+///
+/// Derived from arbitrary key material with HKDF-SHA256, so callers can supply keys of any length.
+#[derive(Clone)]
+pub(crate) struct KeyEncryptionKey(XChaCha20Poly1305);
+
+impl KeyEncryptionKey {
+    pub(crate) fn derive(key_material: &[u8]) -> Self {
+        let mut key = [0; KEY_LEN];
+        Hkdf::<Sha256>::new(None, key_material)
+            .expand(KEK_INFO, &mut key)
+            .expect("output length is valid for hkdf-sha256");
+        Self(XChaCha20Poly1305::new(&key.into()))
+    }
+
+    /// Encrypt the key check value; the nonce is prepended to the ciphertext.
+    fn seal_check(&self) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, KEY_CHECK)
+            .expect("encrypting in memory cannot fail");
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&ciphertext);
+        out
+    }
+
+    /// Whether `sealed` is the key check value encrypted under this key.
+    fn opens_check(&self, sealed: &[u8]) -> bool {
+        if sealed.len() < NONCE_LEN {
+            return false;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.0
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .is_ok_and(|plaintext| plaintext == KEY_CHECK)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct KeyCheck {
+    #[serde(with = "serde_bytes")]
+    sealed: Vec<u8>,
+}
+
+impl Db {
+    /// Fail with [`Error::Decrypt`] unless the master key is the one this database was created with.
+    ///
+    /// The first time, this stores the check value. Databases from before there were check values
+    /// are checked against one of their data keys instead, if they have any.
+    pub(crate) async fn verify_master_key(&self) -> Result<()> {
+        let context = "checking the encryption key";
+        let record = RecordId::from_table_key(KEY_CHECK_TABLE, "master");
+        let mut response = self
+            .inner
+            .query(format!(
+                "SELECT * FROM ONLY $record; (SELECT * FROM {KEYS_TABLE} LIMIT 1)[0];"
+            ))
+            .bind(("record", record.clone()))
+            .await
+            .map_err(Error::surreal(context))?;
+        let check = response
+            .take::<Option<KeyCheck>>(0)
+            .map_err(Error::surreal(context))?;
+        if let Some(check) = check {
+            if !self.kek.opens_check(&check.sealed) {
+                return Err(Error::Decrypt { context });
+            }
+            return Ok(());
+        }
+        let stored = response
+            .take::<Option<StoredKey>>(1)
+            .map_err(Error::surreal(context))?;
+        if let Some(stored) = stored {
+            DataKey::unwrap(&stored.wrapped, &self.kek, &stored.checklist)
+                .map_err(|_| Error::Decrypt { context })?;
+        }
+        self.inner
+            .upsert::<Option<KeyCheck>>(record)
+            .content(KeyCheck {
+                sealed: self.kek.seal_check(),
+            })
+            .await
+            .map_err(Error::surreal(context))?;
+        Ok(())
+    }
+}
+
+/// Key which encrypts attachments and names them by their contents.
+///
+/// Names are a keyed hash rather than a plain one, so that the database does not reveal whether it
+/// holds some known file.
+#[derive(Clone)]
+pub(crate) struct AttachmentKey {
+    cipher: XChaCha20Poly1305,
+    mac: Hmac<Sha256>,
+}
+
+impl AttachmentKey {
+    pub(crate) fn derive(key_material: &[u8]) -> Self {
+        let mut key = [0; 2 * KEY_LEN];
+        Hkdf::<Sha256>::new(None, key_material)
+            .expand(ATTACHMENT_INFO, &mut key)
+            .expect("output length is valid for hkdf-sha256");
+        let (cipher, mac) = key.split_at(KEY_LEN);
+        Self {
+            cipher: XChaCha20Poly1305::new_from_slice(cipher).expect("key length is valid"),
+            mac: <Hmac<Sha256> as Mac>::new_from_slice(mac)
+                .expect("hmac accepts keys of any length"),
+        }
+    }
+
+    /// The content address of `contents`, as lowercase hex.
+    pub(crate) fn digest(&self, contents: &[u8]) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(contents);
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Encrypt `contents`, whose digest is `digest`; the nonce is prepended to the ciphertext.
+    pub(crate) fn encrypt(&self, contents: &[u8], digest: &str) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: contents,
+                    aad: digest.as_bytes(),
+                },
+            )
+            .expect("encrypting in memory cannot fail");
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&ciphertext);
+        out
+    }
+
+    /// Decrypt data produced by [`Self::encrypt`].
+    pub(crate) fn decrypt(&self, data: &[u8], digest: &str) -> Result<Vec<u8>> {
+        let context = "decrypting attachment";
+        if data.len() < NONCE_LEN {
+            return Err(Error::Decrypt { context });
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: digest.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Decrypt { context })
+    }
+}
+
+/// A data key wrapped under some key encryption key.
+///
+/// Format: a one-byte version, then a 24-byte nonce, then the XChaCha20-Poly1305 ciphertext of the
+/// data key. The id of the checklist the key belongs to is bound in as associated data, so a wrapped
+/// key cannot be moved to another checklist undetected.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct WrappedKey(#[serde(with = "serde_bytes")] Vec<u8>);
+
+impl WrappedKey {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let wrapped = Self(bytes);
+        wrapped.parts()?;
+        Ok(wrapped)
+    }
+
+    /// Split into nonce and ciphertext, checking the version on the way.
+    fn parts(&self) -> Result<(&[u8], &[u8])> {
+        match self.0.split_first() {
+            Some((&WRAPPED_KEY_VERSION, rest)) if rest.len() > NONCE_LEN => {
+                Ok(rest.split_at(NONCE_LEN))
+            }
+            Some((&WRAPPED_KEY_VERSION, _)) | None => Err(Error::MalformedKey),
+            Some((&version, _)) => Err(Error::UnsupportedKeyVersion { version }),
+        }
+    }
+}
+
+/// Per-checklist data encryption key.
+#[derive(Clone)]
+pub(crate) struct DataKey(chacha20poly1305::Key);
+
+impl DataKey {
+    fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.0)
+    }
+
+    pub(crate) fn wrap(&self, kek: &KeyEncryptionKey, checklist: &ChecklistId) -> WrappedKey {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = checklist_aad(checklist);
+        let ciphertext = kek
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: self.0.as_slice(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .expect("encrypting a key in memory cannot fail");
+
+        let mut bytes = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+        bytes.push(WRAPPED_KEY_VERSION);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        WrappedKey(bytes)
+    }
+
+    pub(crate) fn unwrap(
+        wrapped: &WrappedKey,
+        kek: &KeyEncryptionKey,
+        checklist: &ChecklistId,
+    ) -> Result<Self> {
+        Self::unwrap_bound(wrapped, kek, &checklist_aad(checklist))
+    }
+
+    /// Unwrap a key bound to `aad`, as given by [`checklist_aad`].
+    pub(crate) fn unwrap_bound(
+        wrapped: &WrappedKey,
+        kek: &KeyEncryptionKey,
+        aad: &str,
+    ) -> Result<Self> {
+        let (nonce, ciphertext) = wrapped.parts()?;
+        let key = kek
+            .0
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Decrypt {
+                context: "unwrapping data key",
+            })?;
+        if key.len() != KEY_LEN {
+            return Err(Error::MalformedKey);
+        }
+        Ok(Self(*chacha20poly1305::Key::from_slice(&key)))
+    }
+
+    /// Encrypt `plaintext` under this key; the nonce is prepended to the ciphertext.
+    pub(crate) fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .expect("encrypting in memory cannot fail");
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&ciphertext);
+        out
+    }
+
+    /// Decrypt data produced by [`Self::encrypt`].
+    pub(crate) fn decrypt(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return Err(Error::Decrypt {
+                context: "decrypting data: too short",
+            });
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| Error::Decrypt {
+                context: "decrypting data",
+            })
+    }
+
+    /// Generate a data key for a new checklist and store it, wrapped under the master key.
+    pub(crate) async fn create(db: &Db, checklist: &ChecklistId) -> Result<Self> {
+        let key = Self::generate();
+        key.store(db, checklist).await?;
+        Ok(key)
+    }
+
+    /// Store this key as the data key for `checklist`, wrapped under the master key.
+    pub(crate) async fn store(&self, db: &Db, checklist: &ChecklistId) -> Result<()> {
+        let wrapped = self.wrap(&db.kek, checklist);
+        db.inner
+            .upsert::<Option<StoredKey>>(key_record(checklist))
+            .content(StoredKey {
+                checklist: checklist.clone(),
+                wrapped,
+            })
+            .await
+            .map_err(Error::surreal("storing data key"))?;
+        Ok(())
+    }
+
+    /// Load the data key for `checklist`.
+    ///
+    /// Checklists which predate envelope encryption have no data key; one is generated for them on demand.
+    pub(crate) async fn load(db: &Db, checklist: &ChecklistId) -> Result<Self> {
+        let stored = db
+            .inner
+            .select::<Option<StoredKey>>(key_record(checklist))
+            .await
+            .map_err(Error::surreal("loading data key"))?;
+        match stored {
+            Some(stored) => Self::unwrap(&stored.wrapped, &db.kek, checklist),
+            None => Self::create(db, checklist).await,
+        }
+    }
+
+    /// Generate and store a new data key for `checklist` as part of `txn`.
+    pub(crate) fn create_in(txn: &mut Transaction<'_>, db: &Db, checklist: &ChecklistId) {
+        Self::generate().store_in(txn, db, checklist);
+    }
+
+    /// Store this key as the data key for `checklist` as part of `txn`.
+    pub(crate) fn store_in(&self, txn: &mut Transaction<'_>, db: &Db, checklist: &ChecklistId) {
+        let record = txn.bind(key_record(checklist));
+        let content = txn.bind(StoredKey {
+            checklist: checklist.clone(),
+            wrapped: self.wrap(&db.kek, checklist),
+        });
+        txn.push(format!("UPSERT {record} CONTENT {content}"));
+    }
+
+    /// Delete the data key of `checklist` as part of `txn`.
+    pub(crate) fn delete_in(txn: &mut Transaction<'_>, checklist: &ChecklistId) {
+        let record = txn.bind(key_record(checklist));
+        txn.push(format!("DELETE {record}"));
+    }
+
+    pub(crate) async fn delete(db: &Db, checklist: &ChecklistId) -> Result<()> {
+        db.inner
+            .delete::<Option<StoredKey>>(key_record(checklist))
+            .await
+            .map_err(Error::surreal("deleting data key"))?;
+        Ok(())
+    }
+}
+
+/// Associated data which binds a wrapped key, or contents encrypted under it, to `checklist`.
+///
+/// This is the raw record key rather than its SurrealQL form, which escapes some keys, so that it
+/// survives being carried around as a string. The two are the same for generated keys.
+pub(crate) fn checklist_aad(checklist: &ChecklistId) -> String {
+    surrealdb::value::from_value::<String>((**checklist).clone().into())
+        .unwrap_or_else(|_| checklist.to_string())
+}
+
+/// Wrapped data keys share their record key with the checklist they belong to.
+fn key_record(checklist: &ChecklistId) -> RecordId {
+    RecordId::from_table_key(KEYS_TABLE, (**checklist).clone())
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct StoredKey {
+    checklist: ChecklistId,
+    wrapped: WrappedKey,
+}
@@ -1,8 +1,50 @@
+mod access;
+mod archive;
+mod assignee;
+mod attachment;
+mod batch;
+mod copy;
+mod datetime;
+mod due;
+mod export;
+mod keys;
+mod note;
+mod priority;
+mod query;
+mod recurrence;
+mod revision;
+mod search;
+mod stats;
+mod sync;
+mod tag;
+mod template;
+mod tree;
+mod txn;
+mod watch;
+
 use std::{borrow::Cow, path::Path, str::FromStr};
 
-use futures::{TryFutureExt as _, future::try_join_all};
+use chrono::{DateTime, Utc};
 use surrealdb::{RecordId, RecordIdKey};
 
+pub use access::{Access, Grant, User, UserId};
+pub use attachment::{Attachment, AttachmentId};
+pub use batch::Batch;
+pub use export::ExportedChecklist;
+pub use keys::WrappedKey;
+pub use note::{Note, NoteId};
+pub use priority::{ItemOrder, Priority};
+pub use query::{ChecklistQuery, ChecklistSort, Cursor, ItemQuery, ItemSort, Page};
+pub use recurrence::{Recurrence, Run, RunItem};
+pub use search::{Fragment, SearchHit, SearchTarget, search};
+pub use stats::{Progress, Summary, checks_per_period};
+pub use sync::{Hlc, Op, OpCursor, SyncState};
+pub use tag::{Tag, Taggable};
+pub use tree::ItemNode;
+pub use watch::{Change, Changed};
+
+use keys::{AttachmentKey, DataKey, KeyEncryptionKey};
+
 // `local::Db` specifies an embedded database.
 type Database = surrealdb::Surreal<surrealdb::engine::local::Db>;
 
@@ -12,7 +54,7 @@ pub enum Error {
     Surreal {
         context: &'static str,
         #[source]
-        inner: surrealdb::Error,
+        inner: Box<surrealdb::Error>,
     },
     #[error("this item is not present in the db; it may have been deleted")]
     MissingItem,
@@ -22,11 +64,56 @@ pub enum Error {
     FailedCreate { resource: &'static str },
     #[error("updating a {resource} did not return an instance of that resource")]
     FailedUpdate { resource: &'static str },
+    #[error("{context}: the key is wrong or the data is corrupt")]
+    Decrypt { context: &'static str },
+    #[error("unsupported wrapped key format version {version}")]
+    UnsupportedKeyVersion { version: u8 },
+    #[error("malformed wrapped key")]
+    MalformedKey,
+    #[error("invalid or mismatched pagination cursor")]
+    InvalidCursor,
+    #[error("tag names must not be empty")]
+    EmptyTag,
+    #[error("an item cannot be nested under itself or one of its descendants")]
+    ParentCycle,
+    #[error("an item can only be nested under an item in the same checklist")]
+    ParentInOtherChecklist,
+    #[error("this checklist is not a template")]
+    NotATemplate,
+    #[error("no value given for template placeholder {{{{{name}}}}}")]
+    MissingTemplateVariable { name: String },
+    #[error("expected a template placeholder value as NAME=VALUE, got {var:?}")]
+    InvalidTemplateVariable { var: String },
+    #[error("invalid recurrence rule {rule:?}: {reason}")]
+    InvalidRecurrence { rule: String, reason: String },
+    #[error("a checklist cannot be merged into itself")]
+    MergeIntoSelf,
+    #[error("changed since it was loaded: expected revision {expected}, found {actual}")]
+    Conflict { expected: u64, actual: u64 },
+    #[error("the acting user does not have permission to do this")]
+    Forbidden,
+    #[error("this checklist is locked; unlock it to change it or its items")]
+    Locked,
+    #[error("attachments may be at most {limit} bytes; this one is {size}")]
+    AttachmentTooLarge { size: usize, limit: usize },
+    #[error("{context}: {inner}")]
+    Serialization {
+        context: &'static str,
+        #[source]
+        inner: serde_json::Error,
+    },
 }
 
 impl Error {
     pub(crate) fn surreal(context: &'static str) -> impl FnOnce(surrealdb::Error) -> Self {
-        move |inner| Self::Surreal { context, inner }
+        move |inner| Self::Surreal {
+            context,
+            inner: Box::new(inner),
+        }
+    }
+
+    pub(crate) fn serialization(context: &'static str) -> impl FnOnce(serde_json::Error) -> Self {
+        move |inner| Self::Serialization { context, inner }
     }
 }
 
@@ -34,10 +121,18 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub struct Db {
     inner: Database,
-    _encryption_key: Vec<u8>,
+    kek: KeyEncryptionKey,
+    attachment_key: AttachmentKey,
+    /// Whom this handle acts as, if anyone; see [`Db::as_user`].
+    user: Option<UserId>,
+    /// Whether this handle may do anything, whoever owns what; see [`Db::with_full_access`].
+    full_access: bool,
 }
 
 impl Db {
+    /// Open the database at `path`, creating it if need be.
+    ///
+    /// Fails with [`Error::Decrypt`] if `encryption_key` is not the key the database was created with.
     pub async fn new(path: impl AsRef<Path>, encryption_key: &[u8]) -> Result<Self> {
         // In the real implementation we could do transparent item-level encryption and decryption;
         // we have an implementation which does this in the indexdb on wasm already.
@@ -47,7 +142,10 @@ impl Db {
         // instead of worrying about file-level encryption.
         //
         // For the purpose of this spike, we will not do any of that, and just pretend that it's already accomplished.
-        let encryption_key = encryption_key.to_owned();
+        // The key hierarchy is real, though: the master key wraps a data key per checklist (see `keys`),
+        // which is what actually protects exported checklists.
+        let kek = KeyEncryptionKey::derive(encryption_key);
+        let attachment_key = AttachmentKey::derive(encryption_key);
 
         let mut capabilities = surrealdb::opt::capabilities::Capabilities::default();
         capabilities.allow_experimental_feature(
@@ -68,9 +166,13 @@ impl Db {
 
         let db = Self {
             inner,
-            _encryption_key: encryption_key,
+            kek,
+            attachment_key,
+            user: None,
+            full_access: false,
         };
         db.ensure_schema().await?;
+        db.verify_master_key().await?;
 
         Ok(db)
     }
@@ -83,6 +185,10 @@ impl Db {
                 .await
                 .map_err(Error::surreal("executing schema"))?;
         }
+        self.inner
+            .query(sync::log_events())
+            .await
+            .map_err(Error::surreal("executing schema"))?;
 
         Ok(())
     }
@@ -141,28 +247,70 @@ struct InsertChecklist {
     pub name: Cow<'static, str>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize)]
+struct NewChecklist {
+    name: Cow<'static, str>,
+    owner: Option<UserId>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Checklist {
     pub id: ChecklistId,
     pub name: Cow<'static, str>,
     pub items: Vec<ItemId>,
+    /// `None` for checklists created before creation times were recorded.
+    #[serde(default, with = "datetime::option")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Whether this checklist is a template for other checklists.
+    #[serde(default)]
+    pub is_template: bool,
+    /// How often this checklist resets, if it does.
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// When the current period of a recurring checklist began.
+    #[serde(default, with = "datetime::option")]
+    pub period_start: Option<DateTime<Utc>>,
+    /// Incremented by the database on every change to this checklist.
+    #[serde(default)]
+    pub revision: u64,
+    /// `None` for checklists which are open to everyone; see [`Db::as_user`].
+    #[serde(default)]
+    pub owner: Option<UserId>,
+    /// Archived checklists are left out of listings unless asked for.
+    #[serde(default)]
+    pub archived: bool,
+    /// Locked checklists and their items cannot be changed.
+    #[serde(default)]
+    pub locked: bool,
+    /// Whether to archive this checklist as soon as all of its items are checked.
+    #[serde(default)]
+    pub auto_archive: bool,
 }
 
 impl Checklist {
+    /// Create a checklist owned by the acting user.
     pub async fn new(db: &Db, name: impl Into<Cow<'static, str>>) -> Result<Self> {
         let name = name.into();
 
-        db.inner
+        let checklist: Self = db
+            .inner
             .create(CHECKLIST_TABLE)
-            .content(InsertChecklist { name })
+            .content(NewChecklist {
+                name,
+                owner: db.user.clone(),
+            })
             .await
             .map_err(Error::surreal("creating checklist"))?
             .ok_or(Error::FailedCreate {
                 resource: CHECKLIST_TABLE,
-            })
+            })?;
+        DataKey::create(db, &checklist.id).await?;
+
+        Ok(checklist)
     }
 
     pub async fn load(db: &Db, id: ChecklistId) -> Result<Option<Self>> {
+        db.authorize(&id, Access::Read).await?;
         let resource = RecordId::from(id);
         db.inner
             .select(resource)
@@ -170,14 +318,17 @@ impl Checklist {
             .map_err(Error::surreal("loading checklist"))
     }
 
+    /// All checklists the acting user can read, other than archived ones.
     pub async fn all(db: &Db) -> Result<Vec<Self>> {
-        db.inner
-            .select(CHECKLIST_TABLE)
-            .await
-            .map_err(Error::surreal("loading all checklists"))
+        ChecklistQuery::new().fetch(db).await.map(|page| page.items)
     }
 
+    /// Only the owner may delete a checklist.
     pub async fn delete(db: &Db, id: ChecklistId) -> Result<()> {
+        db.authorize_owner(&id).await?;
+        DataKey::delete(db, &id).await?;
+        access::delete_grants(db, &id).await?;
+        Self::delete_history(db, &id).await?;
         let resource = RecordId::from(id);
         db.inner
             .delete::<Option<Checklist>>(resource)
@@ -186,18 +337,46 @@ impl Checklist {
         Ok(())
     }
 
+    /// Change the name of this checklist.
+    ///
+    /// Fails with [`Error::Conflict`] if the checklist has changed since it was loaded.
+    pub async fn rename(&mut self, db: &Db, name: impl Into<Cow<'static, str>>) -> Result<()> {
+        db.authorize(&self.id, Access::Edit).await?;
+        *self = revision::update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            InsertChecklist { name: name.into() },
+            "renaming checklist",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// All items in this checklist, in the order in which they were added.
+    ///
+    /// See [`Checklist::items_by`] for other orders.
     pub async fn items(&self, db: &Db) -> Result<Vec<Item>> {
-        let id = self.id.clone();
-        let fresh = Self::load(db, id).await?.ok_or(Error::MissingItem)?;
-        try_join_all(fresh.items.into_iter().map(|id| {
-            Item::load(db, id).and_then(async |maybe_item| maybe_item.ok_or(Error::MissingItem))
-        }))
-        .await
+        db.authorize(&self.id, Access::Read).await?;
+        ItemQuery::in_checklist(self.id.clone())
+            .fetch(db)
+            .await
+            .map(|page| page.items)
     }
 }
 
 const ITEM_TABLE: &str = "item";
 
+/// An expression for the position just past the end of `checklist`, itself an expression.
+///
+/// Positions are found in the statement which uses them, so that items added at the same time
+/// cannot be given the same one.
+pub(crate) fn end_position(checklist: &str) -> String {
+    format!(
+        "((math::max((SELECT VALUE position FROM {ITEM_TABLE} WHERE checklist = {checklist})) ?? -1) + 1)"
+    )
+}
+
 #[derive(
     Debug,
     Clone,
@@ -245,8 +424,7 @@ impl TryFrom<RecordId> for ItemId {
 }
 
 #[derive(Debug, serde::Serialize)]
-struct InsertItem {
-    checklist: ChecklistId,
+struct ItemText {
     item: Cow<'static, str>,
 }
 
@@ -259,7 +437,34 @@ struct CheckedItem {
 pub struct Item {
     pub id: ItemId,
     pub checklist: ChecklistId,
+    /// The item under which this one is nested, if any.
+    #[serde(default)]
+    pub parent: Option<ItemId>,
     pub item: Cow<'static, str>,
+    /// Manual ordering within the checklist; new items go at the end.
+    #[serde(default)]
+    pub position: i64,
+    /// `None` for items created before creation times were recorded.
+    #[serde(default, with = "datetime::option")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When this item should be done by, if ever.
+    #[serde(default, with = "datetime::option")]
+    pub due_at: Option<DateTime<Utc>>,
+    /// When to remind someone about this item, if ever.
+    #[serde(default, with = "datetime::option")]
+    pub remind_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// When this item was checked; `None` while it is unchecked, and for items checked before
+    /// check times were recorded.
+    #[serde(default, with = "datetime::option")]
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Who should do this item, if anyone in particular.
+    #[serde(default)]
+    pub assignee: Option<UserId>,
+    /// Incremented by the database on every change to this item.
+    #[serde(default)]
+    pub revision: u64,
 }
 
 impl Item {
@@ -268,21 +473,40 @@ impl Item {
         checklist: ChecklistId,
         item: impl Into<Cow<'static, str>>,
     ) -> Result<Self> {
-        let item = item.into();
-
-        let item = InsertItem { checklist, item };
+        Self::create(db, checklist, None, item.into()).await
+    }
 
+    async fn create(
+        db: &Db,
+        checklist: ChecklistId,
+        parent: Option<ItemId>,
+        item: Cow<'static, str>,
+    ) -> Result<Self> {
+        db.authorize(&checklist, Access::Edit).await?;
         db.inner
-            .create(ITEM_TABLE)
-            .content(item)
+            .query(format!(
+                "CREATE ONLY {ITEM_TABLE} CONTENT {{
+                     checklist: $checklist,
+                     parent: $parent,
+                     item: $item,
+                     position: {},
+                 }}",
+                end_position("$checklist")
+            ))
+            .bind(("checklist", RecordId::from(checklist)))
+            .bind(("parent", parent.map(RecordId::from)))
+            .bind(("item", item))
             .await
             .map_err(Error::surreal("creating item"))?
+            .take::<Option<Self>>(0)
+            .map_err(Error::surreal("creating item"))?
             .ok_or(Error::FailedCreate {
                 resource: ITEM_TABLE,
             })
     }
 
     pub async fn load(db: &Db, id: ItemId) -> Result<Option<Self>> {
+        db.authorize_item(&id, Access::Read).await?;
         let resource = RecordId::from(id);
         db.inner
             .select(resource)
@@ -290,7 +514,9 @@ impl Item {
             .map_err(Error::surreal("loading item"))
     }
 
+    /// Delete this item, along with any items nested under it.
     pub async fn delete(db: &Db, id: ItemId) -> Result<()> {
+        db.authorize_item(&id, Access::Edit).await?;
         let resource = RecordId::from(id);
         db.inner
             .delete::<Option<Self>>(resource)
@@ -299,7 +525,24 @@ impl Item {
         Ok(())
     }
 
+    /// Change the text of this item.
+    ///
+    /// Fails with [`Error::Conflict`] if the item has changed since it was loaded.
+    pub async fn set_text(&mut self, db: &Db, text: impl Into<Cow<'static, str>>) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        *self = revision::update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            ItemText { item: text.into() },
+            "changing item text",
+        )
+        .await?;
+        Ok(())
+    }
+
     pub async fn is_set(&self, db: &Db) -> Result<bool> {
+        db.authorize_item(&self.id, Access::Read).await?;
         let id = RecordId::from(self.id.clone());
         db.inner
             .select::<Option<CheckedItem>>(id)
@@ -307,18 +550,4 @@ impl Item {
             .map(|maybe_item| maybe_item.is_some_and(|item| item.checked))
             .map_err(Error::surreal("reading item checked status"))
     }
-
-    pub async fn set_checked(&self, db: &Db, checked: bool) -> Result<()> {
-        let id = RecordId::from(self.id.clone());
-        db.inner
-            .update::<Option<Self>>(id)
-            .merge(CheckedItem { checked })
-            .await
-            .map_err(Error::surreal("updating checked item"))?
-            .ok_or(Error::FailedUpdate {
-                resource: ITEM_TABLE,
-            })?;
-
-        Ok(())
-    }
 }
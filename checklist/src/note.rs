@@ -0,0 +1,146 @@
+//! Notes on items.
+//!
+//! An item's text says what to do; notes record what happened, such as why a step was skipped. Each
+//! note is free text, which may run to several paragraphs, stamped with its author and the time it
+//! was written. Notes are deleted along with their item, and are not copied or synced.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use surrealdb::{RecordId, RecordIdKey};
+
+use crate::{Access, Db, Error, Item, ItemId, Result, UserId, datetime};
+
+pub(crate) const NOTE_TABLE: &str = "note";
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    derive_more::Constructor,
+    derive_more::Deref,
+    derive_more::From,
+    derive_more::Into,
+    derive_more::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(try_from = "RecordId", into = "RecordId")]
+pub struct NoteId(RecordIdKey);
+
+impl FromStr for NoteId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(s.into()))
+    }
+}
+
+impl From<NoteId> for RecordId {
+    fn from(value: NoteId) -> Self {
+        RecordId::from_table_key(NOTE_TABLE, value.0)
+    }
+}
+
+impl TryFrom<RecordId> for NoteId {
+    type Error = Error;
+
+    fn try_from(value: RecordId) -> std::result::Result<Self, Self::Error> {
+        if value.table() != NOTE_TABLE {
+            return Err(Error::WrongRecordId {
+                expected: NOTE_TABLE,
+                got: value.table().to_owned(),
+            });
+        }
+        Ok(Self(value.key().to_owned()))
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct InsertNote {
+    item: ItemId,
+    author: Option<UserId>,
+    text: String,
+}
+
+/// A comment on an item.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Note {
+    pub id: NoteId,
+    pub item: ItemId,
+    /// The user who wrote this note; `None` when written with full access.
+    #[serde(default)]
+    pub author: Option<UserId>,
+    pub text: String,
+    #[serde(with = "datetime::required")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Note {
+    /// Add a note to `item`, written by the acting user.
+    ///
+    /// Anyone who may check the item may comment on it.
+    pub async fn add(db: &Db, item: ItemId, text: impl Into<String>) -> Result<Self> {
+        db.authorize_item(&item, Access::Check).await?;
+        if Item::load(db, item.clone()).await?.is_none() {
+            return Err(Error::MissingItem);
+        }
+        let note = InsertNote {
+            item,
+            author: db.user().cloned(),
+            text: text.into(),
+        };
+        db.inner
+            .create(NOTE_TABLE)
+            .content(note)
+            .await
+            .map_err(Error::surreal("adding note"))?
+            .ok_or(Error::FailedCreate {
+                resource: NOTE_TABLE,
+            })
+    }
+
+    /// Notes on `item`, oldest first.
+    pub async fn of(db: &Db, item: ItemId) -> Result<Vec<Self>> {
+        db.authorize_item(&item, Access::Read).await?;
+        db.inner
+            .query(format!(
+                "SELECT * FROM {NOTE_TABLE} WHERE item = $item ORDER BY created_at, id"
+            ))
+            .bind(("item", RecordId::from(item)))
+            .await
+            .map_err(Error::surreal("loading notes"))?
+            .take(0)
+            .map_err(Error::surreal("loading notes"))
+    }
+
+    /// Delete a note.
+    ///
+    /// Authors may delete their own notes as long as they may still check the item; deleting
+    /// anyone else's needs edit access.
+    pub async fn delete(db: &Db, id: NoteId) -> Result<()> {
+        let Some(note) = db
+            .inner
+            .select::<Option<Self>>(RecordId::from(id.clone()))
+            .await
+            .map_err(Error::surreal("loading note"))?
+        else {
+            return Ok(());
+        };
+        let access = if note.author.is_some() && note.author.as_ref() == db.user() {
+            Access::Check
+        } else {
+            Access::Edit
+        };
+        db.authorize_item(&note.item, access).await?;
+        db.inner
+            .delete::<Option<Self>>(RecordId::from(id))
+            .await
+            .map_err(Error::surreal("deleting note"))?;
+        Ok(())
+    }
+}
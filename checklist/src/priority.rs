@@ -0,0 +1,118 @@
+//! Item priorities, and orders in which to list a checklist's items.
+//!
+//! Priorities are synced like an item's other fields, so every change of priority is recorded in
+//! the op log.
+
+use std::cmp::Reverse;
+
+use surrealdb::RecordId;
+
+use crate::{Access, Checklist, Db, Item, ItemId, ItemQuery, Result, revision::update_if_current};
+
+/// How urgent an item is.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(from = "i64", into = "i64")]
+pub enum Priority {
+    Low = 0,
+    #[default]
+    Normal = 1,
+    High = 2,
+    Urgent = 3,
+}
+
+/// Priorities are stored as integers so that the database can order by them; values out of range
+/// are clamped.
+impl From<i64> for Priority {
+    fn from(value: i64) -> Self {
+        match value {
+            ..=0 => Self::Low,
+            1 => Self::Normal,
+            2 => Self::High,
+            3.. => Self::Urgent,
+        }
+    }
+}
+
+impl From<Priority> for i64 {
+    fn from(priority: Priority) -> Self {
+        priority as _
+    }
+}
+
+/// Order in which [`Checklist::items_by`] lists items.
+///
+/// Unlike [`ItemSort`][crate::ItemSort], these orders apply to a whole checklist at once, so they
+/// can combine several keys. Ties are always broken by manual order.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ItemOrder {
+    /// The order in which items were arranged in the checklist.
+    #[default]
+    Manual,
+    /// Most urgent first.
+    Priority,
+    /// Soonest due first; items without a due date come last.
+    Due,
+    /// Items which are not yet checked before those which are.
+    UncheckedFirst,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetPriority {
+    priority: Priority,
+}
+
+impl Item {
+    /// Change how urgent this item is.
+    ///
+    /// Fails with [`Error::Conflict`][crate::Error::Conflict] if the item has changed since it was
+    /// loaded.
+    pub async fn set_priority(&mut self, db: &Db, priority: Priority) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            SetPriority { priority },
+            "setting priority",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl Checklist {
+    /// All items in this checklist, in the given order.
+    pub async fn items_by(&self, db: &Db, order: ItemOrder) -> Result<Vec<Item>> {
+        // manual order first, so that the stable sorts below break ties by it
+        let mut items = self.items(db).await?;
+        match order {
+            ItemOrder::Manual => {}
+            ItemOrder::Priority => items.sort_by_key(|item| Reverse(item.priority)),
+            ItemOrder::Due => items.sort_by_key(|item| (item.due_at.is_none(), item.due_at)),
+            ItemOrder::UncheckedFirst => {
+                let checked = ItemQuery::in_checklist(self.id.clone())
+                    .checked(true)
+                    .fetch(db)
+                    .await?
+                    .items
+                    .into_iter()
+                    .map(|item| item.id)
+                    .collect::<Vec<ItemId>>();
+                items.sort_by_key(|item| checked.contains(&item.id));
+            }
+        }
+        Ok(items)
+    }
+}
@@ -0,0 +1,544 @@
+//! Filtered, sorted and paginated queries over checklists and items.
+//!
+//! Pagination is keyset-based: a [`Cursor`] records the sort key and id of the last result on a page,
+//! and the next page starts strictly after it. Unlike offsets, cursors stay valid while records are
+//! inserted or deleted elsewhere in the result set.
+
+use std::{fmt, str::FromStr};
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use surrealdb::{RecordId, RecordIdKey, sql::Value};
+
+use crate::{
+    Access, CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, ITEM_TABLE, Item, ItemId, Result,
+    Tag, UserId, access,
+};
+
+/// One page of query results.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Where the next page starts, or `None` if this is the last page.
+    pub next: Option<Cursor>,
+}
+
+/// Position in a sorted result set after which the next page begins.
+///
+/// Cursors round-trip through opaque strings via [`Display`][fmt::Display] and [`FromStr`], so they
+/// can be handed to UIs and command lines. A cursor is only valid for the sort order which produced it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Cursor {
+    key: SortKey,
+    id: RecordIdKey,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+enum SortKey {
+    Position(i64),
+    Created(Option<DateTime<Utc>>),
+    Text(String),
+    Due(Option<DateTime<Utc>>),
+}
+
+impl SortKey {
+    fn value(&self) -> Value {
+        match self {
+            SortKey::Position(position) => Value::from(*position),
+            SortKey::Created(Some(created_at)) => Value::from(*created_at),
+            SortKey::Created(None) => Value::None,
+            SortKey::Text(text) => Value::from(text.as_str()),
+            SortKey::Due(Some(due_at)) => Value::from(*due_at),
+            SortKey::Due(None) => Value::None,
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_vec(self).map_err(|_| fmt::Error)?;
+        f.write_str(&URL_SAFE_NO_PAD.encode(json))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let json = URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| Error::InvalidCursor)?;
+        serde_json::from_slice(&json).map_err(|_| Error::InvalidCursor)
+    }
+}
+
+/// Build the condition selecting records strictly after `cursor` in the given order.
+fn after_condition(field: &str, descending: bool) -> String {
+    let op = if descending { "<" } else { ">" };
+    format!("({field} {op} $after_key OR ({field} = $after_key AND id {op} $after_id))")
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
+/// Turn one more result than was asked for into a page and the cursor for the next one.
+fn paginate<T>(
+    mut results: Vec<T>,
+    limit: Option<usize>,
+    cursor: impl Fn(&T) -> Cursor,
+) -> Page<T> {
+    let next = match limit {
+        Some(limit) if results.len() > limit => {
+            results.truncate(limit);
+            results.last().map(cursor)
+        }
+        _ => None,
+    };
+    Page {
+        items: results,
+        next,
+    }
+}
+
+/// Order in which [`ChecklistQuery`] returns checklists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ChecklistSort {
+    #[default]
+    Created,
+    Name,
+}
+
+/// Query over all checklists.
+#[derive(Debug, Default, Clone)]
+pub struct ChecklistQuery {
+    is_template: Option<bool>,
+    archived: bool,
+    contains: Option<String>,
+    tags: Vec<Tag>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    sort: ChecklistSort,
+    descending: bool,
+    limit: Option<usize>,
+    after: Option<Cursor>,
+}
+
+impl ChecklistQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only templates, or only checklists which are not templates.
+    pub fn template(mut self, is_template: bool) -> Self {
+        self.is_template = Some(is_template);
+        self
+    }
+
+    /// Only archived checklists, rather than only those which are not archived.
+    pub fn archived(mut self, archived: bool) -> Self {
+        self.archived = archived;
+        self
+    }
+
+    /// Only checklists whose name contains this text, ignoring case.
+    pub fn containing(mut self, text: impl Into<String>) -> Self {
+        self.contains = Some(text.into());
+        self
+    }
+
+    /// Only checklists with this tag; when called repeatedly, checklists must have every tag.
+    pub fn tagged(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Only checklists created at or after this time.
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.created_after = Some(time);
+        self
+    }
+
+    /// Only checklists created before this time.
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.created_before = Some(time);
+        self
+    }
+
+    pub fn sort(mut self, sort: ChecklistSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Return at most this many checklists per page.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Start after this cursor, which must have come from a query with the same sort order.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    fn sort_field(&self) -> &'static str {
+        match self.sort {
+            ChecklistSort::Created => "created_at",
+            ChecklistSort::Name => "name",
+        }
+    }
+
+    fn cursor(&self, checklist: &Checklist) -> Cursor {
+        let key = match self.sort {
+            ChecklistSort::Created => SortKey::Created(checklist.created_at),
+            ChecklistSort::Name => SortKey::Text(checklist.name.clone().into_owned()),
+        };
+        Cursor {
+            key,
+            id: (*checklist.id).clone(),
+        }
+    }
+
+    fn check_cursor(&self, cursor: &Cursor) -> Result<()> {
+        match (self.sort, &cursor.key) {
+            (ChecklistSort::Created, SortKey::Created(_))
+            | (ChecklistSort::Name, SortKey::Text(_)) => Ok(()),
+            _ => Err(Error::InvalidCursor),
+        }
+    }
+
+    /// Only checklists the acting user can read are returned, and archived checklists only when
+    /// asked for.
+    pub async fn fetch(&self, db: &Db) -> Result<Page<Checklist>> {
+        let mut conditions = vec![access::permits(db, "id", Access::Read)];
+        if self.is_template.is_some() {
+            conditions.push("is_template = $is_template".to_owned());
+        }
+        conditions.push(
+            if self.archived {
+                "archived = true"
+            } else {
+                "archived != true"
+            }
+            .to_owned(),
+        );
+        if self.contains.is_some() {
+            conditions.push("string::contains(string::lowercase(name), $contains)".to_owned());
+        }
+        if !self.tags.is_empty() {
+            conditions.push("$tags ALLINSIDE ->tagged->tag".to_owned());
+        }
+        if self.created_after.is_some() {
+            conditions.push("created_at >= $created_after".to_owned());
+        }
+        if self.created_before.is_some() {
+            conditions.push("created_at < $created_before".to_owned());
+        }
+        if let Some(after) = &self.after {
+            self.check_cursor(after)?;
+            conditions.push(after_condition(self.sort_field(), self.descending));
+        }
+
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let field = self.sort_field();
+        let limit = if self.limit.is_some() {
+            "LIMIT $limit"
+        } else {
+            ""
+        };
+        let query = format!(
+            "SELECT * FROM {CHECKLIST_TABLE} {} ORDER BY {field} {direction}, id {direction} {limit}",
+            where_clause(&conditions)
+        );
+
+        let checklists = db
+            .inner
+            .query(query)
+            .bind(("is_template", self.is_template))
+            .bind(("contains", self.contains.as_deref().map(str::to_lowercase)))
+            .bind((
+                "tags",
+                self.tags.iter().map(Tag::record).collect::<Vec<_>>(),
+            ))
+            .bind(("created_after", self.created_after.map(Value::from)))
+            .bind(("created_before", self.created_before.map(Value::from)))
+            .bind((
+                "after_key",
+                self.after
+                    .as_ref()
+                    .map_or(Value::None, |after| after.key.value()),
+            ))
+            .bind((
+                "after_id",
+                self.after
+                    .as_ref()
+                    .map(|after| RecordId::from(ChecklistId::new(after.id.clone()))),
+            ))
+            .bind(("limit", self.limit.map(|limit| limit + 1)))
+            .bind(("user", db.user_record()))
+            .await
+            .map_err(Error::surreal("querying checklists"))?
+            .take::<Vec<Checklist>>(0)
+            .map_err(Error::surreal("querying checklists"))?;
+
+        Ok(paginate(checklists, self.limit, |checklist| {
+            self.cursor(checklist)
+        }))
+    }
+}
+
+/// Order in which [`ItemQuery`] returns items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ItemSort {
+    /// The order in which items were added to their checklist.
+    #[default]
+    Position,
+    Created,
+    Text,
+    /// Soonest due first; items without a due date sort before all others.
+    Due,
+}
+
+/// Query over items, either within one checklist or across all of them.
+#[derive(Debug, Default, Clone)]
+pub struct ItemQuery {
+    checklist: Option<ChecklistId>,
+    parent: Option<ItemId>,
+    checked: Option<bool>,
+    contains: Option<String>,
+    tags: Vec<Tag>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    due_after: Option<DateTime<Utc>>,
+    due_before: Option<DateTime<Utc>>,
+    assignee: Option<UserId>,
+    sort: ItemSort,
+    descending: bool,
+    limit: Option<usize>,
+    after: Option<Cursor>,
+}
+
+impl ItemQuery {
+    /// Query items across all checklists.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Query items within a single checklist.
+    pub fn in_checklist(checklist: ChecklistId) -> Self {
+        Self {
+            checklist: Some(checklist),
+            ..Self::default()
+        }
+    }
+
+    /// Only items nested directly under `parent`.
+    pub fn children_of(mut self, parent: ItemId) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Only items whose checked state is `checked`.
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = Some(checked);
+        self
+    }
+
+    /// Only items whose text contains this text, ignoring case.
+    pub fn containing(mut self, text: impl Into<String>) -> Self {
+        self.contains = Some(text.into());
+        self
+    }
+
+    /// Only items with this tag; when called repeatedly, items must have every tag.
+    pub fn tagged(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Only items created at or after this time.
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.created_after = Some(time);
+        self
+    }
+
+    /// Only items created before this time.
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.created_before = Some(time);
+        self
+    }
+
+    /// Only items due at or after this time.
+    pub fn due_after(mut self, time: DateTime<Utc>) -> Self {
+        self.due_after = Some(time);
+        self
+    }
+
+    /// Only items due before this time.
+    pub fn due_before(mut self, time: DateTime<Utc>) -> Self {
+        self.due_before = Some(time);
+        self
+    }
+
+    /// Only items assigned to `user`.
+    pub fn assigned_to(mut self, user: UserId) -> Self {
+        self.assignee = Some(user);
+        self
+    }
+
+    pub fn sort(mut self, sort: ItemSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Return at most this many items per page.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Start after this cursor, which must have come from a query with the same sort order.
+    pub fn after(mut self, cursor: Cursor) -> Self {
+        self.after = Some(cursor);
+        self
+    }
+
+    fn sort_field(&self) -> &'static str {
+        match self.sort {
+            ItemSort::Position => "position",
+            ItemSort::Created => "created_at",
+            ItemSort::Text => "item",
+            ItemSort::Due => "due_at",
+        }
+    }
+
+    fn cursor(&self, item: &Item) -> Cursor {
+        let key = match self.sort {
+            ItemSort::Position => SortKey::Position(item.position),
+            ItemSort::Created => SortKey::Created(item.created_at),
+            ItemSort::Text => SortKey::Text(item.item.clone().into_owned()),
+            ItemSort::Due => SortKey::Due(item.due_at),
+        };
+        Cursor {
+            key,
+            id: (*item.id).clone(),
+        }
+    }
+
+    fn check_cursor(&self, cursor: &Cursor) -> Result<()> {
+        match (self.sort, &cursor.key) {
+            (ItemSort::Position, SortKey::Position(_))
+            | (ItemSort::Created, SortKey::Created(_))
+            | (ItemSort::Text, SortKey::Text(_))
+            | (ItemSort::Due, SortKey::Due(_)) => Ok(()),
+            _ => Err(Error::InvalidCursor),
+        }
+    }
+
+    /// Only items in checklists the acting user can read are returned.
+    pub async fn fetch(&self, db: &Db) -> Result<Page<Item>> {
+        let mut conditions = vec![access::permits(db, "checklist", Access::Read)];
+        if self.checklist.is_some() {
+            conditions.push("checklist = $checklist".to_owned());
+        }
+        if self.parent.is_some() {
+            conditions.push("parent = $parent".to_owned());
+        }
+        if self.checked.is_some() {
+            conditions.push("checked = $checked".to_owned());
+        }
+        if self.contains.is_some() {
+            conditions.push("string::contains(string::lowercase(item), $contains)".to_owned());
+        }
+        if !self.tags.is_empty() {
+            conditions.push("$tags ALLINSIDE ->tagged->tag".to_owned());
+        }
+        if self.created_after.is_some() {
+            conditions.push("created_at >= $created_after".to_owned());
+        }
+        if self.created_before.is_some() {
+            conditions.push("created_at < $created_before".to_owned());
+        }
+        if self.due_after.is_some() || self.due_before.is_some() {
+            // NONE compares less than any datetime
+            conditions.push("due_at != NONE".to_owned());
+        }
+        if self.due_after.is_some() {
+            conditions.push("due_at >= $due_after".to_owned());
+        }
+        if self.due_before.is_some() {
+            conditions.push("due_at < $due_before".to_owned());
+        }
+        if self.assignee.is_some() {
+            conditions.push("assignee = $assignee".to_owned());
+        }
+        if let Some(after) = &self.after {
+            self.check_cursor(after)?;
+            conditions.push(after_condition(self.sort_field(), self.descending));
+        }
+
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        let field = self.sort_field();
+        let limit = if self.limit.is_some() {
+            "LIMIT $limit"
+        } else {
+            ""
+        };
+        let query = format!(
+            "SELECT * FROM {ITEM_TABLE} {} ORDER BY {field} {direction}, id {direction} {limit}",
+            where_clause(&conditions)
+        );
+
+        let items = db
+            .inner
+            .query(query)
+            .bind(("checklist", self.checklist.clone().map(RecordId::from)))
+            .bind(("parent", self.parent.clone().map(RecordId::from)))
+            .bind(("checked", self.checked))
+            .bind(("contains", self.contains.as_deref().map(str::to_lowercase)))
+            .bind((
+                "tags",
+                self.tags.iter().map(Tag::record).collect::<Vec<_>>(),
+            ))
+            .bind(("created_after", self.created_after.map(Value::from)))
+            .bind(("created_before", self.created_before.map(Value::from)))
+            .bind(("due_after", self.due_after.map(Value::from)))
+            .bind(("due_before", self.due_before.map(Value::from)))
+            .bind(("assignee", self.assignee.clone().map(RecordId::from)))
+            .bind((
+                "after_key",
+                self.after
+                    .as_ref()
+                    .map_or(Value::None, |after| after.key.value()),
+            ))
+            .bind((
+                "after_id",
+                self.after
+                    .as_ref()
+                    .map(|after| RecordId::from(ItemId::new(after.id.clone()))),
+            ))
+            .bind(("limit", self.limit.map(|limit| limit + 1)))
+            .bind(("user", db.user_record()))
+            .await
+            .map_err(Error::surreal("querying items"))?
+            .take::<Vec<Item>>(0)
+            .map_err(Error::surreal("querying items"))?;
+
+        Ok(paginate(items, self.limit, |item| self.cursor(item)))
+    }
+}
@@ -0,0 +1,367 @@
+//! Checklists which reset on a schedule.
+//!
+//! A recurring checklist has a rule and the start of its current period. Once the next period
+//! begins, the checklist rolls over: the state of its items is archived as a [`Run`], and every
+//! item is unchecked. Rolling over is lazy; callers decide when to check, typically with
+//! [`Checklist::roll_over_due`] before each command, and periodically from a long-running process
+//! such as a server or reminder loop.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Duration, Utc};
+use surrealdb::{RecordId, sql::Value};
+
+use crate::{
+    Access, CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, Result, access, datetime,
+    revision::update_if_current,
+};
+
+pub(crate) const RUN_TABLE: &str = "run";
+
+/// How often a checklist resets.
+///
+/// Periods are measured from the time the rule was set, or from an explicit starting time.
+/// Rules round-trip through strings:
+///
+/// - `daily` and `weekly`
+/// - `every <n><unit>`, where the unit is one of `m`, `h`, `d` or `w`; for example `every 36h`
+/// - `cron <expression>`, with a seconds field and evaluated in UTC; for example `cron 0 0 9 * * Mon-Fri`
+///
+/// An `Every` period must be a positive whole number of minutes; build one with
+/// [`Recurrence::every`] to have it checked.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Recurrence {
+    /// A rule which resets every `period`.
+    ///
+    /// Fails with [`Error::InvalidRecurrence`] unless `period` is a positive whole number of minutes.
+    pub fn every(period: Duration) -> Result<Self> {
+        let recurrence = Recurrence::Every(period);
+        recurrence.validate()?;
+        Ok(recurrence)
+    }
+
+    /// Fail with [`Error::InvalidRecurrence`] if this rule cannot be used.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Recurrence::Every(period) => {
+                check_period(*period).map_err(|reason| Error::InvalidRecurrence {
+                    rule: format!("every {period}"),
+                    reason: reason.to_owned(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The first period boundary strictly after `time`, if there is one.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.period() {
+            Some(period) => time.checked_add_signed(period),
+            None => self.cron()?.after(&time).next(),
+        }
+    }
+
+    /// The last period boundary at or before `now`, counting from `start`.
+    fn current_start(&self, start: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+        if now < start {
+            return start;
+        }
+        match self.period() {
+            // fixed periods: skip straight to the right one instead of stepping through each
+            Some(period) => {
+                let period_ms = period.num_milliseconds();
+                if period_ms <= 0 {
+                    return start;
+                }
+                let periods = (now - start).num_milliseconds() / period_ms;
+                periods
+                    .checked_mul(period_ms)
+                    .and_then(Duration::try_milliseconds)
+                    .and_then(|elapsed| start.checked_add_signed(elapsed))
+                    .unwrap_or(start)
+            }
+            None => self
+                .cron()
+                .and_then(|cron| cron.after(&start).take_while(|time| *time <= now).last())
+                .unwrap_or(start),
+        }
+    }
+
+    fn period(&self) -> Option<Duration> {
+        match self {
+            Recurrence::Daily => Some(Duration::days(1)),
+            Recurrence::Weekly => Some(Duration::weeks(1)),
+            Recurrence::Every(period) => Some(*period),
+            Recurrence::Cron(_) => None,
+        }
+    }
+
+    fn cron(&self) -> Option<&cron::Schedule> {
+        match self {
+            Recurrence::Cron(schedule) => Some(schedule),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => f.write_str("daily"),
+            Recurrence::Weekly => f.write_str("weekly"),
+            Recurrence::Every(period) => {
+                let minutes = period.num_minutes();
+                let (amount, unit) = [(60 * 24 * 7, "w"), (60 * 24, "d"), (60, "h")]
+                    .into_iter()
+                    .find(|(size, _)| minutes % size == 0)
+                    .map_or((minutes, "m"), |(size, unit)| (minutes / size, unit));
+                write!(f, "every {amount}{unit}")
+            }
+            Recurrence::Cron(schedule) => write!(f, "cron {}", schedule.source()),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = |reason: &str| Error::InvalidRecurrence {
+            rule: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        let s = s.trim();
+        let (kind, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        match kind.to_lowercase().as_str() {
+            "daily" if rest.is_empty() => Ok(Recurrence::Daily),
+            "weekly" if rest.is_empty() => Ok(Recurrence::Weekly),
+            "every" => {
+                let split = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .ok_or_else(|| invalid("missing unit; use m, h, d or w"))?;
+                let (amount, unit) = rest.split_at(split);
+                let amount = amount
+                    .parse::<i64>()
+                    .map_err(|_| invalid("expected a whole number before the unit"))?;
+                let period = match unit.trim() {
+                    "m" => Duration::try_minutes(amount),
+                    "h" => Duration::try_hours(amount),
+                    "d" => Duration::try_days(amount),
+                    "w" => Duration::try_weeks(amount),
+                    _ => return Err(invalid("unknown unit; use m, h, d or w")),
+                }
+                .ok_or_else(|| invalid("period is too long"))?;
+                check_period(period).map_err(invalid)?;
+                Ok(Recurrence::Every(period))
+            }
+            "cron" => rest
+                .parse::<cron::Schedule>()
+                .map(|schedule| Recurrence::Cron(Box::new(schedule)))
+                .map_err(|err| invalid(&err.to_string())),
+            _ => Err(invalid(
+                "expected \"daily\", \"weekly\", \"every <n><unit>\" or \"cron <expression>\"",
+            )),
+        }
+    }
+}
+
+/// Why `period` cannot be the period of an `Every` rule, if it cannot.
+fn check_period(period: Duration) -> std::result::Result<(), &'static str> {
+    if period <= Duration::zero() {
+        return Err("period must be positive");
+    }
+    if period != Duration::minutes(period.num_minutes()) {
+        return Err("period must be a whole number of minutes");
+    }
+    Ok(())
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = Error;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// The state of one item at the end of a run.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RunItem {
+    pub item: String,
+    pub checked: bool,
+}
+
+/// One archived period of a recurring checklist.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Run {
+    pub checklist: ChecklistId,
+    #[serde(with = "datetime::required")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "datetime::required")]
+    pub ended_at: DateTime<Utc>,
+    /// Items in checklist order, as they stood when the run ended.
+    pub items: Vec<RunItem>,
+}
+
+impl Run {
+    pub fn completed(&self) -> usize {
+        self.items.iter().filter(|item| item.checked).count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetRecurrence {
+    recurrence: Option<Recurrence>,
+    #[serde(with = "datetime::option")]
+    period_start: Option<DateTime<Utc>>,
+}
+
+impl Checklist {
+    /// Make this checklist recur, with its first period starting at `starting`; or stop it recurring.
+    ///
+    /// Fails with [`Error::InvalidRecurrence`] if the rule cannot be used, and with
+    /// [`Error::Conflict`] if the checklist has changed since it was loaded.
+    pub async fn set_recurrence(
+        &mut self,
+        db: &Db,
+        recurrence: Option<Recurrence>,
+        starting: DateTime<Utc>,
+    ) -> Result<()> {
+        if let Some(recurrence) = &recurrence {
+            recurrence.validate()?;
+        }
+        db.authorize(&self.id, Access::Edit).await?;
+        let period_start = recurrence.as_ref().map(|_| starting);
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            SetRecurrence {
+                recurrence,
+                period_start,
+            },
+            "setting recurrence",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// When the current period ends, if this checklist recurs.
+    pub fn next_rollover(&self) -> Option<DateTime<Utc>> {
+        self.recurrence.as_ref()?.next_after(self.period_start?)
+    }
+
+    /// If the current period has ended by `now`, archive it and reset every item.
+    ///
+    /// Returns whether the checklist rolled over. Periods which passed entirely while nobody was
+    /// looking are not archived; the next run starts at the most recent period boundary.
+    pub async fn roll_over(&mut self, db: &Db, now: DateTime<Utc>) -> Result<bool> {
+        let (Some(recurrence), Some(started_at)) = (&self.recurrence, self.period_start) else {
+            return Ok(false);
+        };
+        let Some(ended_at) = recurrence.next_after(started_at).filter(|end| *end <= now) else {
+            return Ok(false);
+        };
+        let next_start = recurrence.current_start(ended_at, now);
+        db.authorize(&self.id, Access::Check).await?;
+
+        db.inner
+            .query(format!(
+                "BEGIN TRANSACTION;
+                 CREATE {RUN_TABLE} CONTENT {{
+                     checklist: $checklist,
+                     started_at: $started_at,
+                     ended_at: $ended_at,
+                     items: (SELECT item, checked, position FROM item WHERE checklist = $checklist ORDER BY position)
+                         .map(|$item| {{ item: $item.item, checked: $item.checked }}),
+                 }};
+                 UPDATE item SET checked = false WHERE checklist = $checklist;
+                 UPDATE $checklist SET period_start = $next_start;
+                 COMMIT TRANSACTION;"
+            ))
+            .bind(("checklist", RecordId::from(self.id.clone())))
+            .bind(("started_at", Value::from(started_at)))
+            .bind(("ended_at", Value::from(ended_at)))
+            .bind(("next_start", Value::from(next_start)))
+            .await
+            .and_then(surrealdb::Response::check)
+            .map_err(Error::surreal("rolling over checklist"))?;
+
+        self.period_start = Some(next_start);
+        // the transaction wrote the checklist exactly once
+        self.revision += 1;
+        Ok(true)
+    }
+
+    /// Roll over every recurring checklist whose period has ended by `now`, returning those which did.
+    ///
+    /// Only checklists in which the acting user may check items are rolled over, and locked
+    /// checklists are not.
+    pub async fn roll_over_due(db: &Db, now: DateTime<Utc>) -> Result<Vec<Self>> {
+        let recurring: Vec<Self> = db
+            .inner
+            .query(format!(
+                "SELECT * FROM {CHECKLIST_TABLE}
+                 WHERE recurrence != NONE AND period_start <= $now AND !locked AND {}",
+                access::permits(db, "id", Access::Check)
+            ))
+            .bind(("now", Value::from(now)))
+            .bind(("user", db.user_record()))
+            .await
+            .map_err(Error::surreal("finding recurring checklists"))?
+            .take(0)
+            .map_err(Error::surreal("finding recurring checklists"))?;
+
+        let mut rolled = Vec::new();
+        for mut checklist in recurring {
+            if checklist.roll_over(db, now).await? {
+                rolled.push(checklist);
+            }
+        }
+        Ok(rolled)
+    }
+
+    /// Archived runs of this checklist, most recent first.
+    pub async fn history(&self, db: &Db) -> Result<Vec<Run>> {
+        db.authorize(&self.id, Access::Read).await?;
+        db.inner
+            .query(format!(
+                "SELECT * FROM {RUN_TABLE} WHERE checklist = $checklist ORDER BY started_at DESC"
+            ))
+            .bind(("checklist", RecordId::from(self.id.clone())))
+            .await
+            .map_err(Error::surreal("loading checklist history"))?
+            .take(0)
+            .map_err(Error::surreal("loading checklist history"))
+    }
+
+    pub(crate) async fn delete_history(db: &Db, id: &ChecklistId) -> Result<()> {
+        db.inner
+            .query(format!("DELETE {RUN_TABLE} WHERE checklist = $checklist"))
+            .bind(("checklist", RecordId::from(id.clone())))
+            .await
+            .and_then(surrealdb::Response::check)
+            .map_err(Error::surreal("deleting checklist history"))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Optimistic concurrency.
+//!
+//! Every checklist and item has a revision, which the database increments on each write to it.
+//! Methods which take `&mut self` and write the changed record back into it, such as
+//! [`Item::set_checked`], are conditional: they only apply if the record is still at the revision
+//! `self` was loaded at, and otherwise fail with [`Error::Conflict`], so that the caller can reload
+//! and retry.
+
+use serde::de::DeserializeOwned;
+use surrealdb::RecordId;
+
+use crate::{Access, CheckedItem, Db, Error, Item, Result};
+
+/// Merge `changes` into `record`, provided that it is still at revision `expected`.
+///
+/// Returns the updated record.
+pub(crate) async fn update_if_current<T>(
+    db: &Db,
+    record: RecordId,
+    expected: u64,
+    changes: impl serde::Serialize + 'static,
+    context: &'static str,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    merge_if_current(db, record, expected, changes, "$changes", context).await
+}
+
+/// Like [`update_if_current`], but merges `merge`: an object expression which may refer to
+/// `$record` and `$changes`, for changes which depend on the state of the database when they apply.
+pub(crate) async fn merge_if_current<T>(
+    db: &Db,
+    record: RecordId,
+    expected: u64,
+    changes: impl serde::Serialize + 'static,
+    merge: &str,
+    context: &'static str,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    // records written before revisions existed have none; they count as revision 0
+    let mut response = db
+        .inner
+        .query(format!(
+            "UPDATE $record MERGE {merge} WHERE (revision ?? 0) = $expected;
+             SELECT VALUE revision ?? 0 FROM ONLY $record;",
+        ))
+        .bind(("record", record))
+        .bind(("changes", changes))
+        .bind(("expected", expected))
+        .await
+        .map_err(Error::surreal(context))?;
+    let updated = response
+        .take::<Option<T>>(0)
+        .map_err(Error::surreal(context))?;
+    if let Some(updated) = updated {
+        return Ok(updated);
+    }
+    match response
+        .take::<Option<u64>>(1)
+        .map_err(Error::surreal(context))?
+    {
+        Some(actual) => Err(Error::Conflict { expected, actual }),
+        None => Err(Error::MissingItem),
+    }
+}
+
+impl Item {
+    /// Check or uncheck this item.
+    ///
+    /// Fails with [`Error::Conflict`] rather than overwriting someone else's change if the item has
+    /// changed since it was loaded.
+    pub async fn set_checked(&mut self, db: &Db, checked: bool) -> Result<()> {
+        db.authorize_item(&self.id, Access::Check).await?;
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            CheckedItem { checked },
+            "updating checked item",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+//! Full-text search across checklist names and item text.
+//!
+//! Search uses surrealdb's own `SEARCH` indexes (see `schema.surreal`). That works because record
+//! contents are not yet encrypted item-by-item; if they ever are, this needs a library-side index instead.
+
+use surrealdb::RecordId;
+
+use crate::{Access, CHECKLIST_TABLE, ChecklistId, Db, Error, ITEM_TABLE, ItemId, Result, access};
+
+// The schema strips these control characters from checklist names and item text, so they make
+// unambiguous markers. Text written before it did may still contain them; see `SearchHit::try_from`.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// What a search hit refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchTarget {
+    Checklist(ChecklistId),
+    Item { id: ItemId, checklist: ChecklistId },
+}
+
+/// A run of text within a search hit, which either did or did not match the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    pub text: String,
+    pub matched: bool,
+}
+
+/// A single search result.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub target: SearchTarget,
+    /// Relevance according to BM25; only meaningful relative to other hits.
+    pub score: f32,
+    /// The full matched text, split into matching and non-matching fragments.
+    pub highlights: Vec<Fragment>,
+}
+
+impl SearchHit {
+    /// The matched text without highlighting.
+    pub fn text(&self) -> String {
+        self.highlights
+            .iter()
+            .map(|fragment| fragment.text.as_str())
+            .collect()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RawHit {
+    id: RecordId,
+    checklist: Option<ChecklistId>,
+    score: f32,
+    text: String,
+    highlight: String,
+}
+
+fn fragments(highlight: &str) -> Vec<Fragment> {
+    let mut out = Vec::new();
+    let mut push = |text: &str, matched| {
+        if !text.is_empty() {
+            out.push(Fragment {
+                text: text.to_owned(),
+                matched,
+            });
+        }
+    };
+
+    let mut rest = highlight;
+    while let Some((before, after)) = rest.split_once(MATCH_START) {
+        push(before, false);
+        let (matched, after) = after.split_once(MATCH_END).unwrap_or((after, ""));
+        push(matched, true);
+        rest = after;
+    }
+    push(rest, false);
+
+    out
+}
+
+impl TryFrom<RawHit> for SearchHit {
+    type Error = Error;
+
+    fn try_from(raw: RawHit) -> Result<Self> {
+        let target = match raw.checklist {
+            Some(checklist) => SearchTarget::Item {
+                id: raw.id.try_into()?,
+                checklist,
+            },
+            None => SearchTarget::Checklist(raw.id.try_into()?),
+        };
+        // the markers cannot be told apart from the text itself, so leave such text unhighlighted
+        let highlights = if raw.text.contains([MATCH_START, MATCH_END]) {
+            vec![Fragment {
+                text: raw.text,
+                matched: false,
+            }]
+        } else {
+            fragments(&raw.highlight)
+        };
+        Ok(Self {
+            target,
+            score: raw.score,
+            highlights,
+        })
+    }
+}
+
+/// Search checklist names and item text for `query`, returning hits ordered by relevance.
+///
+/// Every term of the query must match; terms are stemmed, so "deploying" matches "deployment". Only
+/// what the acting user can read is searched.
+pub async fn search(db: &Db, query: &str) -> Result<Vec<SearchHit>> {
+    // `search::highlight` loses its markers when the query is ordered, so we sort here instead
+    let mut response = db
+        .inner
+        .query(format!(
+            "SELECT id, NONE AS checklist, search::score(1) AS score, name AS text,
+                 search::highlight($start, $end, 1) AS highlight
+             FROM {CHECKLIST_TABLE} WHERE name @1@ $query AND {};
+             SELECT id, checklist, search::score(1) AS score, item AS text,
+                 search::highlight($start, $end, 1) AS highlight
+             FROM {ITEM_TABLE} WHERE item @1@ $query AND {};",
+            access::permits(db, "id", Access::Read),
+            access::permits(db, "checklist", Access::Read),
+        ))
+        .bind(("query", query.to_owned()))
+        .bind(("user", db.user_record()))
+        .bind(("start", MATCH_START.to_string()))
+        .bind(("end", MATCH_END.to_string()))
+        .await
+        .map_err(Error::surreal("searching"))?;
+
+    let mut hits = Vec::new();
+    for statement in 0..2 {
+        let raw = response
+            .take::<Vec<RawHit>>(statement)
+            .map_err(Error::surreal("reading search results"))?;
+        for raw in raw {
+            hits.push(SearchHit::try_from(raw)?);
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    Ok(hits)
+}
@@ -0,0 +1,248 @@
+//! Completion statistics: how far along checklists are, and how many items get checked over time.
+//!
+//! Progress counts leaf items, those with nothing nested under them, as
+//! [`ItemNode::progress`][crate::ItemNode::progress] does: an item with nested items is done when
+//! they are.
+//!
+//! The database stamps each item with the time it was checked, so how long a checklist took to
+//! complete is only known once every item was checked after those times began to be recorded.
+//! Trends are drawn from the op log instead, which keeps every check, including those since undone
+//! by unchecking an item or resetting a recurring checklist.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    Access, Checklist, ChecklistId, ChecklistQuery, Db, Error, ITEM_TABLE, ItemId, Result, access,
+    datetime,
+};
+
+/// What progress is computed from, for one item.
+#[derive(Debug, serde::Deserialize)]
+struct ItemState {
+    id: ItemId,
+    checklist: ChecklistId,
+    #[serde(default)]
+    parent: Option<ItemId>,
+    checked: bool,
+    #[serde(default, with = "datetime::option")]
+    checked_at: Option<DateTime<Utc>>,
+}
+
+/// How far along one checklist is, counting leaf items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Leaf items which are checked.
+    pub checked: usize,
+    /// All leaf items.
+    pub total: usize,
+    /// When the checklist's current period began if it recurs, or else when it was created.
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the last item was checked, if every item is and all their check times are known.
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Progress {
+    /// The progress of `checklist`, given the state of all its items.
+    fn of(checklist: &Checklist, items: &[&ItemState]) -> Self {
+        // ids are keyed by their text, since surrealdb's record keys make poor hash keys
+        let parents = items
+            .iter()
+            .filter_map(|item| Some(item.parent.as_ref()?.to_string()))
+            .collect::<HashSet<_>>();
+        let items = items
+            .iter()
+            .filter(|item| !parents.contains(&item.id.to_string()))
+            .collect::<Vec<_>>();
+        let checked = items.iter().filter(|item| item.checked).count();
+        let complete = !items.is_empty() && checked == items.len();
+        let completed_at = complete
+            .then(|| {
+                items
+                    .iter()
+                    .map(|item| item.checked_at)
+                    .collect::<Option<Vec<_>>>()
+            })
+            .flatten()
+            .and_then(|times| times.into_iter().max());
+        Self {
+            checked,
+            total: items.len(),
+            started_at: checklist.period_start.or(checklist.created_at),
+            completed_at,
+        }
+    }
+
+    /// Whether every item is checked; never true for a checklist without items.
+    pub fn is_complete(&self) -> bool {
+        self.total > 0 && self.checked == self.total
+    }
+
+    /// Percentage of items checked; 0 for a checklist without items.
+    pub fn percent(&self) -> f64 {
+        percent(self.checked, self.total)
+    }
+
+    /// How long it took from the start of the checklist to checking its last item.
+    pub fn time_to_complete(&self) -> Option<Duration> {
+        Some(self.completed_at? - self.started_at?)
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / whole as f64
+}
+
+/// Progress of every checklist the acting user can read, archived ones included; templates are
+/// left out.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    /// Each checklist with its progress, oldest first.
+    pub checklists: Vec<(Checklist, Progress)>,
+}
+
+impl Summary {
+    pub async fn gather(db: &Db) -> Result<Self> {
+        let query = ChecklistQuery::new().template(false);
+        let mut checklists = query.clone().fetch(db).await?.items;
+        checklists.extend(query.archived(true).fetch(db).await?.items);
+        checklists.sort_by_key(|checklist| checklist.created_at);
+
+        let states: Vec<ItemState> = db
+            .inner
+            .query(format!(
+                "SELECT id, checklist, parent, checked, checked_at FROM {ITEM_TABLE} WHERE {}",
+                access::permits(db, "checklist", Access::Read)
+            ))
+            .bind(("user", db.user_record()))
+            .await
+            .map_err(Error::surreal("gathering progress"))?
+            .take(0)
+            .map_err(Error::surreal("gathering progress"))?;
+        let mut by_checklist = HashMap::<_, Vec<_>>::new();
+        for state in &states {
+            by_checklist
+                .entry(state.checklist.to_string())
+                .or_default()
+                .push(state);
+        }
+        let checklists = checklists
+            .into_iter()
+            .map(|checklist| {
+                let items = by_checklist
+                    .get(&checklist.id.to_string())
+                    .map_or(&[][..], Vec::as_slice);
+                let progress = Progress::of(&checklist, items);
+                (checklist, progress)
+            })
+            .collect();
+        Ok(Self { checklists })
+    }
+
+    /// How many checklists have every item checked.
+    pub fn completed(&self) -> usize {
+        self.progress()
+            .filter(|progress| progress.is_complete())
+            .count()
+    }
+
+    /// How many items there are across all checklists.
+    pub fn total(&self) -> usize {
+        self.progress().map(|progress| progress.total).sum()
+    }
+
+    /// How many items are checked across all checklists.
+    pub fn checked(&self) -> usize {
+        self.progress().map(|progress| progress.checked).sum()
+    }
+
+    /// Percentage of items checked across all checklists.
+    pub fn percent(&self) -> f64 {
+        percent(self.checked(), self.total())
+    }
+
+    /// Mean time to complete, over the completed checklists for which it is known.
+    pub fn mean_time_to_complete(&self) -> Option<Duration> {
+        let times = self
+            .progress()
+            .filter_map(Progress::time_to_complete)
+            .collect::<Vec<_>>();
+        let count = i32::try_from(times.len()).ok().filter(|&count| count > 0)?;
+        Some(times.into_iter().sum::<Duration>() / count)
+    }
+
+    fn progress(&self) -> impl Iterator<Item = &Progress> {
+        self.checklists.iter().map(|(_, progress)| progress)
+    }
+}
+
+/// How many items were checked in each of `periods` consecutive periods of length `period`, the
+/// first starting at `since`.
+///
+/// A check is an existing item changing from unchecked to checked; items created already checked,
+/// such as copies, are not counted. Only checks in checklists the acting user can read are counted.
+/// Checks made on other devices count once they have been synced.
+pub async fn checks_per_period(
+    db: &Db,
+    since: DateTime<Utc>,
+    period: Duration,
+    periods: usize,
+) -> Result<Vec<usize>> {
+    let mut counts = vec![0; periods];
+    let period_ms = period.num_milliseconds();
+    if periods == 0 || period_ms <= 0 {
+        return Ok(counts);
+    }
+    let since_ms = since.timestamp_millis();
+    let until_ms = since_ms.saturating_add(period_ms.saturating_mul(periods as i64));
+
+    // an item's first op creates it, with every field; later ops carry only the fields which
+    // changed, so a later op setting `checked` to true checks an unchecked item
+    let walls: Vec<i64> = db
+        .inner
+        .query(format!(
+            "SELECT VALUE wall FROM op
+             WHERE record::tb(target) = '{ITEM_TABLE}' AND fields CONTAINS 'checked'
+                 AND changes.checked = true AND wall >= $since AND wall < $until
+                 AND (SELECT VALUE id FROM op WHERE target = $parent.target AND id < $parent.id LIMIT 1) != []
+                 AND {}",
+            access::permits(db, "checklists[0]", Access::Read)
+        ))
+        .bind(("since", since_ms))
+        .bind(("until", until_ms))
+        .bind(("user", db.user_record()))
+        .await
+        .map_err(Error::surreal("counting checks"))?
+        .take(0)
+        .map_err(Error::surreal("counting checks"))?;
+    for wall in walls {
+        let index = ((wall - since_ms) / period_ms) as usize;
+        if let Some(count) = counts.get_mut(index) {
+            *count += 1;
+        }
+    }
+    Ok(counts)
+}
+
+impl Checklist {
+    /// How far along this checklist is.
+    pub async fn progress(&self, db: &Db) -> Result<Progress> {
+        db.authorize(&self.id, Access::Read).await?;
+        let items: Vec<ItemState> = db
+            .inner
+            .query(format!(
+                "SELECT id, checklist, parent, checked, checked_at FROM {ITEM_TABLE} WHERE checklist = $checklist"
+            ))
+            .bind(("checklist", RecordId::from(self.id.clone())))
+            .await
+            .map_err(Error::surreal("getting progress"))?
+            .take(0)
+            .map_err(Error::surreal("getting progress"))?;
+        Ok(Progress::of(self, &items.iter().collect::<Vec<_>>()))
+    }
+}
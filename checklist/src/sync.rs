@@ -0,0 +1,550 @@
+//! Syncing checklists between devices.
+//!
+//! Every write to a checklist or item is recorded in an append-only operation log, by events
+//! defined in the schema, so that no write path can forget to. Each [`Op`] is stamped with a
+//! hybrid logical clock ([`Hlc`]): wall-clock milliseconds, a counter for events within the same
+//! millisecond, and the id of the database which made it. Clocks move forward past every op a
+//! database receives, so an op made after seeing another always sorts after it.
+//!
+//! Devices exchange ops with [`Db::export_ops_since`] and [`Db::apply_ops`], in any order and as
+//! often as they like, keeping track of how far they have got with [`Db::sync_state`]. The state
+//! of a record is derived from all of its ops, so every database which has seen the same ops
+//! agrees on it:
+//!
+//! - each field takes the value of the latest op which set it (last writer wins);
+//! - a deleted record stays deleted unless some op on it is later than the latest op its deleter
+//!   had seen, in which case the edit wins and the record is restored (add wins).
+//!
+//! Tags, notes, attachments, run history and data keys are not synced; each device keeps its own.
+//! Nor are users, grants, or assignments, and since ops carry every checklist, syncing needs full
+//! access (see [`Db::as_user`]). A checklist's owner is synced, though, as the id of a user on the
+//! device which created it, so that a private checklist stays closed to everyone else.
+
+use chrono::{DateTime, Utc};
+use surrealdb::RecordId;
+
+use crate::{
+    CHECKLIST_TABLE, ChecklistId, Db, Error, ITEM_TABLE, Result, copy, keys::DataKey,
+    txn::Transaction,
+};
+
+/// A hybrid logical clock timestamp: the order of ops across every database.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Hlc {
+    /// Milliseconds since the Unix epoch, or later if some clock ran ahead.
+    pub wall: u64,
+    /// Distinguishes ops within the same millisecond.
+    pub counter: u64,
+    /// Id of the database which made the op.
+    pub node: String,
+}
+
+/// How far a peer has read this database's op log.
+///
+/// Cursors are local to the database which issued them.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+    derive_more::Display,
+    derive_more::FromStr,
+)]
+#[serde(transparent)]
+pub struct OpCursor(pub(crate) u64);
+
+/// How far this database has synced with a peer.
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct SyncState {
+    /// Cursor into the peer's op log, up to which its ops have been applied here.
+    pub pulled: OpCursor,
+    /// Cursor into this database's op log, up to which its ops have been sent to the peer.
+    pub pushed: OpCursor,
+}
+
+/// The fields of a checklist which are synced, and so logged by the `checklist_log` event.
+pub(crate) const CHECKLIST_FIELDS: &[&str] = &[
+    "name",
+    "created_at",
+    "is_template",
+    "recurrence",
+    "period_start",
+    "archived",
+    "locked",
+    "auto_archive",
+    "owner",
+];
+
+/// The fields of an item which are synced, and so logged by the `item_log` event.
+pub(crate) const ITEM_FIELDS: &[&str] = &[
+    "checklist",
+    "parent",
+    "item",
+    "checked",
+    "position",
+    "created_at",
+    "due_at",
+    "remind_at",
+    "reminded",
+    "priority",
+    "checked_at",
+];
+
+/// Definitions of the events which log every write to a checklist or item, other than those made
+/// by [`Db::apply_ops`].
+pub(crate) fn log_events() -> String {
+    [
+        ("checklist_log", CHECKLIST_TABLE, CHECKLIST_FIELDS),
+        ("item_log", ITEM_TABLE, ITEM_FIELDS),
+    ]
+    .into_iter()
+    .map(|(event, table, fields)| {
+        let fields = fields
+            .iter()
+            .map(|field| format!("{field:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "DEFINE EVENT OVERWRITE {event} ON {table} WHEN $syncing != true THEN {{
+                 fn::log_op($event, $before, $after, [{fields}]);
+             }};"
+        )
+    })
+    .collect()
+}
+
+/// The synced fields of a checklist or item.
+///
+/// An op which sets a field to `NONE` carries it as `None`, so which fields an op sets is recorded
+/// separately.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+struct Fields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::datetime::option"
+    )]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    is_template: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::datetime::option"
+    )]
+    period_start: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archived: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    locked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto_archive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<RecordId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checklist: Option<RecordId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<RecordId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    item: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<i64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::datetime::option"
+    )]
+    due_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::datetime::option"
+    )]
+    remind_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reminded: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<i64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::datetime::option"
+    )]
+    checked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+enum Change {
+    /// Set each of `fields` to its value in `values`.
+    Set {
+        fields: Vec<String>,
+        values: Box<Fields>,
+    },
+    /// Delete the record, having seen every op on it up to `observed`.
+    Delete { observed: Option<Hlc> },
+}
+
+/// One change to one checklist or item, as recorded in the op log.
+///
+/// Ops are opaque: they exist to be carried between databases, in whatever serde format suits.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Op {
+    hlc: Hlc,
+    target: RecordId,
+    change: Change,
+}
+
+impl Op {
+    pub fn hlc(&self) -> &Hlc {
+        &self.hlc
+    }
+}
+
+/// An op as stored in the `op` table.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct OpRecord {
+    /// Position in the local log; assigned by the database.
+    #[serde(default, skip_serializing)]
+    seq: u64,
+    wall: u64,
+    counter: u64,
+    node: String,
+    target: RecordId,
+    #[serde(default)]
+    fields: Option<Vec<String>>,
+    #[serde(default)]
+    changes: Option<Fields>,
+    #[serde(default)]
+    observed: Option<(u64, u64, String)>,
+}
+
+impl From<OpRecord> for Op {
+    fn from(record: OpRecord) -> Self {
+        let change = match record.fields {
+            Some(fields) => Change::Set {
+                fields,
+                values: Box::new(record.changes.unwrap_or_default()),
+            },
+            None => Change::Delete {
+                observed: record.observed.map(|(wall, counter, node)| Hlc {
+                    wall,
+                    counter,
+                    node,
+                }),
+            },
+        };
+        Self {
+            hlc: Hlc {
+                wall: record.wall,
+                counter: record.counter,
+                node: record.node,
+            },
+            target: record.target,
+            change,
+        }
+    }
+}
+
+impl From<Op> for OpRecord {
+    fn from(op: Op) -> Self {
+        let (fields, changes, observed) = match op.change {
+            Change::Set { fields, values } => (Some(fields), Some(*values), None),
+            Change::Delete { observed } => (
+                None,
+                None,
+                observed.map(|hlc| (hlc.wall, hlc.counter, hlc.node)),
+            ),
+        };
+        Self {
+            seq: 0,
+            wall: op.hlc.wall,
+            counter: op.hlc.counter,
+            node: op.hlc.node,
+            target: op.target,
+            fields,
+            changes,
+            observed,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Clock {
+    wall: u64,
+    counter: u64,
+}
+
+/// The fields of a record given all of its ops in order, or `None` if it is deleted.
+fn fold<'a>(ops: impl IntoIterator<Item = &'a Op>) -> Result<Option<Fields>> {
+    let mut state = serde_json::Map::new();
+    let mut last_write = None;
+    let mut deleted_after = None;
+    let mut deleted = false;
+    for op in ops {
+        match &op.change {
+            Change::Set { fields, values } => {
+                let values =
+                    serde_json::to_value(values).map_err(Error::serialization("merging ops"))?;
+                for field in fields {
+                    let value = values.get(field).cloned().unwrap_or_default();
+                    state.insert(field.clone(), value);
+                }
+                last_write = Some(&op.hlc);
+            }
+            Change::Delete { observed } => {
+                deleted = true;
+                deleted_after = deleted_after.max(observed.as_ref());
+            }
+        }
+    }
+    // `None < Some(_)`: a write survives a delete whose deleter had not seen it
+    let exists = last_write.is_some() && (!deleted || last_write > deleted_after);
+    if !exists {
+        return Ok(None);
+    }
+    serde_json::from_value(state.into())
+        .map(Some)
+        .map_err(Error::serialization("merging ops"))
+}
+
+/// The checklists which `target` belongs or belonged to, given all of its ops.
+fn checklists_of(target: &RecordId, ops: &[Op]) -> Vec<RecordId> {
+    if target.table() == CHECKLIST_TABLE {
+        return vec![target.clone()];
+    }
+    let mut checklists = Vec::new();
+    for op in ops.iter().filter(|op| &op.target == target) {
+        if let Change::Set { values, .. } = &op.change
+            && let Some(checklist) = &values.checklist
+            && !checklists.contains(checklist)
+        {
+            checklists.push(checklist.clone());
+        }
+    }
+    checklists
+}
+
+impl Db {
+    /// Up to `limit` ops recorded or received by this database after `cursor`, in the order it
+    /// learned of them, and the cursor to pass next time.
+    ///
+    /// The cursor only stays the same once there are no more ops to export.
+    pub async fn export_ops_since(
+        &self,
+        cursor: OpCursor,
+        limit: u32,
+    ) -> Result<(Vec<Op>, OpCursor)> {
+        self.require_full_access()?;
+        let logged: Vec<OpRecord> = self
+            .inner
+            .query("SELECT * FROM op WHERE seq > $cursor ORDER BY seq LIMIT $limit")
+            .bind(("cursor", cursor.0))
+            .bind(("limit", limit))
+            .await
+            .map_err(Error::surreal("exporting ops"))?
+            .take(0)
+            .map_err(Error::surreal("exporting ops"))?;
+        let cursor = logged.last().map_or(cursor, |last| OpCursor(last.seq));
+        let ops = logged.into_iter().map(Op::from).collect();
+        Ok((ops, cursor))
+    }
+
+    /// The id this database stamps on the ops it makes, as [`Hlc::node`].
+    pub async fn node_id(&self) -> Result<String> {
+        self.inner
+            .query("SELECT VALUE node FROM ONLY clock:local")
+            .await
+            .map_err(Error::surreal("reading node id"))?
+            .take::<Option<String>>(0)
+            .map_err(Error::surreal("reading node id"))?
+            .ok_or(Error::MissingItem)
+    }
+
+    /// How far this database has synced with `peer`, as last recorded by [`Db::set_sync_state`].
+    ///
+    /// Peers are named however the caller likes, for example by url.
+    pub async fn sync_state(&self, peer: &str) -> Result<SyncState> {
+        let state: Option<SyncState> = self
+            .inner
+            .query("SELECT pulled, pushed FROM ONLY type::thing('peer', $peer)")
+            .bind(("peer", peer.to_owned()))
+            .await
+            .map_err(Error::surreal("loading sync state"))?
+            .take(0)
+            .map_err(Error::surreal("loading sync state"))?;
+        Ok(state.unwrap_or_default())
+    }
+
+    /// Record how far this database has synced with `peer`.
+    pub async fn set_sync_state(&self, peer: &str, state: SyncState) -> Result<()> {
+        self.inner
+            .query("UPSERT type::thing('peer', $peer) CONTENT $state")
+            .bind(("peer", peer.to_owned()))
+            .bind(("state", state))
+            .await
+            .map_err(Error::surreal("saving sync state"))?
+            .check()
+            .map_err(Error::surreal("saving sync state"))?;
+        Ok(())
+    }
+
+    /// Merge ops from another database into this one, returning how many were new.
+    ///
+    /// Ops which this database already has are skipped, so it is safe to apply the same ops
+    /// more than once. Either every op is applied, or none is.
+    pub async fn apply_ops(&self, ops: Vec<Op>) -> Result<usize> {
+        self.require_full_access()?;
+        let keys = ops
+            .iter()
+            .map(|op| (op.hlc.wall, op.hlc.counter, op.hlc.node.clone()))
+            .collect::<Vec<_>>();
+        let known: Vec<(u64, u64, String)> = self
+            .inner
+            .query(
+                "SELECT VALUE [wall, counter, node] FROM $keys.map(|$key| type::thing('op', $key))",
+            )
+            .bind(("keys", keys))
+            .await
+            .map_err(Error::surreal("finding known ops"))?
+            .take(0)
+            .map_err(Error::surreal("finding known ops"))?;
+        let mut new = Vec::<Op>::new();
+        for op in ops {
+            let key = (op.hlc.wall, op.hlc.counter, &op.hlc.node);
+            let seen = known.iter().any(|(w, c, n)| (*w, *c, n) == key)
+                || new.iter().any(|other| other.hlc == op.hlc);
+            if !seen {
+                new.push(op);
+            }
+        }
+        if new.is_empty() {
+            return Ok(0);
+        }
+
+        let mut targets = Vec::<RecordId>::new();
+        for op in &new {
+            if !targets.contains(&op.target) {
+                targets.push(op.target.clone());
+            }
+        }
+        let mut response = self
+            .inner
+            .query(
+                "SELECT * FROM op WHERE target IN $targets;
+                 $targets.filter(|$target| record::exists($target));
+                 SELECT wall, counter FROM ONLY clock:local;",
+            )
+            .bind(("targets", targets.clone()))
+            .await
+            .map_err(Error::surreal("loading ops to merge"))?;
+        let mut all = response
+            .take::<Vec<OpRecord>>(0)
+            .map_err(Error::surreal("loading ops to merge"))?
+            .into_iter()
+            .map(Op::from)
+            .chain(new.iter().cloned())
+            .collect::<Vec<_>>();
+        all.sort_by(|a, b| a.hlc.cmp(&b.hlc));
+        let existing = response
+            .take::<Vec<RecordId>>(1)
+            .map_err(Error::surreal("loading ops to merge"))?;
+        let clock = response
+            .take::<Option<Clock>>(2)
+            .map_err(Error::surreal("reading clock"))?
+            .ok_or(Error::MissingItem)?;
+
+        let mut txn = Transaction::begin(self);
+        txn.push("LET $syncing = true");
+
+        // never issue a timestamp at or before one already received
+        let latest = new
+            .iter()
+            .map(|op| &op.hlc)
+            .max()
+            .expect("new is not empty");
+        if (latest.wall, latest.counter) > (clock.wall, clock.counter) {
+            let wall = txn.bind(latest.wall);
+            let counter = txn.bind(latest.counter);
+            txn.push(format!(
+                "UPDATE clock:local SET wall = {wall}, counter = {counter}"
+            ));
+        }
+
+        let count = new.len();
+        for op in new {
+            let checklists = txn.bind(checklists_of(&op.target, &all));
+            let record = OpRecord::from(op);
+            let key = txn.bind((record.wall, record.counter, record.node.clone()));
+            let op = txn.bind(record);
+            txn.push(format!(
+                "CREATE type::thing('op', {key}) SET
+                     seq = fn::next_seq(),
+                     wall = {op}.wall,
+                     counter = {op}.counter,
+                     node = {op}.node,
+                     target = {op}.target,
+                     fields = {op}.fields,
+                     changes = {op}.changes,
+                     observed = {op}.observed,
+                     checklists = {checklists}"
+            ));
+        }
+
+        // checklists first, so that items never refer to a checklist which does not exist yet
+        targets.sort_by_key(|target| target.table() != CHECKLIST_TABLE);
+        for target in targets {
+            let state = fold(all.iter().filter(|op| op.target == target))?;
+            let is_new = !existing.contains(&target);
+            match state {
+                Some(fields) => {
+                    let record = txn.bind(target.clone());
+                    let values = txn.bind(fields);
+                    let tracked = if target.table() == CHECKLIST_TABLE {
+                        CHECKLIST_FIELDS
+                    } else {
+                        ITEM_FIELDS
+                    };
+                    // unset fields are absent from `values`, so this also clears them; but an owner
+                    // is only set on creation, and ops logged before owners were synced carry none
+                    let fields = tracked
+                        .iter()
+                        .map(|&field| match field {
+                            "owner" => format!("owner = {values}.owner ?? owner"),
+                            field => format!("{field} = {values}.{field}"),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    txn.push(format!("UPSERT {record} SET {fields}"));
+                    if is_new && target.table() == CHECKLIST_TABLE {
+                        DataKey::create_in(&mut txn, self, &ChecklistId::try_from(target)?);
+                    }
+                }
+                None if is_new => {}
+                None if target.table() == CHECKLIST_TABLE => {
+                    copy::delete_in(&mut txn, &ChecklistId::try_from(target)?);
+                }
+                None => {
+                    let record = txn.bind(target);
+                    txn.push(format!("DELETE {record}"));
+                }
+            }
+        }
+        txn.commit("applying ops").await?;
+
+        Ok(count)
+    }
+}
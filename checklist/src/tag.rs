@@ -0,0 +1,133 @@
+//! Tags on checklists and items.
+//!
+//! Tags are records in the `tag` table, keyed by their name. Tagging is a graph edge:
+//! `checklist->tagged->tag` or `item->tagged->tag`. Deleting a checklist or item removes its edges,
+//! and the schema deletes a tag along with its last edge.
+
+use surrealdb::RecordId;
+
+use crate::{Access, ChecklistId, Db, Error, Item, ItemId, ItemQuery, Result, txn::Transaction};
+
+pub(crate) const TAG_TABLE: &str = "tag";
+
+/// The name of a tag.
+///
+/// Tag names are trimmed and lowercased, so `Urgent` and ` urgent` are the same tag.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    derive_more::Deref,
+    derive_more::Display,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Tag(String);
+
+impl Tag {
+    pub fn new(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err(Error::EmptyTag);
+        }
+        Ok(Self(name))
+    }
+
+    pub(crate) fn record(&self) -> RecordId {
+        RecordId::from_table_key(TAG_TABLE, self.0.as_str())
+    }
+
+    /// All tags in use, in alphabetical order.
+    pub async fn all(db: &Db) -> Result<Vec<Self>> {
+        // tags left without edges before the schema cleaned them up are not in use
+        db.inner
+            .query(format!(
+                "SELECT VALUE name FROM {TAG_TABLE} WHERE count(<-tagged) > 0 ORDER BY name"
+            ))
+            .await
+            .map_err(Error::surreal("loading tags"))?
+            .take(0)
+            .map_err(Error::surreal("loading tags"))
+    }
+
+    /// Tag `target` with this tag. Tagging something twice is not an error.
+    pub async fn add(&self, db: &Db, target: impl Into<Taggable>) -> Result<()> {
+        let target = target.into();
+        target.authorize(db, Access::Edit).await?;
+        let mut txn = Transaction::begin(db);
+        let tag = txn.bind(self.record());
+        let name = txn.bind(self.0.clone());
+        let target = txn.bind(target.record());
+        txn.push(format!("UPSERT {tag} SET name = {name}"));
+        txn.push(format!(
+            "IF (SELECT VALUE id FROM tagged WHERE in = {target} AND out = {tag}).len() = 0 {{ RELATE {target}->tagged->{tag} }}"
+        ));
+        txn.commit("adding tag").await
+    }
+
+    /// Remove this tag from `target`. Removing a tag which is not present is not an error.
+    pub async fn remove(&self, db: &Db, target: impl Into<Taggable>) -> Result<()> {
+        let target = target.into();
+        target.authorize(db, Access::Edit).await?;
+        db.inner
+            .query("DELETE tagged WHERE in = $target AND out = $tag")
+            .bind(("tag", self.record()))
+            .bind(("target", target.record()))
+            .await
+            .and_then(surrealdb::Response::check)
+            .map_err(Error::surreal("removing tag"))?;
+        Ok(())
+    }
+
+    /// Tags on `target`, in alphabetical order.
+    pub async fn of(db: &Db, target: impl Into<Taggable>) -> Result<Vec<Self>> {
+        let target = target.into();
+        target.authorize(db, Access::Read).await?;
+        let mut tags: Vec<Self> = db
+            .inner
+            .query("RETURN $target->tagged->tag.name")
+            .bind(("target", target.record()))
+            .await
+            .map_err(Error::surreal("loading tags"))?
+            .take::<Vec<Self>>(0)
+            .map_err(Error::surreal("loading tags"))?;
+        tags.sort();
+        Ok(tags)
+    }
+
+    /// All items with this tag, across all checklists the acting user can read.
+    pub async fn items(&self, db: &Db) -> Result<Vec<Item>> {
+        ItemQuery::new()
+            .tagged(self.clone())
+            .fetch(db)
+            .await
+            .map(|page| page.items)
+    }
+}
+
+/// Something which can be tagged.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::From)]
+pub enum Taggable {
+    Checklist(ChecklistId),
+    Item(ItemId),
+}
+
+impl Taggable {
+    fn record(self) -> RecordId {
+        match self {
+            Taggable::Checklist(id) => id.into(),
+            Taggable::Item(id) => id.into(),
+        }
+    }
+
+    async fn authorize(&self, db: &Db, access: Access) -> Result<()> {
+        match self {
+            Taggable::Checklist(id) => db.authorize(id, access).await,
+            Taggable::Item(id) => db.authorize_item(id, access).await,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Checklists which serve as templates for other checklists.
+//!
+//! Instantiating a template copies its name, items, nesting, order and tags, but not whether
+//! items are checked, nor due dates, reminders or recurrence. Names and item text may contain
+//! placeholders like `{{service}}`, which are substituted at instantiation time.
+
+use std::collections::{BTreeSet, HashMap};
+
+use surrealdb::RecordId;
+
+use crate::{
+    Access, CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, ITEM_TABLE, ItemId, ItemNode,
+    NewChecklist, Result,
+    copy::{TagEdge, tags_of},
+    keys::DataKey,
+    revision::update_if_current,
+    txn::{Transaction, new_key},
+};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Split `text` into literal text and placeholder names, passing each to `visit`.
+fn parts<'a>(text: &'a str, mut visit: impl FnMut(Part<'a>)) {
+    let mut rest = text;
+    while let Some((before, after)) = rest.split_once(OPEN) {
+        visit(Part::Text(before));
+        match after.split_once(CLOSE) {
+            Some((name, after)) => {
+                visit(Part::Placeholder(name.trim()));
+                rest = after;
+            }
+            None => {
+                // an unclosed placeholder is just text
+                visit(Part::Text(OPEN));
+                rest = after;
+            }
+        }
+    }
+    visit(Part::Text(rest));
+}
+
+/// Replace every placeholder in `text` with its value in `vars`.
+fn substitute(text: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut missing = None;
+    parts(text, |part| match part {
+        Part::Text(text) => out.push_str(text),
+        Part::Placeholder(name) => match vars.get(name) {
+            Some(value) => out.push_str(value),
+            None => {
+                missing.get_or_insert_with(|| name.to_owned());
+            }
+        },
+    });
+    match missing {
+        Some(name) => Err(Error::MissingTemplateVariable { name }),
+        None => Ok(out),
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SetTemplate {
+    is_template: bool,
+}
+
+/// An item to create, with its text already substituted.
+struct Planned {
+    source: ItemId,
+    text: String,
+    children: Vec<Planned>,
+}
+
+/// An instantiated item, as inserted.
+#[derive(Debug, serde::Serialize)]
+struct NewItem {
+    id: ItemId,
+    checklist: ChecklistId,
+    parent: Option<ItemId>,
+    item: String,
+    position: i64,
+}
+
+fn plan(node: ItemNode, vars: &HashMap<String, String>) -> Result<Planned> {
+    Ok(Planned {
+        text: substitute(&node.item.item, vars)?,
+        source: node.item.id,
+        children: node
+            .children
+            .into_iter()
+            .map(|child| plan(child, vars))
+            .collect::<Result<_>>()?,
+    })
+}
+
+impl Checklist {
+    /// Mark this checklist as a template, or not.
+    ///
+    /// Fails with [`Error::Conflict`] if the checklist has changed since it was loaded.
+    pub async fn set_template(&mut self, db: &Db, is_template: bool) -> Result<()> {
+        db.authorize(&self.id, Access::Edit).await?;
+        *self = update_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            SetTemplate { is_template },
+            "marking template",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Names of the placeholders used in this checklist's name and items, in alphabetical order.
+    pub async fn placeholders(&self, db: &Db) -> Result<Vec<String>> {
+        let mut names = BTreeSet::new();
+        let mut collect = |text: &str| {
+            parts(text, |part| {
+                if let Part::Placeholder(name) = part {
+                    names.insert(name.to_owned());
+                }
+            })
+        };
+        collect(&self.name);
+        for item in self.items(db).await? {
+            collect(&item.item);
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    /// Create a new checklist from the template `template_id`, filling placeholders from `vars`.
+    ///
+    /// Every placeholder must have a value; this is checked before anything is created. The new
+    /// checklist, its items and its tags are created in a single transaction. It belongs to the
+    /// acting user, who needs only read access to the template.
+    pub async fn instantiate(
+        db: &Db,
+        template_id: ChecklistId,
+        vars: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let template = Self::load(db, template_id)
+            .await?
+            .ok_or(Error::MissingItem)?;
+        if !template.is_template {
+            return Err(Error::NotATemplate);
+        }
+
+        let name = substitute(&template.name, vars)?;
+        let plans = template
+            .tree(db)
+            .await?
+            .into_iter()
+            .map(|node| plan(node, vars))
+            .collect::<Result<Vec<_>>>()?;
+
+        let checklist = ChecklistId::new(new_key());
+        // depth-first, so that positions keep the template's order
+        let mut new_ids = Vec::new();
+        let mut items = Vec::new();
+        let mut stack = plans
+            .into_iter()
+            .rev()
+            .map(|plan| (None, plan))
+            .collect::<Vec<_>>();
+        while let Some((parent, plan)) = stack.pop() {
+            let id = ItemId::new(new_key());
+            new_ids.push((plan.source, id.clone()));
+            stack.extend(
+                plan.children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (Some(id.clone()), child)),
+            );
+            items.push(NewItem {
+                id,
+                checklist: checklist.clone(),
+                parent,
+                item: plan.text,
+                position: items.len() as _,
+            });
+        }
+        let tags = tags_of(db, &template.id)
+            .await?
+            .into_iter()
+            .filter_map(|edge| {
+                let tagged = if edge.tagged == RecordId::from(template.id.clone()) {
+                    checklist.clone().into()
+                } else {
+                    let source = ItemId::try_from(edge.tagged).ok()?;
+                    new_ids
+                        .iter()
+                        .find_map(|(from, to)| (*from == source).then(|| to.clone()))?
+                        .into()
+                };
+                Some(TagEdge {
+                    tagged,
+                    out: edge.out,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut txn = Transaction::begin(db);
+        let record = txn.bind(RecordId::from(checklist.clone()));
+        let content = txn.bind(NewChecklist {
+            name: name.into(),
+            owner: db.user.clone(),
+        });
+        txn.push(format!("CREATE {record} CONTENT {content}"));
+        DataKey::create_in(&mut txn, db, &checklist);
+        if !items.is_empty() {
+            let items = txn.bind(items);
+            txn.push(format!("INSERT INTO {ITEM_TABLE} {items}"));
+        }
+        if !tags.is_empty() {
+            let tags = txn.bind(tags);
+            txn.push(format!("INSERT RELATION INTO tagged {tags}"));
+        }
+        txn.commit("instantiating template").await?;
+
+        Self::load(db, checklist).await?.ok_or(Error::FailedCreate {
+            resource: CHECKLIST_TABLE,
+        })
+    }
+}
@@ -0,0 +1,210 @@
+//! Items nested under other items.
+//!
+//! Each item may have a `parent` in the same checklist. Children are stored flat, like any other
+//! item; the tree is assembled on read. Moving an item therefore moves its whole subtree, and
+//! deleting an item deletes its subtree (the schema cascades deletes along `parent`).
+
+use std::borrow::Cow;
+
+use surrealdb::RecordId;
+
+use crate::{
+    Access, Checklist, ChecklistId, Db, Error, Item, ItemId, ItemQuery, Result, end_position,
+    revision::merge_if_current,
+};
+
+/// An item together with the items nested under it.
+#[derive(Debug, Clone)]
+pub struct ItemNode {
+    pub item: Item,
+    /// Whether this item itself is checked. See [`ItemNode::is_complete`] for derived completion.
+    pub checked: bool,
+    /// Nested items, in checklist order.
+    pub children: Vec<ItemNode>,
+}
+
+impl ItemNode {
+    /// Whether this item is complete.
+    ///
+    /// An item without children is complete when it is checked. An item with children is complete
+    /// when all of its children are complete, regardless of whether it is checked itself.
+    pub fn is_complete(&self) -> bool {
+        if self.children.is_empty() {
+            self.checked
+        } else {
+            self.children.iter().all(ItemNode::is_complete)
+        }
+    }
+
+    /// Count of `(complete, total)` leaf items in this subtree.
+    pub fn progress(&self) -> (usize, usize) {
+        if self.children.is_empty() {
+            return (usize::from(self.checked), 1);
+        }
+        self.children
+            .iter()
+            .map(ItemNode::progress)
+            .fold((0, 0), |(done, total), (d, t)| (done + d, total + t))
+    }
+
+    /// Visit this node and its descendants depth-first, along with their depth below this node.
+    pub fn walk(&self, visit: &mut impl FnMut(&ItemNode, usize)) {
+        self.walk_at(0, visit);
+    }
+
+    fn walk_at(&self, depth: usize, visit: &mut impl FnMut(&ItemNode, usize)) {
+        visit(self, depth);
+        for child in &self.children {
+            child.walk_at(depth + 1, visit);
+        }
+    }
+}
+
+/// Assemble the items of `checklist` into trees, returning the top-level nodes.
+async fn build(db: &Db, checklist: &ChecklistId) -> Result<Vec<ItemNode>> {
+    let items = ItemQuery::in_checklist(checklist.clone())
+        .fetch(db)
+        .await?
+        .items;
+    let checked = ItemQuery::in_checklist(checklist.clone())
+        .checked(true)
+        .fetch(db)
+        .await?
+        .items
+        .into_iter()
+        .map(|item| item.id)
+        .collect::<Vec<_>>();
+
+    let present = items.iter().map(|item| item.id.clone()).collect::<Vec<_>>();
+    let mut items = items
+        .into_iter()
+        .map(|item| {
+            // an item whose parent is gone would otherwise vanish; show it at the top level instead
+            let parent = item
+                .parent
+                .clone()
+                .filter(|parent| present.contains(parent));
+            (parent, item)
+        })
+        .collect::<Vec<_>>();
+
+    // checklists are short, so repeatedly scanning for each node's children is fine
+    fn assemble(
+        parent: Option<&ItemId>,
+        items: &mut Vec<(Option<ItemId>, Item)>,
+        checked: &[ItemId],
+    ) -> Vec<ItemNode> {
+        let (children, rest) = std::mem::take(items)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(item_parent, _)| item_parent.as_ref() == parent);
+        *items = rest;
+        children
+            .into_iter()
+            .map(|(_, item)| {
+                let children = assemble(Some(&item.id), items, checked);
+                ItemNode {
+                    checked: checked.contains(&item.id),
+                    item,
+                    children,
+                }
+            })
+            .collect()
+    }
+
+    Ok(assemble(None, &mut items, &checked))
+}
+
+impl Checklist {
+    /// All items in this checklist, arranged as trees by nesting.
+    pub async fn tree(&self, db: &Db) -> Result<Vec<ItemNode>> {
+        db.authorize(&self.id, Access::Read).await?;
+        build(db, &self.id).await
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MoveItem {
+    parent: Option<ItemId>,
+}
+
+impl Item {
+    /// Create a new item nested under `parent`, in the parent's checklist.
+    pub async fn new_child(
+        db: &Db,
+        parent: ItemId,
+        item: impl Into<Cow<'static, str>>,
+    ) -> Result<Self> {
+        let parent = Self::load(db, parent).await?.ok_or(Error::MissingItem)?;
+        Self::create(db, parent.checklist, Some(parent.id), item.into()).await
+    }
+
+    /// Items nested directly under this one, in checklist order.
+    pub async fn children(&self, db: &Db) -> Result<Vec<Self>> {
+        ItemQuery::new()
+            .children_of(self.id.clone())
+            .fetch(db)
+            .await
+            .map(|page| page.items)
+    }
+
+    /// This item and everything nested under it.
+    pub async fn subtree(&self, db: &Db) -> Result<ItemNode> {
+        db.authorize_item(&self.id, Access::Read).await?;
+        let mut found = None;
+        for root in build(db, &self.checklist).await? {
+            root.walk(&mut |node, _| {
+                if found.is_none() && node.item.id == self.id {
+                    found = Some(node.clone());
+                }
+            });
+        }
+        found.ok_or(Error::MissingItem)
+    }
+
+    /// Whether this item is complete, derived from its children if it has any.
+    ///
+    /// See [`ItemNode::is_complete`].
+    pub async fn is_complete(&self, db: &Db) -> Result<bool> {
+        self.subtree(db).await.map(|node| node.is_complete())
+    }
+
+    /// Move this item, along with everything nested under it, under `parent`.
+    ///
+    /// `None` moves it to the top level. Either way it goes to the end of its new siblings.
+    /// Fails with [`Error::Conflict`] if the item has changed since it was loaded.
+    pub async fn move_under(&mut self, db: &Db, parent: Option<ItemId>) -> Result<()> {
+        db.authorize_item(&self.id, Access::Edit).await?;
+        if let Some(parent) = &parent {
+            let mut ancestor = Self::load(db, parent.clone())
+                .await?
+                .ok_or(Error::MissingItem)?;
+            if ancestor.checklist != self.checklist {
+                return Err(Error::ParentInOtherChecklist);
+            }
+            loop {
+                if ancestor.id == self.id {
+                    return Err(Error::ParentCycle);
+                }
+                let Some(next) = ancestor.parent else {
+                    break;
+                };
+                ancestor = Self::load(db, next).await?.ok_or(Error::MissingItem)?;
+            }
+        }
+
+        *self = merge_if_current(
+            db,
+            RecordId::from(self.id.clone()),
+            self.revision,
+            MoveItem { parent },
+            &format!(
+                "{{ parent: $changes.parent, position: {} }}",
+                end_position("$record.checklist")
+            ),
+            "moving item",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Multi-statement transactions.
+//!
+//! The embedded client has no interactive transactions: a transaction is a single query with
+//! `BEGIN` and `COMMIT` around its statements. So everything a transaction needs must be known up
+//! front, including the ids of any records it creates, which are therefore generated here.
+
+use surrealdb::RecordIdKey;
+
+use crate::{Db, Error, Result};
+
+type Query<'r> = surrealdb::method::Query<'r, surrealdb::engine::local::Db>;
+
+/// Statements which are committed together or not at all.
+pub(crate) struct Transaction<'r> {
+    query: Option<Query<'r>>,
+    params: usize,
+}
+
+impl<'r> Transaction<'r> {
+    pub(crate) fn begin(db: &'r Db) -> Self {
+        Self {
+            query: Some(db.inner.query("BEGIN TRANSACTION")),
+            params: 0,
+        }
+    }
+
+    fn map(&mut self, f: impl FnOnce(Query<'r>) -> Query<'r>) {
+        let query = self
+            .query
+            .take()
+            .expect("query is only absent during `map`");
+        self.query = Some(f(query));
+    }
+
+    /// Bind `value` to a fresh parameter, returning its name (including the `$`) for use in statements.
+    pub(crate) fn bind(&mut self, value: impl serde::Serialize + 'static) -> String {
+        let name = format!("p{}", self.params);
+        self.params += 1;
+        self.map(|query| query.bind((name.clone(), value)));
+        format!("${name}")
+    }
+
+    /// Append a statement.
+    pub(crate) fn push(&mut self, statement: impl Into<String>) {
+        let statement = statement.into();
+        self.map(|query| query.query(statement));
+    }
+
+    /// Run every statement, failing if any of them failed.
+    pub(crate) async fn commit(mut self, context: &'static str) -> Result<()> {
+        self.push("COMMIT TRANSACTION");
+        let mut response = self
+            .query
+            .take()
+            .expect("query is only absent during `map`")
+            .await
+            .map_err(Error::surreal(context))?;
+        // once one statement fails, every other statement reports only that the transaction
+        // failed; report the one which caused it
+        let cause = response
+            .take_errors()
+            .into_iter()
+            .min_by_key(|(index, err)| {
+                let not_executed = matches!(
+                    err,
+                    surrealdb::Error::Db(surrealdb::error::Db::QueryNotExecuted)
+                );
+                (not_executed, *index)
+            });
+        match cause {
+            Some((_, err)) => Err(Error::surreal(context)(err)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A random record key, in the same format surrealdb generates.
+pub(crate) fn new_key() -> RecordIdKey {
+    RecordIdKey::from(surrealdb::sql::Id::rand().to_raw())
+}
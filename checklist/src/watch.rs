@@ -0,0 +1,233 @@
+//! Following changes as they happen.
+//!
+//! Changes to synced fields are read from the operation log (see [`Db::export_ops_since`]) through
+//! a live query, so they include changes merged in from other devices, and every change has a
+//! position in the log from which a watcher which lost its connection can pick up again.
+//!
+//! Changes to the other fields of checklists and items, such as an item's assignee, are local to a
+//! database and so are not in the log. They are read from live queries on the `checklist` and
+//! `item` tables instead, and reported as they happen, but not again to a watcher which resumes
+//! after missing them. Changes to shares, notes, attachments and tags are not reported.
+
+use futures::{Stream, StreamExt as _, TryStreamExt as _, stream};
+use surrealdb::{Action, Notification, RecordId};
+
+use crate::{
+    Access, CHECKLIST_TABLE, Checklist, ChecklistId, Db, Error, ITEM_TABLE, Item, ItemId, OpCursor,
+    Result,
+    sync::{CHECKLIST_FIELDS, ITEM_FIELDS},
+};
+
+/// Fields which change with every write, or are not stored, and so are no change of their own.
+const DERIVED_FIELDS: &[&str] = &["id", "revision", "items"];
+
+/// The fields whose values differ between `$before` and `$after`, which closures cannot see.
+const CHANGED_FIELDS: &str =
+    "(SELECT VALUE $this FROM array::union(object::keys($before), object::keys($after))
+    WHERE $before[$this] != $after[$this])";
+
+/// A checklist or item which changed, as reported by [`Db::changes`].
+#[derive(Debug, Clone)]
+pub struct Change {
+    /// Pass to [`Db::changes`] to resume after this change.
+    ///
+    /// A change which is not synced has no place in the log, and carries the cursor of the change
+    /// reported before it.
+    pub cursor: OpCursor,
+    /// The checklists the record belongs to; for an item which moved, both old and new.
+    pub checklists: Vec<ChecklistId>,
+    pub record: Changed,
+}
+
+/// What changed, and its state after the change; `None` if it was deleted, or moved where the
+/// acting user cannot read it.
+///
+/// The state is read when the change is reported, so it may already include later changes.
+#[derive(Debug, Clone)]
+pub enum Changed {
+    Checklist(ChecklistId, Option<Checklist>),
+    Item(ItemId, Option<Item>),
+}
+
+/// The parts of an op needed to report it.
+#[derive(Debug, serde::Deserialize)]
+struct Logged {
+    seq: u64,
+    target: RecordId,
+    #[serde(default)]
+    checklists: Vec<ChecklistId>,
+}
+
+/// A checklist or item whose fields which are not synced changed.
+#[derive(Debug, serde::Deserialize)]
+struct Unsynced {
+    id: RecordId,
+    /// The item's checklist; `None` for a checklist.
+    #[serde(default)]
+    checklist: Option<ChecklistId>,
+}
+
+/// A change reported by one of the live queries behind [`Db::changes`].
+enum Live {
+    Logged(Logged),
+    Unsynced(Unsynced),
+}
+
+/// A live query for writes to `table` which change some field other than its `synced` ones, but
+/// none of those, which are reported from the op log instead.
+fn unsynced_changes(table: &str, synced: &[&str]) -> String {
+    let list = |fields: &mut dyn Iterator<Item = &&str>| {
+        let fields = fields
+            .map(|field| format!("{field:?}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("[{fields}]")
+    };
+    let ignored = list(&mut synced.iter().chain(DERIVED_FIELDS));
+    let synced = list(&mut synced.iter());
+    let checklist = match table {
+        ITEM_TABLE => ", checklist",
+        _ => "",
+    };
+    format!(
+        "LIVE SELECT id{checklist} FROM {table} WHERE $event = 'UPDATE'
+             AND {CHANGED_FIELDS}.complement({ignored}) != []
+             AND {CHANGED_FIELDS}.intersect({synced}) = []"
+    )
+}
+
+impl Db {
+    /// Changes to checklists and items after `since`, followed by every change from now on.
+    ///
+    /// Without `since`, only changes from now on are reported. With a `checklist`, only changes to
+    /// that checklist and its items are reported, and only those the acting user can read are
+    /// reported at all. The stream never ends of its own accord; drop it to stop watching.
+    ///
+    /// Changes which are not synced are only reported from now on; see the [module docs](self).
+    pub async fn changes(
+        &self,
+        checklist: Option<ChecklistId>,
+        since: Option<OpCursor>,
+    ) -> Result<impl Stream<Item = Result<Change>> + '_> {
+        // subscribe before catching up, so that nothing slips between the two
+        let live = self
+            .inner
+            .query("LIVE SELECT * FROM op")
+            .await
+            .map_err(Error::surreal("watching changes"))?
+            .stream::<Notification<Logged>>(0)
+            .map_err(Error::surreal("watching changes"))?;
+        let mut unsynced = self
+            .inner
+            .query(unsynced_changes(CHECKLIST_TABLE, CHECKLIST_FIELDS))
+            .query(unsynced_changes(ITEM_TABLE, ITEM_FIELDS))
+            .await
+            .map_err(Error::surreal("watching changes"))?;
+        let unsynced = stream::select(
+            unsynced
+                .stream::<Notification<Unsynced>>(0)
+                .map_err(Error::surreal("watching changes"))?,
+            unsynced
+                .stream::<Notification<Unsynced>>(1)
+                .map_err(Error::surreal("watching changes"))?,
+        );
+        let logged: Vec<Logged> = match since {
+            None => Vec::new(),
+            Some(since) => self
+                .inner
+                .query("SELECT seq, target, checklists FROM op WHERE seq > $since ORDER BY seq")
+                .bind(("since", since.0))
+                .await
+                .map_err(Error::surreal("catching up on changes"))?
+                .take(0)
+                .map_err(Error::surreal("catching up on changes"))?,
+        };
+        let caught_up = logged
+            .last()
+            .map(|last| last.seq)
+            .or(since.map(|since| since.0))
+            .unwrap_or_default();
+
+        let live = live.filter_map(move |notification| async move {
+            match notification {
+                Ok(Notification {
+                    action: Action::Create,
+                    data,
+                    ..
+                }) if data.seq > caught_up => Some(Ok(Live::Logged(data))),
+                Ok(_) => None,
+                Err(err) => Some(Err(Error::surreal("watching changes")(err))),
+            }
+        });
+        let unsynced = unsynced.map(|notification| {
+            notification
+                .map(|notification| Live::Unsynced(notification.data))
+                .map_err(Error::surreal("watching changes"))
+        });
+        // an unsynced change takes its place in the log after the change reported before it
+        let mut last = caught_up;
+        let live = stream::select(live, unsynced).and_then(move |change| {
+            let logged = match change {
+                Live::Logged(logged) => {
+                    last = logged.seq;
+                    Ok(logged)
+                }
+                Live::Unsynced(Unsynced { id, checklist }) => checklist
+                    .map_or_else(|| ChecklistId::try_from(id.clone()), Ok)
+                    .map(|checklist| Logged {
+                        seq: last,
+                        target: id,
+                        checklists: vec![checklist],
+                    }),
+            };
+            std::future::ready(logged)
+        });
+        let relevant = move |logged: &Logged| {
+            checklist
+                .as_ref()
+                .is_none_or(|checklist| logged.checklists.contains(checklist))
+        };
+        Ok(stream::iter(logged.into_iter().map(Ok))
+            .chain(live)
+            .try_filter(move |logged| std::future::ready(relevant(logged)))
+            .try_filter_map(move |logged| self.change(logged)))
+    }
+
+    /// The change recorded by `logged`, unless the acting user may not see it.
+    async fn change(&self, logged: Logged) -> Result<Option<Change>> {
+        if !self.may_read_any(&logged.checklists).await? {
+            return Ok(None);
+        }
+        let record = if logged.target.table() == CHECKLIST_TABLE {
+            let id = ChecklistId::try_from(logged.target)?;
+            Changed::Checklist(id.clone(), visible(Checklist::load(self, id).await)?)
+        } else {
+            let id = ItemId::try_from(logged.target)?;
+            Changed::Item(id.clone(), visible(Item::load(self, id).await)?)
+        };
+        Ok(Some(Change {
+            cursor: OpCursor(logged.seq),
+            checklists: logged.checklists,
+            record,
+        }))
+    }
+
+    async fn may_read_any(&self, checklists: &[ChecklistId]) -> Result<bool> {
+        for checklist in checklists {
+            match self.authorize(checklist, Access::Read).await {
+                Ok(()) => return Ok(true),
+                Err(Error::Forbidden) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// A record which the acting user may no longer read is as good as gone.
+fn visible<T>(loaded: Result<Option<T>>) -> Result<Option<T>> {
+    match loaded {
+        Err(Error::Forbidden) => Ok(None),
+        loaded => loaded,
+    }
+}
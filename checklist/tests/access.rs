@@ -0,0 +1,134 @@
+//! What a handle may do depends on whom it acts as.
+
+mod common;
+
+use checklist::{Access, Attachment, Checklist, Db, Error, Item, User};
+use tempfile::TempDir;
+
+use common::open;
+
+async fn names(db: &Db) -> Vec<String> {
+    Checklist::all(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|checklist| checklist.name.into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn without_a_user_only_checklists_without_an_owner_are_open() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let alice = User::new(&db, "alice").await.unwrap();
+    let as_alice = db.as_user(alice.id);
+    let diary = Checklist::new(&as_alice, "diary").await.unwrap();
+    Checklist::new(&db, "shopping").await.unwrap();
+
+    assert_eq!(names(&db).await, ["shopping"]);
+    assert!(matches!(
+        Checklist::load(&db, diary.id.clone()).await,
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        db.export_ops_since(Default::default(), 1).await,
+        Err(Error::Forbidden)
+    ));
+
+    let full = db.with_full_access();
+    assert_eq!(names(&full).await, ["diary", "shopping"]);
+    assert!(full.export_ops_since(Default::default(), 1).await.is_ok());
+}
+
+#[tokio::test]
+async fn sharing_again_replaces_the_access_granted() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let alice = User::new(&db, "alice").await.unwrap();
+    let bob = User::new(&db, "bob").await.unwrap();
+    let (as_alice, as_bob) = (db.as_user(alice.id), db.as_user(bob.id.clone()));
+    let chores = Checklist::new(&as_alice, "chores").await.unwrap();
+
+    Checklist::share(&as_alice, chores.id.clone(), bob.id.clone(), Access::Edit)
+        .await
+        .unwrap();
+    Item::new(&as_bob, chores.id.clone(), "dishes")
+        .await
+        .unwrap();
+
+    Checklist::share(&as_alice, chores.id.clone(), bob.id.clone(), Access::Read)
+        .await
+        .unwrap();
+    let grants = Checklist::grants(&as_alice, chores.id.clone())
+        .await
+        .unwrap();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0].access, Access::Read);
+    assert_eq!(names(&as_bob).await, ["chores"]);
+    assert!(matches!(
+        Item::new(&as_bob, chores.id.clone(), "laundry").await,
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        Checklist::share(&as_bob, chores.id, bob.id, Access::Edit).await,
+        Err(Error::Forbidden)
+    ));
+}
+
+#[tokio::test]
+async fn uploaders_may_remove_their_own_attachments() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let alice = User::new(&db, "alice").await.unwrap();
+    let bob = User::new(&db, "bob").await.unwrap();
+    let (as_alice, as_bob) = (db.as_user(alice.id), db.as_user(bob.id.clone()));
+    let chores = Checklist::new(&as_alice, "chores").await.unwrap();
+    let dishes = Item::new(&as_alice, chores.id.clone(), "dishes")
+        .await
+        .unwrap();
+    Checklist::share(&as_alice, chores.id.clone(), bob.id.clone(), Access::Check)
+        .await
+        .unwrap();
+
+    let theirs = Attachment::add(&as_alice, dishes.id.clone(), "before.jpg", b"before")
+        .await
+        .unwrap();
+    let own = Attachment::add(&as_bob, dishes.id.clone(), "after.jpg", b"after")
+        .await
+        .unwrap();
+    assert_eq!(own.uploader, Some(bob.id));
+
+    assert!(matches!(
+        Attachment::remove(&as_bob, theirs.id.clone()).await,
+        Err(Error::Forbidden)
+    ));
+    Attachment::remove(&as_bob, own.id).await.unwrap();
+    let left = Attachment::of(&as_alice, dishes.id).await.unwrap();
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].id, theirs.id);
+}
+
+#[tokio::test]
+async fn only_the_owner_may_hand_out_a_checklists_key() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let alice = User::new(&db, "alice").await.unwrap();
+    let bob = User::new(&db, "bob").await.unwrap();
+    let (as_alice, as_bob) = (db.as_user(alice.id), db.as_user(bob.id.clone()));
+    let chores = Checklist::new(&as_alice, "chores").await.unwrap();
+    Checklist::share(&as_alice, chores.id.clone(), bob.id, Access::Read)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        Checklist::export(&as_bob, chores.id.clone(), b"bob's key").await,
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        Checklist::export_key(&as_bob, chores.id.clone(), b"bob's key").await,
+        Err(Error::Forbidden)
+    ));
+    Checklist::export(&as_alice, chores.id, b"bob's key")
+        .await
+        .unwrap();
+}
@@ -0,0 +1,9 @@
+//! Setup shared by the integration tests.
+
+use checklist::Db;
+use tempfile::TempDir;
+
+/// A database in `dir`, acting as nobody in particular.
+pub async fn open(dir: &TempDir) -> Db {
+    Db::new(dir.path(), b"test key").await.unwrap()
+}
@@ -0,0 +1,76 @@
+//! Progress and trends agree with what was done to the items.
+
+mod common;
+
+use checklist::{Checklist, Db, Item, Summary, checks_per_period};
+use chrono::{Duration, Utc};
+use tempfile::TempDir;
+
+use common::open;
+
+/// Checks over the last hour.
+async fn checks(db: &Db) -> usize {
+    let since = Utc::now() - Duration::hours(1);
+    checks_per_period(db, since, Duration::hours(2), 1)
+        .await
+        .unwrap()[0]
+}
+
+#[tokio::test]
+async fn progress_counts_leaf_items_as_the_tree_does() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let trip = Checklist::new(&db, "trip").await.unwrap();
+    let pack = Item::new(&db, trip.id.clone(), "pack").await.unwrap();
+    let mut socks = Item::new_child(&db, pack.id.clone(), "socks")
+        .await
+        .unwrap();
+    Item::new_child(&db, pack.id, "shirts").await.unwrap();
+    Item::new(&db, trip.id.clone(), "book hotel").await.unwrap();
+    socks.set_checked(&db, true).await.unwrap();
+
+    let progress = trip.progress(&db).await.unwrap();
+    assert_eq!((progress.checked, progress.total), (1, 3));
+    let tree = trip
+        .tree(&db)
+        .await
+        .unwrap()
+        .iter()
+        .map(|node| node.progress())
+        .fold((0, 0), |(done, total), (d, t)| (done + d, total + t));
+    assert_eq!(tree, (progress.checked, progress.total));
+
+    let empty = Checklist::new(&db, "empty").await.unwrap();
+    let summary = Summary::gather(&db).await.unwrap();
+    let progress = summary
+        .checklists
+        .iter()
+        .map(|(checklist, progress)| (checklist.id.clone(), progress.checked, progress.total))
+        .collect::<Vec<_>>();
+    assert_eq!(progress, [(trip.id, 1, 3), (empty.id, 0, 0)]);
+}
+
+#[tokio::test]
+async fn only_checking_an_existing_item_counts_as_a_check() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let chores = Checklist::new(&db, "chores").await.unwrap();
+    let mut dishes = Item::new(&db, chores.id.clone(), "dishes").await.unwrap();
+    assert_eq!(checks(&db).await, 0);
+
+    dishes.set_checked(&db, true).await.unwrap();
+    assert_eq!(checks(&db).await, 1);
+
+    // the copy's item is created checked, and its text changing is no check either
+    let copy = Checklist::duplicate(&db, chores.id.clone(), false)
+        .await
+        .unwrap();
+    let mut copied = copy.items(&db).await.unwrap().remove(0);
+    assert!(copied.checked_at.is_some());
+    copied.set_text(&db, "dry dishes").await.unwrap();
+    assert_eq!(checks(&db).await, 1);
+
+    dishes.set_checked(&db, false).await.unwrap();
+    dishes.set_checked(&db, true).await.unwrap();
+    assert_eq!(checks(&db).await, 2);
+}
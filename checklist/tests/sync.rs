@@ -0,0 +1,197 @@
+//! Two databases which exchange ops end up in the same state.
+
+mod common;
+
+use std::time::Duration;
+
+use checklist::{Checklist, ChecklistId, Db, Error, Hlc, Item, ItemId, OpCursor, User};
+use tempfile::TempDir;
+
+/// Syncing needs full access.
+async fn open(dir: &TempDir) -> Db {
+    common::open(dir).await.with_full_access()
+}
+
+/// Send every op `from` has to `to`, returning how many were new there.
+async fn send(from: &Db, to: &Db) -> usize {
+    let (ops, _) = from
+        .export_ops_since(OpCursor::default(), u32::MAX)
+        .await
+        .unwrap();
+    to.apply_ops(ops).await.unwrap()
+}
+
+async fn exchange(a: &Db, b: &Db) {
+    send(a, b).await;
+    send(b, a).await;
+}
+
+async fn hlcs(db: &Db) -> Vec<Hlc> {
+    let (ops, _) = db
+        .export_ops_since(OpCursor::default(), u32::MAX)
+        .await
+        .unwrap();
+    let mut hlcs = ops.iter().map(|op| op.hlc().clone()).collect::<Vec<_>>();
+    hlcs.sort();
+    hlcs
+}
+
+/// The text, checked state and position of each item of `checklist`, in order.
+async fn contents(db: &Db, checklist: &ChecklistId) -> Vec<(String, bool, i64)> {
+    Checklist::load(db, checklist.clone())
+        .await
+        .unwrap()
+        .expect("checklist exists")
+        .items(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|item| {
+            (
+                item.item.into_owned(),
+                item.checked_at.is_some(),
+                item.position,
+            )
+        })
+        .collect()
+}
+
+async fn text(db: &Db, item: &ItemId) -> Option<String> {
+    Item::load(db, item.clone())
+        .await
+        .unwrap()
+        .map(|item| item.item.into_owned())
+}
+
+/// Let the wall clock move on, so that later ops are unambiguously later.
+async fn tick() {
+    tokio::time::sleep(Duration::from_millis(5)).await;
+}
+
+#[tokio::test]
+async fn databases_converge_after_exchanging_ops() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+
+    let checklist = Checklist::new(&a, "groceries").await.unwrap();
+    let mut milk = Item::new(&a, checklist.id.clone(), "milk").await.unwrap();
+    Item::new(&a, checklist.id.clone(), "eggs").await.unwrap();
+    milk.set_checked(&a, true).await.unwrap();
+    exchange(&a, &b).await;
+    Item::new(&b, checklist.id.clone(), "bread").await.unwrap();
+    exchange(&a, &b).await;
+
+    let expected = vec![
+        ("milk".to_owned(), true, 0),
+        ("eggs".to_owned(), false, 1),
+        ("bread".to_owned(), false, 2),
+    ];
+    assert_eq!(contents(&a, &checklist.id).await, expected);
+    assert_eq!(contents(&b, &checklist.id).await, expected);
+}
+
+#[tokio::test]
+async fn concurrent_edits_to_a_field_keep_the_last() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let checklist = Checklist::new(&a, "chores").await.unwrap();
+    let item = Item::new(&a, checklist.id.clone(), "dishes").await.unwrap();
+    exchange(&a, &b).await;
+
+    let mut on_a = Item::load(&a, item.id.clone()).await.unwrap().unwrap();
+    let mut on_b = Item::load(&b, item.id.clone()).await.unwrap().unwrap();
+    on_a.set_text(&a, "wash dishes").await.unwrap();
+    tick().await;
+    on_b.set_text(&b, "dry dishes").await.unwrap();
+    exchange(&a, &b).await;
+
+    assert_eq!(text(&a, &item.id).await.as_deref(), Some("dry dishes"));
+    assert_eq!(text(&b, &item.id).await.as_deref(), Some("dry dishes"));
+}
+
+#[tokio::test]
+async fn an_edit_the_deleter_had_not_seen_restores_the_record() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let checklist = Checklist::new(&a, "chores").await.unwrap();
+    let item = Item::new(&a, checklist.id.clone(), "laundry")
+        .await
+        .unwrap();
+    exchange(&a, &b).await;
+
+    Item::delete(&a, item.id.clone()).await.unwrap();
+    tick().await;
+    let mut on_b = Item::load(&b, item.id.clone()).await.unwrap().unwrap();
+    on_b.set_text(&b, "fold laundry").await.unwrap();
+    exchange(&a, &b).await;
+
+    assert_eq!(text(&a, &item.id).await.as_deref(), Some("fold laundry"));
+    assert_eq!(text(&b, &item.id).await.as_deref(), Some("fold laundry"));
+}
+
+#[tokio::test]
+async fn a_delete_after_seeing_every_edit_wins() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let checklist = Checklist::new(&a, "chores").await.unwrap();
+    let item = Item::new(&a, checklist.id.clone(), "laundry")
+        .await
+        .unwrap();
+    exchange(&a, &b).await;
+
+    Item::delete(&b, item.id.clone()).await.unwrap();
+    exchange(&a, &b).await;
+
+    assert_eq!(text(&a, &item.id).await, None);
+    assert_eq!(text(&b, &item.id).await, None);
+}
+
+#[tokio::test]
+async fn applying_ops_again_changes_nothing() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let checklist = Checklist::new(&a, "packing").await.unwrap();
+    Item::new(&a, checklist.id.clone(), "passport")
+        .await
+        .unwrap();
+
+    assert!(send(&a, &b).await > 0);
+    let before = contents(&b, &checklist.id).await;
+    assert_eq!(send(&a, &b).await, 0);
+    assert_eq!(contents(&b, &checklist.id).await, before);
+}
+
+#[tokio::test]
+async fn applied_ops_are_not_logged_again() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let checklist = Checklist::new(&a, "packing").await.unwrap();
+    let mut passport = Item::new(&a, checklist.id.clone(), "passport")
+        .await
+        .unwrap();
+    passport.set_checked(&a, true).await.unwrap();
+
+    send(&a, &b).await;
+
+    // had the log events fired while the ops were applied, `b` would have ops of its own
+    assert_eq!(hlcs(&b).await, hlcs(&a).await);
+}
+
+#[tokio::test]
+async fn a_private_checklist_stays_private_on_other_databases() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let (a, b) = (open(&dir_a).await, open(&dir_b).await);
+    let alice = User::new(&a, "alice").await.unwrap();
+    let diary = Checklist::new(&a.as_user(alice.id.clone()), "diary")
+        .await
+        .unwrap();
+    send(&a, &b).await;
+
+    let bob = User::new(&b, "bob").await.unwrap();
+    assert!(matches!(
+        Checklist::load(&b.as_user(bob.id), diary.id.clone()).await,
+        Err(Error::Forbidden)
+    ));
+    let synced = Checklist::load(&b, diary.id).await.unwrap().unwrap();
+    assert_eq!(synced.owner, Some(alice.id));
+}
@@ -0,0 +1,107 @@
+//! Following changes as they happen.
+
+mod common;
+
+use std::{pin::pin, time::Duration};
+
+use checklist::{Change, Changed, Checklist, Item, OpCursor, User};
+use futures::{Stream, StreamExt as _};
+use tempfile::TempDir;
+
+use common::open;
+
+/// The next change, failing if none comes soon.
+async fn next(changes: &mut (impl Stream<Item = checklist::Result<Change>> + Unpin)) -> Change {
+    tokio::time::timeout(Duration::from_secs(5), changes.next())
+        .await
+        .expect("a change is reported")
+        .expect("the stream goes on")
+        .unwrap()
+}
+
+/// The text and checked state of the item reported by `change`.
+fn item(change: &Change) -> (String, bool) {
+    match &change.record {
+        Changed::Item(_, Some(item)) => (item.item.to_string(), item.checked_at.is_some()),
+        record => panic!("expected an item, got {record:?}"),
+    }
+}
+
+#[tokio::test]
+async fn changes_are_reported_as_they_happen_and_again_after_a_cursor() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let checklist = Checklist::new(&db, "groceries").await.unwrap();
+    let mut milk = Item::new(&db, checklist.id.clone(), "milk").await.unwrap();
+
+    let first = {
+        let mut changes = pin!(db.changes(None, None).await.unwrap());
+        milk.set_checked(&db, true).await.unwrap();
+        let first = next(&mut changes).await;
+        assert_eq!(item(&first), ("milk".to_owned(), true));
+        assert_eq!(first.checklists, vec![checklist.id.clone()]);
+        first.cursor
+    };
+
+    milk.set_text(&db, "oat milk").await.unwrap();
+    let mut changes = pin!(db.changes(None, Some(first)).await.unwrap());
+    assert_eq!(
+        item(&next(&mut changes).await),
+        ("oat milk".to_owned(), true)
+    );
+}
+
+#[tokio::test]
+async fn changes_to_other_checklists_are_left_out() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let groceries = Checklist::new(&db, "groceries").await.unwrap();
+    let chores = Checklist::new(&db, "chores").await.unwrap();
+
+    let mut changes = pin!(
+        db.changes(Some(groceries.id.clone()), Some(OpCursor::default()))
+            .await
+            .unwrap()
+    );
+    assert!(matches!(
+        next(&mut changes).await.record,
+        Changed::Checklist(id, Some(_)) if id == groceries.id
+    ));
+    Item::new(&db, chores.id.clone(), "dishes").await.unwrap();
+    Item::new(&db, groceries.id.clone(), "milk").await.unwrap();
+    assert_eq!(item(&next(&mut changes).await), ("milk".to_owned(), false));
+}
+
+#[tokio::test]
+async fn changes_which_are_not_synced_are_reported_once() {
+    let dir = TempDir::new().unwrap();
+    let db = open(&dir).await;
+    let alice = User::new(&db, "alice").await.unwrap();
+    let checklist = Checklist::new(&db, "chores").await.unwrap();
+    let mut dishes = Item::new(&db, checklist.id.clone(), "dishes")
+        .await
+        .unwrap();
+
+    let mut changes = pin!(db.changes(None, None).await.unwrap());
+    dishes.set_text(&db, "wash dishes").await.unwrap();
+    let text = next(&mut changes).await;
+    dishes.assign(&db, Some(alice.id.clone())).await.unwrap();
+    let assigned = next(&mut changes).await;
+    assert!(matches!(
+        &assigned.record,
+        Changed::Item(_, Some(item)) if item.assignee == Some(alice.id.clone())
+    ));
+    assert_eq!(assigned.cursor, text.cursor);
+
+    // only the synced change is in the log
+    dishes.set_checked(&db, true).await.unwrap();
+    assert_eq!(
+        item(&next(&mut changes).await),
+        ("wash dishes".to_owned(), true)
+    );
+    let mut resumed = pin!(db.changes(None, Some(text.cursor)).await.unwrap());
+    assert_eq!(
+        item(&next(&mut resumed).await),
+        ("wash dishes".to_owned(), true)
+    );
+}
@@ -1,10 +1,19 @@
-use std::{os::unix::ffi::OsStrExt, path::PathBuf};
+use std::{net::SocketAddr, path::PathBuf};
 
 use anyhow::{Context, Result};
-use checklist::{ChecklistId, ItemId};
-use clap::{Args, Parser, Subcommand};
+use checklist::{
+    Access, AttachmentId, ChecklistId, Cursor, ItemId, ItemOrder, NoteId, Priority, Taggable,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    exit,
+    key::{self, Key, Keyring, KeyringKind},
+    when::When,
+};
 
 #[derive(Debug, Parser)]
+#[command(after_long_help = exit::STATUSES)]
 pub struct Cli {
     #[command(subcommand)]
     pub noun: Noun,
@@ -17,15 +26,64 @@ pub struct Cli {
 
     /// Path to file containing encryption key for data at rest
     ///
-    /// This file can contain arbitrary bytes which comprise the key for the database
+    /// This file can contain arbitrary bytes which comprise the key for the database.
+    /// The key is used as-is: it is neither derived nor cached.
     #[arg(short = 'E', long)]
     encryption_key_file: Option<PathBuf>,
 
-    /// Encryption key for data at rest
+    /// Passphrase from which the encryption key for data at rest is derived
+    ///
+    /// When neither this nor a key file is set, the key cached in the keyring is used;
+    /// if there is none, the passphrase is prompted for interactively.
+    #[arg(
+        short = 'e',
+        long,
+        alias = "encryption-key",
+        env = "CHECKLIST_PASSPHRASE",
+        conflicts_with = "encryption_key_file"
+    )]
+    passphrase: Option<String>,
+
+    /// Where to cache the derived encryption key between invocations
+    #[arg(short, long, value_enum, default_value_t)]
+    keyring: KeyringKind,
+
+    /// Name of the user to act as
+    ///
+    /// Lists created while acting as a user belong to them, and only lists they own or which were
+    /// shared with them can be seen or changed. Without a user, only lists which belong to nobody
+    /// can, unless `--full-access` is given.
+    #[arg(short, long, env = "CHECKLIST_USER")]
+    pub user: Option<String>,
+
+    /// Act with access to every list, whoever it belongs to
     ///
-    /// Default: "$USER@$NAME"
-    #[arg(short, long, conflicts_with = "encryption_key_file")]
-    encryption_key: Option<String>,
+    /// Syncing needs this, since it carries every change.
+    #[arg(long, env = "CHECKLIST_FULL_ACCESS", conflicts_with = "user")]
+    pub full_access: bool,
+
+    /// How to print results
+    ///
+    /// The machine-readable formats print every checklist, item, or other result as a record,
+    /// with ids and times in RFC 3339. `remind` without `--once` never finishes, so it prints
+    /// NDJSON instead of JSON.
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub format: Format,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Colored text
+    #[default]
+    Text,
+    /// A JSON array of records, each with a "kind", printed when the command finishes
+    Json,
+    /// One JSON record per line, each with a "kind", printed as they come
+    Ndjson,
+    /// Comma-separated values, with a header row whenever the kind of record changes
+    Csv,
+    /// Tab-separated values, with a header row whenever the kind of record changes
+    Tsv,
 }
 
 impl Cli {
@@ -39,21 +97,31 @@ impl Cli {
             .join("checklist/surrealdb"))
     }
 
-    pub(crate) fn encryption_key(&self) -> Result<Vec<u8>> {
+    pub(crate) fn keyring(&self) -> Result<Option<Box<dyn Keyring>>> {
+        self.keyring.open(&self.path()?)
+    }
+
+    pub(crate) fn encryption_key(&self) -> Result<Key> {
         if let Some(path) = &self.encryption_key_file {
-            return std::fs::read(path).context("reading encryption key from file");
+            let key = std::fs::read(path).context("reading encryption key from file")?;
+            return Ok(Key::new(key, None));
         }
 
-        if let Some(key) = &self.encryption_key {
-            return Ok(key.as_bytes().to_owned());
+        let path = self.path()?;
+        let keyring = self.keyring()?;
+
+        if let Some(passphrase) = &self.passphrase {
+            let key = key::derive(&path, passphrase.as_bytes())?;
+            return Ok(Key::new(key, keyring));
         }
 
-        let mut out = Vec::new();
-        out.extend_from_slice(std::env::var_os("USER").unwrap_or_default().as_bytes());
-        out.push(b'@');
-        out.extend_from_slice(std::env::var_os("NAME").unwrap_or_default().as_bytes());
+        if let Some(key) = key::cached(keyring.as_deref()) {
+            return Ok(Key::new(key, None));
+        }
 
-        Ok(out)
+        let passphrase = key::prompt(&path)?;
+        let key = key::derive(&path, passphrase.as_bytes())?;
+        Ok(Key::new(key, keyring))
     }
 }
 
@@ -64,6 +132,89 @@ pub enum Noun {
 
     /// Manage items
     Item(ItemVerbAction),
+
+    /// Manage tags on lists and items
+    Tag(TagVerbAction),
+
+    /// Manage the users who can own and share lists
+    User(UserVerbAction),
+
+    /// Manage the cached encryption key
+    Key(KeyVerbAction),
+
+    /// Search checklist names and item text
+    Search(Search),
+
+    /// Show overdue and upcoming items across all lists
+    Due(ShowDue),
+
+    /// Show unchecked items across all lists which are assigned to the acting user
+    Mine,
+
+    /// Show how far along lists are, and how many items were checked each day
+    Stats(ShowStats),
+
+    /// Keep running, announcing reminders as they fall due
+    Remind(Remind),
+
+    /// Push and pull changes with a sync server
+    Sync(SyncRemote),
+
+    /// Keep running, serving sync requests from other devices
+    ServeSync(ServeSync),
+}
+
+#[derive(Debug, Args)]
+pub struct SyncRemote {
+    /// Url of the sync server, for example "http://127.0.0.1:7878"
+    #[arg(short, long)]
+    pub remote: reqwest::Url,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeSync {
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:7878")]
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowDue {
+    /// How far ahead to look for upcoming items
+    #[arg(short, long, default_value = "7d")]
+    pub within: humantime::Duration,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowStats {
+    /// Only show the progress of this checklist
+    pub id: Option<ChecklistId>,
+
+    /// How many days of checks to show, ending today
+    #[arg(short, long, default_value_t = 7)]
+    pub days: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct Remind {
+    /// How often to check for reminders
+    #[arg(short, long, default_value = "1m")]
+    pub interval: humantime::Duration,
+
+    /// Show desktop notifications as well as printing reminders
+    #[arg(short, long)]
+    pub notify: bool,
+
+    /// Announce pending reminders once, then exit
+    #[arg(long)]
+    pub once: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct Search {
+    /// Terms to search for; all must match
+    #[arg(required = true)]
+    pub terms: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -82,10 +233,70 @@ pub enum ListVerb {
 
     /// Delete a checklist
     Remove(RemoveChecklist),
+
+    /// Set or clear how often a checklist resets
+    Recur(SetRecurrence),
+
+    /// Show past runs of a recurring checklist
+    History(ShowHistory),
+
+    /// Mark a checklist as a template, or unmark it
+    Template(MarkTemplate),
+
+    /// Hide a checklist from listings, or bring it back
+    Archive(ArchiveChecklist),
+
+    /// Make a checklist you own read-only, or writable again
+    Lock(LockChecklist),
+
+    /// Archive a checklist once all its items are checked, or stop doing so
+    AutoArchive(SetAutoArchive),
+
+    /// Create a new checklist from a template
+    FromTemplate(FromTemplate),
+
+    /// Copy a checklist, with all its items
+    Copy(CopyChecklist),
+
+    /// Move every item of one checklist into another, then delete the first
+    Merge(MergeChecklists),
+
+    /// Give another user access to a checklist you own
+    Share(ShareChecklist),
+
+    /// Take away another user's access to a checklist you own
+    Unshare(UnshareChecklist),
+
+    /// Show who a checklist is shared with
+    Shares(ShowShares),
 }
 
 #[derive(Debug, Args)]
-pub struct ShowAllChecklists {}
+pub struct ShowAllChecklists {
+    /// Only show templates
+    #[arg(long)]
+    pub templates: bool,
+
+    /// Only show archived checklists
+    #[arg(long)]
+    pub archived: bool,
+
+    /// Only show checklists whose name contains this text
+    #[arg(short, long)]
+    pub contains: Option<String>,
+
+    /// Only show checklists with this tag; may be repeated
+    #[arg(short, long = "tag")]
+    pub tags: Vec<String>,
+
+    /// Show at most this many checklists
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Continue from the cursor printed after the previous page
+    #[arg(short, long)]
+    pub after: Option<Cursor>,
+}
 
 #[derive(Debug, Args)]
 pub struct NewChecklist {
@@ -99,6 +310,163 @@ pub struct RemoveChecklist {
     pub id: ChecklistId,
 }
 
+#[derive(Debug, Args)]
+pub struct SetRecurrence {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// "daily", "weekly", "every <n><m|h|d|w>", or "cron <sec> <min> <hour> <day> <month> <weekday>"
+    ///
+    /// Each reset archives the checklist's state and unchecks all of its items.
+    #[arg(required_unless_present = "clear")]
+    pub rule: Vec<String>,
+
+    /// When the first period starts; later periods follow on from it. Default: now
+    #[arg(short, long)]
+    pub starting: Option<When>,
+
+    /// Stop the checklist recurring
+    #[arg(short, long, conflicts_with_all = ["rule", "starting"])]
+    pub clear: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowHistory {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Show the state of each item in each run
+    #[arg(short, long)]
+    pub items: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MarkTemplate {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Make the checklist an ordinary checklist again
+    #[arg(short, long)]
+    pub unset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArchiveChecklist {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Unarchive the checklist
+    #[arg(short, long)]
+    pub unset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct LockChecklist {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Unlock the checklist
+    #[arg(short, long)]
+    pub unset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SetAutoArchive {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Stop archiving the checklist automatically
+    #[arg(short, long)]
+    pub unset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct FromTemplate {
+    /// Id of the template
+    pub template_id: ChecklistId,
+
+    /// Value for a placeholder, as NAME=VALUE; may be repeated
+    ///
+    /// Every `{{NAME}}` placeholder in the template must be given a value.
+    #[arg(short = 's', long = "set", value_name = "NAME=VALUE", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+}
+
+#[derive(Debug, Args)]
+pub struct CopyChecklist {
+    /// Id of the checklist to copy
+    pub id: ChecklistId,
+
+    /// Uncheck every item in the copy
+    #[arg(short, long)]
+    pub reset: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct MergeChecklists {
+    /// Id of the checklist to merge; it is deleted afterwards
+    pub source: ChecklistId,
+
+    /// Id of the checklist to merge into
+    ///
+    /// Items whose text matches an item already at the same place in this checklist are dropped.
+    pub target: ChecklistId,
+}
+
+#[derive(Debug, Args)]
+pub struct ShareChecklist {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Name of the user to share with
+    pub user: String,
+
+    /// What the user may do; sharing again replaces their previous access
+    #[arg(short, long, value_enum, default_value_t)]
+    pub access: AccessLevel,
+}
+
+#[derive(Debug, Args)]
+pub struct UnshareChecklist {
+    /// Id of the checklist
+    pub id: ChecklistId,
+
+    /// Name of the user to stop sharing with
+    pub user: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowShares {
+    /// Id of the checklist
+    pub id: ChecklistId,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AccessLevel {
+    /// See the checklist and its items
+    #[default]
+    Read,
+    /// Also check and uncheck items
+    Check,
+    /// Also change the checklist and its items
+    Edit,
+}
+
+impl From<AccessLevel> for Access {
+    fn from(level: AccessLevel) -> Self {
+        match level {
+            AccessLevel::Read => Access::Read,
+            AccessLevel::Check => Access::Check,
+            AccessLevel::Edit => Access::Edit,
+        }
+    }
+}
+
+fn parse_var(s: &str) -> Result<(String, String)> {
+    let (name, value) = s.split_once('=').context("expected NAME=VALUE")?;
+    Ok((name.trim().to_owned(), value.to_owned()))
+}
+
 #[derive(Debug, Args)]
 pub struct ItemVerbAction {
     #[command(subcommand)]
@@ -113,13 +481,44 @@ pub enum ItemVerb {
     /// Create a new item in a checklist
     New(NewItem),
 
+    /// Create a new item nested under another item
+    NewChild(NewChildItem),
+
+    /// Create many items at once, one per line of standard input
+    AddMany(AddManyItems),
+
+    /// Move an item, along with everything nested under it
+    Move(MoveItem),
+
     /// Delete an item in a checklist
     Remove(RemoveItem),
 
     /// Toggle completion status of an item in a checklist
     Toggle(ToggleItem),
+
+    /// Set or clear when an item is due
+    Due(SetDue),
+
+    /// Set or clear when to be reminded of an item
+    Remind(SetReminder),
+
+    /// Set how urgent an item is
+    Priority(SetPriority),
+
+    /// Assign an item to a user, or unassign it
+    Assign(AssignItem),
+
+    /// Manage notes on an item
+    Note(NoteVerbAction),
+
+    /// Attach a small file to an item
+    Attach(AttachFile),
+
+    /// Manage files attached to items
+    Attachment(AttachmentVerbAction),
 }
 
+/// Items are shown as an indented tree unless filtered or paginated.
 #[derive(Debug, Args)]
 pub struct ShowAllItems {
     /// Checklist Id for items to show
@@ -128,6 +527,58 @@ pub struct ShowAllItems {
     /// When set, omit the item header
     #[arg(short, long)]
     pub omit_header: bool,
+
+    /// Only show items which are not yet checked
+    #[arg(short, long)]
+    pub unchecked: bool,
+
+    /// Only show items whose text contains this text
+    #[arg(short, long)]
+    pub contains: Option<String>,
+
+    /// Only show items with this tag; may be repeated
+    #[arg(short, long = "tag")]
+    pub tags: Vec<String>,
+
+    /// Show at most this many items
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Continue from the cursor printed after the previous page
+    #[arg(short, long)]
+    pub after: Option<Cursor>,
+
+    /// Show items as a flat list in this order, instead of as a tree
+    #[arg(
+        short,
+        long,
+        value_enum,
+        conflicts_with_all = ["unchecked", "contains", "tags", "limit", "after"],
+    )]
+    pub sort: Option<SortItems>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortItems {
+    /// The order in which items were arranged
+    Manual,
+    /// Most urgent first
+    Priority,
+    /// Soonest due first
+    Due,
+    /// Unchecked items first
+    Unchecked,
+}
+
+impl From<SortItems> for ItemOrder {
+    fn from(sort: SortItems) -> Self {
+        match sort {
+            SortItems::Manual => ItemOrder::Manual,
+            SortItems::Priority => ItemOrder::Priority,
+            SortItems::Due => ItemOrder::Due,
+            SortItems::Unchecked => ItemOrder::UncheckedFirst,
+        }
+    }
 }
 
 #[derive(Debug, Args)]
@@ -139,6 +590,51 @@ pub struct NewItem {
     pub name: String,
 }
 
+/// Blank lines are skipped. A line indented further than the line before it is nested under that
+/// line. Either every item is created, or none is.
+#[derive(Debug, Args)]
+pub struct AddManyItems {
+    /// Checklist Id in which to put these items
+    pub checklist_id: ChecklistId,
+}
+
+#[derive(Debug, Args)]
+pub struct NewChildItem {
+    /// Id of the item under which to nest this item
+    pub parent_id: ItemId,
+
+    /// Name of this item
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct MoveItem {
+    /// Ids of the items to move
+    ///
+    /// Items nested under them move too.
+    #[arg(required = true)]
+    pub ids: Vec<ItemId>,
+
+    #[command(flatten)]
+    pub destination: MoveDestination,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct MoveDestination {
+    /// Nest the items under this item
+    #[arg(short, long)]
+    pub under: Option<ItemId>,
+
+    /// Move the items to the top level of their checklist
+    #[arg(short, long)]
+    pub top: bool,
+
+    /// Move the items to the end of this checklist
+    #[arg(long)]
+    pub to: Option<ChecklistId>,
+}
+
 #[derive(Debug, Args)]
 pub struct RemoveItem {
     /// Id of the item to remove
@@ -150,3 +646,287 @@ pub struct ToggleItem {
     /// Id of the item to toggle
     pub id: ItemId,
 }
+
+#[derive(Debug, Args)]
+pub struct SetDue {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// When the item is due: a duration from now like "2h", a date, or a date and time
+    #[arg(required_unless_present = "clear")]
+    pub when: Option<When>,
+
+    /// Remove the due date
+    #[arg(short, long, conflicts_with = "when")]
+    pub clear: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SetPriority {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// How urgent the item is
+    #[arg(value_enum)]
+    pub priority: PriorityLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PriorityLevel {
+    Low,
+    Normal,
+    High,
+    Urgent,
+}
+
+impl From<PriorityLevel> for Priority {
+    fn from(level: PriorityLevel) -> Self {
+        match level {
+            PriorityLevel::Low => Priority::Low,
+            PriorityLevel::Normal => Priority::Normal,
+            PriorityLevel::High => Priority::High,
+            PriorityLevel::Urgent => Priority::Urgent,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct AssignItem {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// Name of the user who should do the item
+    #[arg(required_unless_present = "clear")]
+    pub user: Option<String>,
+
+    /// Unassign the item
+    #[arg(short, long, conflicts_with = "user")]
+    pub clear: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NoteVerbAction {
+    #[command(subcommand)]
+    pub verb: NoteVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NoteVerb {
+    /// Add a note to an item
+    Add(AddNote),
+
+    /// Show the notes on an item
+    Ls(ShowNotes),
+
+    /// Delete a note
+    Rm(RemoveNote),
+}
+
+#[derive(Debug, Args)]
+pub struct AddNote {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// Text of the note; when omitted, the note is read from standard input
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowNotes {
+    /// Id of the item
+    pub id: ItemId,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveNote {
+    /// Id of the note
+    pub id: NoteId,
+}
+
+#[derive(Debug, Args)]
+pub struct AttachFile {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// Path of the file to attach
+    pub file: PathBuf,
+
+    /// Name to attach the file under, instead of its file name
+    #[arg(short, long)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AttachmentVerbAction {
+    #[command(subcommand)]
+    pub verb: AttachmentVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AttachmentVerb {
+    /// Write the contents of an attachment to standard output or a file
+    Get(GetAttachment),
+
+    /// Show the files attached to an item
+    Ls(ShowAttachments),
+
+    /// Remove an attachment from its item
+    Rm(RemoveAttachment),
+}
+
+#[derive(Debug, Args)]
+pub struct GetAttachment {
+    /// Id of the attachment
+    pub id: AttachmentId,
+
+    /// Write the contents to this file instead of standard output
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowAttachments {
+    /// Id of the item
+    pub id: ItemId,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveAttachment {
+    /// Id of the attachment
+    pub id: AttachmentId,
+}
+
+#[derive(Debug, Args)]
+pub struct SetReminder {
+    /// Id of the item
+    pub id: ItemId,
+
+    /// When to be reminded: a duration from now like "2h", a date, or a date and time
+    #[arg(required_unless_present = "clear")]
+    pub when: Option<When>,
+
+    /// Remove the reminder
+    #[arg(short, long, conflicts_with = "when")]
+    pub clear: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct TagVerbAction {
+    #[command(subcommand)]
+    pub verb: TagVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TagVerb {
+    /// Tag a list or item
+    Add(AddTags),
+
+    /// Remove tags from a list or item
+    Rm(RemoveTags),
+
+    /// Show the tags on a list or item, or all tags
+    Ls(ShowTags),
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct TagTarget {
+    /// Id of the list
+    #[arg(short, long)]
+    pub list: Option<ChecklistId>,
+
+    /// Id of the item
+    #[arg(short, long)]
+    pub item: Option<ItemId>,
+}
+
+#[derive(Debug, Args)]
+#[group(required = false, multiple = false)]
+pub struct OptionalTagTarget {
+    /// Id of the list
+    #[arg(short, long)]
+    pub list: Option<ChecklistId>,
+
+    /// Id of the item
+    #[arg(short, long)]
+    pub item: Option<ItemId>,
+}
+
+#[derive(Debug, Args)]
+pub struct AddTags {
+    #[command(flatten)]
+    pub target: TagTarget,
+
+    /// Tags to add
+    #[arg(required = true)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveTags {
+    #[command(flatten)]
+    pub target: TagTarget,
+
+    /// Tags to remove
+    #[arg(required = true)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowTags {
+    #[command(flatten)]
+    pub target: OptionalTagTarget,
+}
+
+impl TagTarget {
+    pub(crate) fn taggable(self) -> Taggable {
+        match (self.list, self.item) {
+            (Some(list), _) => list.into(),
+            (None, Some(item)) => item.into(),
+            (None, None) => unreachable!("clap requires exactly one target"),
+        }
+    }
+}
+
+impl OptionalTagTarget {
+    pub(crate) fn taggable(self) -> Option<Taggable> {
+        match (self.list, self.item) {
+            (Some(list), _) => Some(list.into()),
+            (None, Some(item)) => Some(item.into()),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct UserVerbAction {
+    #[command(subcommand)]
+    pub verb: UserVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserVerb {
+    /// Show all users
+    ShowAll,
+
+    /// Create a new user
+    New(NewUser),
+}
+
+#[derive(Debug, Args)]
+pub struct NewUser {
+    /// Name of the user; must be unique
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct KeyVerbAction {
+    #[command(subcommand)]
+    pub verb: KeyVerb,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeyVerb {
+    /// Remove the cached encryption key from the keyring
+    Forget,
+}
@@ -0,0 +1,58 @@
+//! Exit statuses, so that scripts can tell kinds of failure apart.
+
+use std::{fmt, process::ExitCode};
+
+/// Listed in `--help`.
+pub(crate) const STATUSES: &str = "\
+Exit status:
+  0  success
+  1  any other failure
+  2  invalid command line
+  3  no such checklist, item, or other record
+  4  the acting user may not do this
+  5  changed by someone else since it was loaded; reload and retry
+  6  the checklist is locked
+  7  invalid input, such as a bad cursor, tag, or recurrence rule
+  8  wrong encryption key, or corrupt data";
+
+/// A record which a command needs does not exist.
+#[derive(Debug)]
+pub(crate) struct NotFound(pub(crate) &'static str);
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} not found", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// The exit status for a command which failed with `err`.
+pub(crate) fn status(err: &anyhow::Error) -> ExitCode {
+    use checklist::Error as E;
+
+    if err.downcast_ref::<NotFound>().is_some() {
+        return ExitCode::from(3);
+    }
+    let code = match err.downcast_ref::<E>() {
+        Some(E::MissingItem) => 3,
+        Some(E::Forbidden) => 4,
+        Some(E::Conflict { .. }) => 5,
+        Some(E::Locked) => 6,
+        Some(
+            E::WrongRecordId { .. }
+            | E::InvalidCursor
+            | E::EmptyTag
+            | E::ParentCycle
+            | E::ParentInOtherChecklist
+            | E::NotATemplate
+            | E::MissingTemplateVariable { .. }
+            | E::InvalidRecurrence { .. }
+            | E::MergeIntoSelf
+            | E::AttachmentTooLarge { .. },
+        ) => 7,
+        Some(E::Decrypt { .. } | E::UnsupportedKeyVersion { .. } | E::MalformedKey) => 8,
+        Some(_) | None => 1,
+    };
+    ExitCode::from(code)
+}
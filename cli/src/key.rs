@@ -0,0 +1,243 @@
+use std::{
+    ffi::OsString,
+    fs::OpenOptions,
+    io::{ErrorKind, Write as _},
+    os::unix::{ffi::OsStrExt, fs::OpenOptionsExt as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use color_print::ceprintln;
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const KEYRING_SERVICE: &str = "checklist";
+
+/// Path of the salt file which lives alongside the database at `db_path`.
+fn salt_path(db_path: &Path) -> PathBuf {
+    let mut path = OsString::from(db_path.as_os_str());
+    path.push(".salt");
+    path.into()
+}
+
+/// Load the salt for the database at `db_path`, creating it if it does not yet exist.
+fn salt(db_path: &Path) -> Result<Vec<u8>> {
+    let path = salt_path(db_path);
+    match std::fs::read(&path) {
+        Ok(salt) => return Ok(salt),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err).context("reading key derivation salt"),
+    }
+
+    let mut salt = vec![0; SALT_LEN];
+    getrandom::getrandom(&mut salt).context("generating key derivation salt")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("creating key derivation salt directory")?;
+    }
+    std::fs::write(&path, &salt).context("writing key derivation salt")?;
+    Ok(salt)
+}
+
+/// Derive the database key from a passphrase with Argon2.
+pub(crate) fn derive(db_path: &Path, passphrase: &[u8]) -> Result<Vec<u8>> {
+    let salt = salt(db_path)?;
+    let mut key = vec![0; KEY_LEN];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase, &salt, &mut key)
+        .map_err(|err| anyhow::anyhow!("{err}"))
+        .context("deriving encryption key")?;
+    Ok(key)
+}
+
+/// Interactively ask the user for the passphrase of the database at `db_path`.
+///
+/// When the database has no salt yet, this is the first time a key is derived for it, so
+/// the passphrase must be entered twice.
+pub(crate) fn prompt(db_path: &Path) -> Result<String> {
+    let passphrase = rpassword::prompt_password(format!("passphrase for {}: ", db_path.display()))
+        .context("no passphrase configured and unable to prompt for one")?;
+
+    if !salt_path(db_path).exists() {
+        let confirmation =
+            rpassword::prompt_password("confirm passphrase: ").context("confirming passphrase")?;
+        if confirmation != passphrase {
+            bail!("passphrases do not match");
+        }
+    }
+
+    Ok(passphrase)
+}
+
+/// Get the key cached in the keyring, if any.
+///
+/// Keyring failures are not fatal: we can always fall back to deriving the key again.
+pub(crate) fn cached(keyring: Option<&dyn Keyring>) -> Option<Vec<u8>> {
+    match keyring?.load() {
+        Ok(key) => key,
+        Err(err) => {
+            ceprintln!("<yellow>warning:</yellow> {err:#}");
+            None
+        }
+    }
+}
+
+/// Store the key in the keyring, if any.
+///
+/// Keyring failures are not fatal: the key just won't be available next time.
+fn cache(keyring: Option<&dyn Keyring>, key: &[u8]) {
+    if let Some(Err(err)) = keyring.map(|keyring| keyring.store(key)) {
+        ceprintln!("<yellow>warning:</yellow> {err:#}");
+    }
+}
+
+/// A database key, as found or derived.
+pub(crate) struct Key {
+    pub(crate) bytes: Vec<u8>,
+    /// Where to cache a key which was just derived.
+    uncached: Option<Box<dyn Keyring>>,
+}
+
+impl Key {
+    pub(crate) fn new(bytes: Vec<u8>, uncached: Option<Box<dyn Keyring>>) -> Self {
+        Self { bytes, uncached }
+    }
+
+    /// Cache a newly derived key.
+    ///
+    /// Only call this once the database has accepted the key, so that a mistyped passphrase is not
+    /// remembered.
+    pub(crate) fn cache(&self) {
+        cache(self.uncached.as_deref(), &self.bytes);
+    }
+}
+
+/// Where a derived key is cached between invocations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyringKind {
+    /// The system secret service
+    #[default]
+    System,
+    /// A private file in the user's config directory, for headless machines
+    File,
+    /// Do not cache the key; derive it on every invocation
+    None,
+}
+
+impl KeyringKind {
+    pub(crate) fn open(self, db_path: &Path) -> Result<Option<Box<dyn Keyring>>> {
+        // the same database may be reached through several paths; the entry should not depend on which
+        let db_path = db_path
+            .canonicalize()
+            .unwrap_or_else(|_| db_path.to_owned());
+        Ok(match self {
+            KeyringKind::System => Some(Box::new(SystemKeyring::new(&db_path)?)),
+            KeyringKind::File => Some(Box::new(FileKeyring::new(&db_path)?)),
+            KeyringKind::None => None,
+        })
+    }
+}
+
+/// Storage for a cached database key.
+pub(crate) trait Keyring {
+    /// Load the cached key, if one is present.
+    fn load(&self) -> Result<Option<Vec<u8>>>;
+
+    /// Cache this key, replacing any existing key.
+    fn store(&self, key: &[u8]) -> Result<()>;
+
+    /// Remove the cached key, if one is present.
+    fn forget(&self) -> Result<()>;
+}
+
+/// A key cached in the system secret service.
+struct SystemKeyring {
+    entry: keyring::Entry,
+}
+
+impl SystemKeyring {
+    fn new(db_path: &Path) -> Result<Self> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, &db_path.to_string_lossy())
+            .context("opening system keyring entry")?;
+        Ok(Self { entry })
+    }
+}
+
+impl Keyring for SystemKeyring {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        match self.entry.get_secret() {
+            Ok(key) => Ok(Some(key)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err).context("loading key from system keyring (try `--keyring file`)"),
+        }
+    }
+
+    fn store(&self, key: &[u8]) -> Result<()> {
+        self.entry
+            .set_secret(key)
+            .context("storing key in system keyring (try `--keyring file`)")
+    }
+
+    fn forget(&self) -> Result<()> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(err).context("removing key from system keyring"),
+        }
+    }
+}
+
+/// A key cached in a file readable only by the current user.
+///
+/// This is a stand-in for machines without a secret service. It lives in the config directory
+/// rather than alongside the database so that copying the database does not also copy its key.
+struct FileKeyring {
+    path: PathBuf,
+}
+
+impl FileKeyring {
+    fn new(db_path: &Path) -> Result<Self> {
+        let name = db_path
+            .as_os_str()
+            .as_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let path = dirs::config_local_dir()
+            .context("config local dir must exist on this system")?
+            .join("checklist/keyring")
+            .join(name);
+        Ok(Self { path })
+    }
+}
+
+impl Keyring for FileKeyring {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(&self.path) {
+            Ok(key) => Ok(Some(key)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).context("loading key from keyring file"),
+        }
+    }
+
+    fn store(&self, key: &[u8]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).context("creating keyring directory")?;
+        }
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(key))
+            .context("storing key in keyring file")
+    }
+
+    fn forget(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).context("removing keyring file"),
+        }
+    }
+}
@@ -1,35 +1,126 @@
 mod cli;
+mod exit;
+mod key;
+mod output;
+mod sync;
+mod when;
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write as _,
+    process::ExitCode,
+};
+
 use anyhow::Context;
-use bytes::Bytes;
-use checklist::{Checklist, Db, Item};
+use checklist::{
+    Attachment, Checklist, ChecklistId, ChecklistQuery, Db, Item, ItemId, ItemQuery, Note,
+    Recurrence, Summary, Tag, User, UserId, checks_per_period,
+};
+use chrono::{Days, Local, NaiveTime, Utc};
 use clap::Parser as _;
 use cli::{
-    Cli, ItemVerb, ItemVerbAction, ListVerb, ListVerbAction, NewChecklist, NewItem,
-    RemoveChecklist, RemoveItem, ShowAllChecklists, ShowAllItems, ToggleItem,
+    AddManyItems, AddNote, AddTags, ArchiveChecklist, AssignItem, AttachFile, AttachmentVerb,
+    AttachmentVerbAction, Cli, CopyChecklist, FromTemplate, GetAttachment, ItemVerb,
+    ItemVerbAction, KeyVerb, KeyVerbAction, ListVerb, ListVerbAction, LockChecklist, MarkTemplate,
+    MergeChecklists, MoveDestination, MoveItem, NewChecklist, NewChildItem, NewItem, NewUser,
+    NoteVerb, NoteVerbAction, Remind, RemoveAttachment, RemoveChecklist, RemoveItem, RemoveNote,
+    RemoveTags, Search, ServeSync, SetAutoArchive, SetDue, SetPriority, SetRecurrence, SetReminder,
+    ShareChecklist, ShowAllChecklists, ShowAllItems, ShowAttachments, ShowDue, ShowHistory,
+    ShowNotes, ShowShares, ShowStats, ShowTags, SyncRemote, TagVerb, TagVerbAction, ToggleItem,
+    UnshareChecklist, UserVerb, UserVerbAction,
 };
-use color_print::cprintln;
+use color_print::{ceprintln, cprintln};
+use exit::NotFound;
+use output::{Output, local, show_duration};
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
+    let mut out = Output::new(cli.format);
+    match run(cli, &mut out).await {
+        Ok(()) => {
+            out.finish();
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            exit::status(&err)
+        }
+    }
+}
+
+async fn run(cli: Cli, out: &mut Output) -> anyhow::Result<()> {
+    // managing the key must not require the key
+    if let cli::Noun::Key(KeyVerbAction {
+        verb: KeyVerb::Forget,
+    }) = cli.noun
+    {
+        let Some(keyring) = cli.keyring()? else {
+            anyhow::bail!("`--keyring none` caches no key, so there is none to forget");
+        };
+        keyring.forget()?;
+        return Ok(());
+    }
 
     let path = cli.path()?;
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).context("creating checklist data directory")?;
     }
-    let encryption_key = Bytes::from(cli.encryption_key()?);
+    let encryption_key = cli.encryption_key()?;
 
-    let db = Db::new(path, &encryption_key)
+    let db = Db::new(path, &encryption_key.bytes)
         .await
         .context("connecting to database")?;
+    encryption_key.cache();
+    let db = match &cli.user {
+        Some(name) => db.as_user(find_user(&db, name).await?),
+        None if cli.full_access => db.with_full_access(),
+        None => db,
+    };
+    if matches!(cli.noun, cli::Noun::Sync(_) | cli::Noun::ServeSync(_)) {
+        anyhow::ensure!(
+            cli.full_access,
+            "syncing carries every change, so it needs --full-access"
+        );
+    }
+    // nothing is written unless a period has ended, in which case the reset is due anyway
+    Checklist::roll_over_due(&db, Utc::now())
+        .await
+        .context("resetting recurring checklists")?;
 
     match cli.noun {
         cli::Noun::List(ListVerbAction {
-            verb: ListVerb::ShowAll(ShowAllChecklists {}),
+            verb:
+                ListVerb::ShowAll(ShowAllChecklists {
+                    templates,
+                    archived,
+                    contains,
+                    tags,
+                    limit,
+                    after,
+                }),
         }) => {
-            for checklist in Checklist::all(&db).await.context("getting checklists")? {
-                show_checklist(&checklist);
+            let mut query = ChecklistQuery::new().archived(archived);
+            if templates {
+                query = query.template(true);
+            }
+            if let Some(contains) = contains {
+                query = query.containing(contains);
             }
+            for tag in tags {
+                query = query.tagged(Tag::new(&tag)?);
+            }
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+            if let Some(after) = after {
+                query = query.after(after);
+            }
+            let page = query.fetch(&db).await.context("getting checklists")?;
+            for checklist in &page.items {
+                out.checklist(checklist);
+            }
+            out.next(page.next.as_ref());
         }
         cli::Noun::List(ListVerbAction {
             verb: ListVerb::New(NewChecklist { name }),
@@ -37,7 +128,7 @@ async fn main() -> anyhow::Result<()> {
             let checklist = Checklist::new(&db, name)
                 .await
                 .context("creating checklist")?;
-            show_checklist(&checklist);
+            out.checklist(&checklist);
         }
         cli::Noun::List(ListVerbAction {
             verb: ListVerb::Remove(RemoveChecklist { id }),
@@ -46,27 +137,245 @@ async fn main() -> anyhow::Result<()> {
                 .await
                 .context("deleting checklist")?;
         }
+        cli::Noun::List(ListVerbAction {
+            verb:
+                ListVerb::Recur(SetRecurrence {
+                    id,
+                    rule,
+                    starting,
+                    clear: _,
+                }),
+        }) => {
+            let recurrence = if rule.is_empty() {
+                None
+            } else {
+                Some(rule.join(" ").parse::<Recurrence>()?)
+            };
+            let mut checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            let starting = starting.map_or_else(Utc::now, |when| when.0);
+            checklist
+                .set_recurrence(&db, recurrence, starting)
+                .await
+                .context("setting recurrence")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::History(ShowHistory { id, items }),
+        }) => {
+            let checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            out.checklist(&checklist);
+            for run in checklist.history(&db).await.context("getting history")? {
+                out.run(&run, items);
+            }
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Template(MarkTemplate { id, unset }),
+        }) => {
+            let mut checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            checklist
+                .set_template(&db, !unset)
+                .await
+                .context("marking template")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Archive(ArchiveChecklist { id, unset }),
+        }) => {
+            let mut checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            if unset {
+                checklist.unarchive(&db).await
+            } else {
+                checklist.archive(&db).await
+            }
+            .context("archiving checklist")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Lock(LockChecklist { id, unset }),
+        }) => {
+            let mut checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            if unset {
+                checklist.unlock(&db).await
+            } else {
+                checklist.lock(&db).await
+            }
+            .context("locking checklist")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::AutoArchive(SetAutoArchive { id, unset }),
+        }) => {
+            let mut checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            checklist
+                .set_auto_archive(&db, !unset)
+                .await
+                .context("setting auto-archive")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::FromTemplate(FromTemplate { template_id, vars }),
+        }) => {
+            let vars = vars.into_iter().collect::<HashMap<_, _>>();
+            let checklist = Checklist::instantiate(&db, template_id, &vars)
+                .await
+                .context("creating checklist from template")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Copy(CopyChecklist { id, reset }),
+        }) => {
+            let checklist = Checklist::duplicate(&db, id, reset)
+                .await
+                .context("copying checklist")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Merge(MergeChecklists { source, target }),
+        }) => {
+            let checklist = Checklist::merge_into(&db, source, target)
+                .await
+                .context("merging checklists")?;
+            out.checklist(&checklist);
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Share(ShareChecklist { id, user, access }),
+        }) => {
+            let user = find_user(&db, &user).await?;
+            Checklist::share(&db, id, user, access.into())
+                .await
+                .context("sharing checklist")?;
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Unshare(UnshareChecklist { id, user }),
+        }) => {
+            let user = find_user(&db, &user).await?;
+            Checklist::unshare(&db, id, user)
+                .await
+                .context("unsharing checklist")?;
+        }
+        cli::Noun::List(ListVerbAction {
+            verb: ListVerb::Shares(ShowShares { id }),
+        }) => {
+            let checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            if let Some(owner) = &checklist.owner {
+                let owner = User::load(&db, owner.clone())
+                    .await
+                    .context("getting owner")?;
+                if let Some(owner) = owner {
+                    out.share(&owner.name, "owner");
+                }
+            }
+            for grant in Checklist::grants(&db, checklist.id)
+                .await
+                .context("getting shares")?
+            {
+                let user = User::load(&db, grant.user.clone())
+                    .await
+                    .context("getting user")?;
+                let name = user.map_or_else(|| grant.user.to_string(), |user| user.name);
+                let access = match grant.access {
+                    checklist::Access::Read => "read",
+                    checklist::Access::Check => "check",
+                    checklist::Access::Edit => "edit",
+                };
+                out.share(&name, access);
+            }
+        }
         cli::Noun::Item(ItemVerbAction {
             verb:
                 ItemVerb::ShowAll(ShowAllItems {
                     checklist_id,
                     omit_header,
+                    unchecked,
+                    contains,
+                    tags,
+                    limit,
+                    after,
+                    sort,
                 }),
         }) => {
             let checklist = Checklist::load(&db, checklist_id)
                 .await
                 .context("getting checklist")?
-                .context("checklist not found")?;
+                .context(NotFound("checklist"))?;
 
             if !omit_header {
-                show_checklist(&checklist);
-                println!("=========================")
+                out.checklist(&checklist);
+                let progress = checklist.progress(&db).await.context("getting progress")?;
+                out.progress(&checklist.id, &progress);
+                out.text(|| println!("========================="));
+            }
+
+            if let Some(sort) = sort {
+                let checked = checked_items(&db, &checklist.id).await?;
+                for item in checklist
+                    .items_by(&db, sort.into())
+                    .await
+                    .context("getting items")?
+                {
+                    out.item(&item, checked.contains(&item.id.to_string()));
+                }
+                return Ok(());
             }
 
-            for item in checklist.items(&db).await.context("getting items")? {
-                let checked = item.is_set(&db).await.context("getting item status")?;
-                show_item(&item, checked);
+            let filtered = unchecked
+                || contains.is_some()
+                || !tags.is_empty()
+                || limit.is_some()
+                || after.is_some();
+            if !filtered {
+                for root in checklist.tree(&db).await.context("getting items")? {
+                    root.walk(&mut |node, depth| out.node(node, depth));
+                }
+                return Ok(());
+            }
+
+            let mut query = ItemQuery::in_checklist(checklist.id.clone());
+            if unchecked {
+                query = query.checked(false);
+            }
+            if let Some(contains) = contains {
+                query = query.containing(contains);
             }
+            for tag in tags {
+                query = query.tagged(Tag::new(&tag)?);
+            }
+            if let Some(limit) = limit {
+                query = query.limit(limit);
+            }
+            if let Some(after) = after {
+                query = query.after(after);
+            }
+            let page = query.fetch(&db).await.context("getting items")?;
+            let checked = match unchecked {
+                true => HashSet::new(),
+                false => checked_items(&db, &checklist.id).await?,
+            };
+            for item in &page.items {
+                out.item(item, checked.contains(&item.id.to_string()));
+            }
+            out.next(page.next.as_ref());
         }
         cli::Noun::Item(ItemVerbAction {
             verb: ItemVerb::New(NewItem { checklist_id, name }),
@@ -74,7 +383,43 @@ async fn main() -> anyhow::Result<()> {
             let item = Item::new(&db, checklist_id, name)
                 .await
                 .context("creating item")?;
-            show_item(&item, false);
+            out.item(&item, false);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::AddMany(AddManyItems { checklist_id }),
+        }) => {
+            add_many(&db, out, checklist_id).await?;
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::NewChild(NewChildItem { parent_id, name }),
+        }) => {
+            let item = Item::new_child(&db, parent_id, name)
+                .await
+                .context("creating item")?;
+            out.item(&item, false);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Move(MoveItem {
+                    ids,
+                    destination: MoveDestination { under, top: _, to },
+                }),
+        }) => {
+            if let Some(to) = to {
+                Item::move_many_to_checklist(&db, ids, to)
+                    .await
+                    .context("moving items")?;
+            } else {
+                for id in ids {
+                    let mut item = Item::load(&db, id)
+                        .await
+                        .context("loading item from db")?
+                        .context(NotFound("item"))?;
+                    item.move_under(&db, under.clone())
+                        .await
+                        .context("moving item")?;
+                }
+            }
         }
         cli::Noun::Item(ItemVerbAction {
             verb: ItemVerb::Remove(RemoveItem { id }),
@@ -87,29 +432,443 @@ async fn main() -> anyhow::Result<()> {
             let mut item = Item::load(&db, id)
                 .await
                 .context("loading item from db")?
-                .context("item not found")?;
+                .context(NotFound("item"))?;
             let checked = item
                 .is_set(&db)
                 .await
                 .context("getting item check status")?;
+            // fails rather than undoing a toggle which happened since we loaded the item
             item.set_checked(&db, !checked)
                 .await
                 .context("updating item check status")?;
-            show_item(&item, !checked);
+            out.item(&item, !checked);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::Due(SetDue { id, when, clear: _ }),
+        }) => {
+            let mut item = Item::load(&db, id)
+                .await
+                .context("loading item from db")?
+                .context(NotFound("item"))?;
+            item.set_due(&db, when.map(|when| when.0))
+                .await
+                .context("setting due date")?;
+            let checked = item.is_set(&db).await.context("getting item status")?;
+            out.item(&item, checked);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::Remind(SetReminder { id, when, clear: _ }),
+        }) => {
+            let mut item = Item::load(&db, id)
+                .await
+                .context("loading item from db")?
+                .context(NotFound("item"))?;
+            item.set_reminder(&db, when.map(|when| when.0))
+                .await
+                .context("setting reminder")?;
+            let checked = item.is_set(&db).await.context("getting item status")?;
+            out.item(&item, checked);
+            if let Some(remind_at) = item.remind_at {
+                out.text(|| cprintln!("<dim>reminder at {}</dim>", local(remind_at)));
+            }
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::Priority(SetPriority { id, priority }),
+        }) => {
+            let mut item = Item::load(&db, id)
+                .await
+                .context("loading item from db")?
+                .context(NotFound("item"))?;
+            item.set_priority(&db, priority.into())
+                .await
+                .context("setting priority")?;
+            let checked = item.is_set(&db).await.context("getting item status")?;
+            out.item(&item, checked);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::Assign(AssignItem { id, user, clear: _ }),
+        }) => {
+            let assignee = match &user {
+                Some(name) => Some(find_user(&db, name).await?),
+                None => None,
+            };
+            let mut item = Item::load(&db, id)
+                .await
+                .context("loading item from db")?
+                .context(NotFound("item"))?;
+            item.assign(&db, assignee).await.context("assigning item")?;
+            let checked = item.is_set(&db).await.context("getting item status")?;
+            out.item(&item, checked);
+            if let Some(name) = user {
+                out.text(|| cprintln!("<dim>assigned to {name}</dim>"));
+            }
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Note(NoteVerbAction {
+                    verb: NoteVerb::Add(AddNote { id, text }),
+                }),
+        }) => {
+            let text = match text {
+                Some(text) => text,
+                None => std::io::read_to_string(std::io::stdin())
+                    .context("reading note from stdin")?
+                    .trim()
+                    .to_owned(),
+            };
+            let note = Note::add(&db, id, text).await.context("adding note")?;
+            show_note(&db, out, &note).await?;
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Note(NoteVerbAction {
+                    verb: NoteVerb::Ls(ShowNotes { id }),
+                }),
+        }) => {
+            for note in Note::of(&db, id).await.context("getting notes")? {
+                show_note(&db, out, &note).await?;
+            }
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Note(NoteVerbAction {
+                    verb: NoteVerb::Rm(RemoveNote { id }),
+                }),
+        }) => {
+            Note::delete(&db, id).await.context("deleting note")?;
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb: ItemVerb::Attach(AttachFile { id, file, name }),
+        }) => {
+            let name = match name {
+                Some(name) => name,
+                None => file
+                    .file_name()
+                    .context("the file has no name; pass --name")?
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            // refuse a file which is too large before reading all of it
+            let size = std::fs::metadata(&file)
+                .with_context(|| format!("reading {}", file.display()))?
+                .len();
+            if size > Attachment::MAX_SIZE as u64 {
+                return Err(anyhow::Error::from(checklist::Error::AttachmentTooLarge {
+                    size: usize::try_from(size).unwrap_or(usize::MAX),
+                    limit: Attachment::MAX_SIZE,
+                }))
+                .context("attaching file");
+            }
+            let contents =
+                std::fs::read(&file).with_context(|| format!("reading {}", file.display()))?;
+            let attachment = Attachment::add(&db, id, name, &contents)
+                .await
+                .context("attaching file")?;
+            out.attachment(&attachment);
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Attachment(AttachmentVerbAction {
+                    verb: AttachmentVerb::Get(GetAttachment { id, output }),
+                }),
+        }) => {
+            let attachment = Attachment::load(&db, id)
+                .await
+                .context("loading attachment")?
+                .context(NotFound("attachment"))?;
+            let contents = attachment.read(&db).await.context("reading attachment")?;
+            match output {
+                Some(output) => std::fs::write(&output, contents)
+                    .with_context(|| format!("writing {}", output.display()))?,
+                None => {
+                    out.raw();
+                    std::io::stdout()
+                        .write_all(&contents)
+                        .context("writing attachment to stdout")?
+                }
+            }
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Attachment(AttachmentVerbAction {
+                    verb: AttachmentVerb::Ls(ShowAttachments { id }),
+                }),
+        }) => {
+            for attachment in Attachment::of(&db, id)
+                .await
+                .context("getting attachments")?
+            {
+                out.attachment(&attachment);
+            }
+        }
+        cli::Noun::Item(ItemVerbAction {
+            verb:
+                ItemVerb::Attachment(AttachmentVerbAction {
+                    verb: AttachmentVerb::Rm(RemoveAttachment { id }),
+                }),
+        }) => {
+            Attachment::remove(&db, id)
+                .await
+                .context("removing attachment")?;
+        }
+        cli::Noun::Tag(TagVerbAction {
+            verb: TagVerb::Add(AddTags { target, tags }),
+        }) => {
+            let target = target.taggable();
+            for tag in tags {
+                Tag::new(&tag)?
+                    .add(&db, target.clone())
+                    .await
+                    .context("adding tag")?;
+            }
+        }
+        cli::Noun::Tag(TagVerbAction {
+            verb: TagVerb::Rm(RemoveTags { target, tags }),
+        }) => {
+            let target = target.taggable();
+            for tag in tags {
+                Tag::new(&tag)?
+                    .remove(&db, target.clone())
+                    .await
+                    .context("removing tag")?;
+            }
+        }
+        cli::Noun::Tag(TagVerbAction {
+            verb: TagVerb::Ls(ShowTags { target }),
+        }) => {
+            let tags = match target.taggable() {
+                Some(target) => Tag::of(&db, target).await,
+                None => Tag::all(&db).await,
+            }
+            .context("getting tags")?;
+            for tag in &tags {
+                out.tag(tag);
+            }
+        }
+        cli::Noun::User(UserVerbAction {
+            verb: UserVerb::ShowAll,
+        }) => {
+            for user in User::all(&db).await.context("getting users")? {
+                out.user(&user);
+            }
+        }
+        cli::Noun::User(UserVerbAction {
+            verb: UserVerb::New(NewUser { name }),
+        }) => {
+            let user = User::new(&db, name).await.context("creating user")?;
+            out.user(&user);
+        }
+        cli::Noun::Search(Search { terms }) => {
+            let query = terms.join(" ");
+            for hit in checklist::search(&db, &query).await.context("searching")? {
+                out.hit(&hit);
+            }
+        }
+        cli::Noun::Due(ShowDue { within }) => {
+            let now = Utc::now();
+            let until =
+                now + chrono::Duration::from_std(*within).context("duration out of range")?;
+            let overdue = Item::overdue(&db, now)
+                .await
+                .context("getting overdue items")?;
+            let upcoming = Item::upcoming(&db, now, until)
+                .await
+                .context("getting upcoming items")?;
+            if !overdue.is_empty() {
+                out.text(|| cprintln!("<bold>overdue</bold>"));
+                for item in &overdue {
+                    out.item(item, false);
+                }
+            }
+            if !upcoming.is_empty() {
+                out.text(|| cprintln!("<bold>upcoming</bold>"));
+                for item in &upcoming {
+                    out.item(item, false);
+                }
+            }
+        }
+        cli::Noun::Mine => {
+            let user = db
+                .user()
+                .context("showing your items needs a user; pass --user")?
+                .clone();
+            for item in Item::assigned_to(&db, user)
+                .await
+                .context("getting assigned items")?
+            {
+                out.item(&item, false);
+            }
+        }
+        cli::Noun::Stats(ShowStats { id: Some(id), .. }) => {
+            let checklist = Checklist::load(&db, id)
+                .await
+                .context("getting checklist")?
+                .context(NotFound("checklist"))?;
+            let progress = checklist.progress(&db).await.context("getting progress")?;
+            out.checklist(&checklist);
+            out.progress(&checklist.id, &progress);
+            if let Some(time) = progress.time_to_complete() {
+                out.text(|| cprintln!("completed in {}", show_duration(time)));
+            }
+        }
+        cli::Noun::Stats(ShowStats { id: None, days }) => {
+            let summary = Summary::gather(&db).await.context("gathering progress")?;
+            for (checklist, progress) in &summary.checklists {
+                out.checklist(checklist);
+                out.progress(&checklist.id, progress);
+            }
+            out.summary(&summary);
+
+            let first_day = Local::now().date_naive() - Days::new(days.saturating_sub(1).into());
+            let since = first_day
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest()
+                .context("no local midnight")?
+                .to_utc();
+            let counts = checks_per_period(&db, since, chrono::Duration::days(1), days as _)
+                .await
+                .context("counting checks")?;
+            if !counts.is_empty() {
+                out.text(|| cprintln!("<bold>checked per day</bold>"));
+            }
+            for (day, count) in first_day.iter_days().zip(counts) {
+                out.day(day, count);
+            }
+        }
+        cli::Noun::Remind(Remind {
+            interval,
+            notify,
+            once,
+        }) => {
+            if !once {
+                out.stream();
+            }
+            loop {
+                Checklist::roll_over_due(&db, Utc::now())
+                    .await
+                    .context("resetting recurring checklists")?;
+                for item in Item::pending_reminders(&db, Utc::now())
+                    .await
+                    .context("getting reminders")?
+                {
+                    remind(&db, out, &item, notify).await?;
+                    item.mark_reminded(&db)
+                        .await
+                        .context("recording reminder")?;
+                }
+                if once {
+                    break;
+                }
+                tokio::time::sleep(*interval).await;
+            }
+        }
+        cli::Noun::Sync(SyncRemote { remote }) => {
+            let synced = sync::sync(&db, &remote).await?;
+            out.synced(synced.pulled, synced.pushed);
         }
+        cli::Noun::ServeSync(ServeSync { listen }) => {
+            let listener = tokio::net::TcpListener::bind(listen)
+                .await
+                .with_context(|| format!("listening on {listen}"))?;
+            out.text(|| cprintln!("<dim>serving sync on http://{listen}</dim>"));
+            sync::serve(db, listener).await?;
+        }
+        cli::Noun::Key(_) => unreachable!("key management is handled before opening the database"),
     }
 
     Ok(())
 }
 
-fn show_checklist(Checklist { id, name, .. }: &Checklist) {
-    cprintln!("<dim>{id:>6}:</dim> {name}")
+async fn find_user(db: &Db, name: &str) -> anyhow::Result<UserId> {
+    User::by_name(db, name)
+        .await
+        .context("getting user")?
+        .map(|user| user.id)
+        .ok_or(NotFound("user"))
+        .with_context(|| format!("no user named {name:?}"))
+}
+
+/// The ids of the checked items in `checklist`, to list its items without asking about each.
+async fn checked_items(db: &Db, checklist: &ChecklistId) -> anyhow::Result<HashSet<String>> {
+    Ok(ItemQuery::in_checklist(checklist.clone())
+        .checked(true)
+        .fetch(db)
+        .await
+        .context("getting checked items")?
+        .items
+        .iter()
+        .map(|item| item.id.to_string())
+        .collect())
+}
+
+async fn show_note(db: &Db, out: &mut Output, note: &Note) -> anyhow::Result<()> {
+    let author = match &note.author {
+        Some(author) => User::load(db, author.clone())
+            .await
+            .context("getting author")?
+            .map_or_else(|| author.to_string(), |user| user.name),
+        None => "someone".to_owned(),
+    };
+    out.note(note, &author);
+    Ok(())
+}
+
+async fn remind(db: &Db, out: &mut Output, item: &Item, notify: bool) -> anyhow::Result<()> {
+    let checklist = Checklist::load(db, item.checklist.clone())
+        .await
+        .context("loading checklist")?
+        .map_or_else(
+            || item.checklist.to_string(),
+            |checklist| checklist.name.into_owned(),
+        );
+    out.text(|| cprintln!("<bold>reminder</bold> <dim>({checklist})</dim>"));
+    out.item(item, false);
+
+    if notify {
+        // a missing notification daemon should not stop the reminders printed above
+        if let Err(err) = notify_rust::Notification::new()
+            .summary(&checklist)
+            .body(&item.item)
+            .show()
+        {
+            ceprintln!("<yellow>warning:</yellow> showing notification: {err}");
+        }
+    }
+    Ok(())
 }
 
-fn show_item(Item { id, item, .. }: &Item, checked: bool) {
-    if checked {
-        cprintln!("<dim>{id:>6}:</dim> ☑ <strike>{item}</strike>");
-    } else {
-        cprintln!("<dim>{id:>6}:</dim> ☐ {item}");
+async fn add_many(db: &Db, out: &mut Output, checklist_id: ChecklistId) -> anyhow::Result<()> {
+    let lines = std::io::read_to_string(std::io::stdin()).context("reading items from stdin")?;
+
+    let mut batch = db.batch();
+    // indentation and id of each item which later lines may be nested under
+    let mut open = Vec::<(usize, ItemId)>::new();
+    let mut added = Vec::new();
+    for line in lines.lines() {
+        let item = line.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        while open.last().is_some_and(|(open, _)| *open >= indent) {
+            open.pop();
+        }
+        let id = match open.last() {
+            Some((_, parent)) => batch.new_child(parent.clone(), item.to_owned()),
+            None => batch.new_item(checklist_id.clone(), item.to_owned()),
+        };
+        added.push((open.len(), id.clone()));
+        open.push((indent, id));
     }
+    batch.commit().await.context("creating items")?;
+
+    for (depth, id) in added {
+        let item = Item::load(db, id)
+            .await
+            .context("loading item")?
+            .ok_or(NotFound("item"))?;
+        out.nested_item(&item, false, depth);
+    }
+    Ok(())
 }
@@ -0,0 +1,691 @@
+//! Printing results as colored text for people, or as records for scripts.
+//!
+//! In the machine-readable formats, everything a command prints is a flat record of some kind:
+//! JSON records carry their kind in a `kind` field, and CSV and TSV print a header row whenever
+//! the kind changes. Decoration which only makes sense to people, such as headings and the
+//! separator under a checklist, is left out of them.
+
+use checklist::{
+    Attachment, Checklist, ChecklistId, Cursor, Item, ItemNode, Note, Priority, Progress, Run,
+    RunItem, SearchHit, SearchTarget, Summary, Tag, User,
+};
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat, Utc};
+use color_print::{cformat, cprintln};
+
+use crate::cli::Format;
+
+pub(crate) struct Output {
+    format: Format,
+    /// Records waiting to be printed as one JSON array.
+    records: Vec<serde_json::Value>,
+    /// Kind of the last CSV or TSV row printed, to know when a header is due.
+    last_kind: Option<&'static str>,
+    /// Whether the command writes something other than records to standard output.
+    raw: bool,
+}
+
+impl Output {
+    pub(crate) fn new(format: Format) -> Self {
+        Self {
+            format,
+            records: Vec::new(),
+            last_kind: None,
+            raw: false,
+        }
+    }
+
+    /// Print whatever is still waiting to be printed.
+    pub(crate) fn finish(self) {
+        if self.format == Format::Json && !self.raw {
+            let records = serde_json::to_string_pretty(&self.records)
+                .expect("records always serialize to json");
+            println!("{records}");
+        }
+    }
+
+    /// Print records as they come from now on, for a command which may never finish: JSON is
+    /// printed as NDJSON instead.
+    pub(crate) fn stream(&mut self) {
+        if self.format == Format::Json {
+            self.format = Format::Ndjson;
+        }
+    }
+
+    /// Leave standard output to the command, which writes something other than records to it,
+    /// such as the contents of a file.
+    pub(crate) fn raw(&mut self) {
+        self.raw = true;
+    }
+
+    /// Print something only in text format.
+    pub(crate) fn text(&self, print: impl FnOnce()) {
+        if self.format == Format::Text {
+            print();
+        }
+    }
+
+    fn emit(&mut self, kind: &'static str, row: impl serde::Serialize) {
+        let delimiter = match self.format {
+            Format::Text => unreachable!("text is printed directly"),
+            Format::Json | Format::Ndjson => {
+                let mut record = serde_json::to_value(row).expect("rows always serialize to json");
+                if let Some(fields) = record.as_object_mut() {
+                    fields.insert("kind".to_owned(), kind.into());
+                }
+                if self.format == Format::Json {
+                    self.records.push(record);
+                } else {
+                    println!("{record}");
+                }
+                return;
+            }
+            Format::Csv => b',',
+            Format::Tsv => b'\t',
+        };
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(self.last_kind != Some(kind))
+            .from_writer(Vec::new());
+        writer.serialize(row).expect("rows are flat records");
+        let line = writer.into_inner().expect("writing to memory cannot fail");
+        print!("{}", String::from_utf8_lossy(&line));
+        self.last_kind = Some(kind);
+    }
+
+    pub(crate) fn checklist(&mut self, checklist: &Checklist) {
+        if self.format == Format::Text {
+            show_checklist(checklist);
+            return;
+        }
+        self.emit(
+            "checklist",
+            ChecklistRow {
+                id: checklist.id.to_string(),
+                name: &checklist.name,
+                is_template: checklist.is_template,
+                recurrence: checklist.recurrence.as_ref().map(ToString::to_string),
+                next_rollover: checklist.next_rollover().map(timestamp),
+                archived: checklist.archived,
+                locked: checklist.locked,
+                auto_archive: checklist.auto_archive,
+                owner: checklist.owner.as_ref().map(ToString::to_string),
+                created_at: checklist.created_at.map(timestamp),
+            },
+        );
+    }
+
+    pub(crate) fn item(&mut self, item: &Item, checked: bool) {
+        if self.format == Format::Text {
+            show_item(item, checked, 0);
+            return;
+        }
+        self.emit("item", ItemRow::new(item, checked, None));
+    }
+
+    /// An item at `depth` in its checklist, as when listing newly added items.
+    pub(crate) fn nested_item(&mut self, item: &Item, checked: bool, depth: usize) {
+        if self.format == Format::Text {
+            show_item(item, checked, depth);
+            return;
+        }
+        self.emit("item", ItemRow::new(item, checked, Some(depth)));
+    }
+
+    pub(crate) fn node(&mut self, node: &ItemNode, depth: usize) {
+        if self.format == Format::Text {
+            show_node(node, depth);
+            return;
+        }
+        self.emit("item", ItemRow::new(&node.item, node.checked, Some(depth)));
+    }
+
+    pub(crate) fn run(&mut self, run: &Run, items: bool) {
+        if self.format == Format::Text {
+            show_run(run, items);
+            return;
+        }
+        self.emit(
+            "run",
+            RunRow {
+                checklist: run.checklist.to_string(),
+                started_at: timestamp(run.started_at),
+                ended_at: timestamp(run.ended_at),
+                completed: run.completed(),
+                total: run.total(),
+            },
+        );
+        if items {
+            for RunItem { item, checked } in &run.items {
+                self.emit(
+                    "run_item",
+                    RunItemRow {
+                        started_at: timestamp(run.started_at),
+                        item,
+                        checked: *checked,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Someone with access to a checklist; `access` is "owner" for its owner.
+    pub(crate) fn share(&mut self, user: &str, access: &str) {
+        if self.format == Format::Text {
+            cprintln!("{user} <dim>({access})</dim>");
+            return;
+        }
+        self.emit("share", ShareRow { user, access });
+    }
+
+    pub(crate) fn note(&mut self, note: &Note, author: &str) {
+        if self.format == Format::Text {
+            cprintln!(
+                "<dim>{:>6}: {author}, {}</dim>",
+                note.id,
+                local(note.created_at)
+            );
+            for line in note.text.lines() {
+                println!("        {line}");
+            }
+            return;
+        }
+        self.emit(
+            "note",
+            NoteRow {
+                id: note.id.to_string(),
+                item: note.item.to_string(),
+                author,
+                created_at: timestamp(note.created_at),
+                text: &note.text,
+            },
+        );
+    }
+
+    pub(crate) fn attachment(&mut self, attachment: &Attachment) {
+        if self.format == Format::Text {
+            cprintln!(
+                "<dim>{:>6}:</dim> {} <dim>({} bytes, {})</dim>",
+                attachment.id,
+                attachment.name,
+                attachment.size,
+                local(attachment.created_at)
+            );
+            return;
+        }
+        self.emit(
+            "attachment",
+            AttachmentRow {
+                id: attachment.id.to_string(),
+                item: attachment.item.to_string(),
+                name: &attachment.name,
+                size: attachment.size,
+                created_at: timestamp(attachment.created_at),
+            },
+        );
+    }
+
+    pub(crate) fn tag(&mut self, tag: &Tag) {
+        if self.format == Format::Text {
+            println!("{tag}");
+            return;
+        }
+        self.emit("tag", TagRow { tag });
+    }
+
+    pub(crate) fn user(&mut self, user: &User) {
+        if self.format == Format::Text {
+            cprintln!("<dim>{:>6}:</dim> {}", user.id, user.name);
+            return;
+        }
+        self.emit(
+            "user",
+            UserRow {
+                id: user.id.to_string(),
+                name: &user.name,
+            },
+        );
+    }
+
+    pub(crate) fn hit(&mut self, hit: &SearchHit) {
+        if self.format == Format::Text {
+            show_hit(hit);
+            return;
+        }
+        let (target, id, checklist) = match &hit.target {
+            SearchTarget::Checklist(id) => ("checklist", id.to_string(), None),
+            SearchTarget::Item { id, checklist } => {
+                ("item", id.to_string(), Some(checklist.to_string()))
+            }
+        };
+        self.emit(
+            "hit",
+            HitRow {
+                target,
+                id,
+                checklist,
+                score: hit.score,
+                text: hit.text(),
+            },
+        );
+    }
+
+    /// The cursor from which the next page starts, if there is one.
+    pub(crate) fn next(&mut self, next: Option<&Cursor>) {
+        let Some(next) = next else {
+            return;
+        };
+        if self.format == Format::Text {
+            cprintln!("<dim>more: --after {next}</dim>");
+            return;
+        }
+        self.emit(
+            "next",
+            NextRow {
+                after: next.to_string(),
+            },
+        );
+    }
+
+    pub(crate) fn progress(&mut self, checklist: &ChecklistId, progress: &Progress) {
+        if self.format == Format::Text {
+            println!("{}", show_progress(progress));
+            return;
+        }
+        self.emit(
+            "progress",
+            ProgressRow {
+                checklist: checklist.to_string(),
+                checked: progress.checked,
+                total: progress.total,
+                percent: progress.percent(),
+                started_at: progress.started_at.map(timestamp),
+                completed_at: progress.completed_at.map(timestamp),
+                seconds_to_complete: progress.time_to_complete().map(|time| time.num_seconds()),
+            },
+        );
+    }
+
+    pub(crate) fn summary(&mut self, summary: &Summary) {
+        if self.format == Format::Text {
+            cprintln!(
+                "<bold>{} lists, {} complete; {} of {} items checked ({:.0}%)</bold>",
+                summary.checklists.len(),
+                summary.completed(),
+                summary.checked(),
+                summary.total(),
+                summary.percent()
+            );
+            if let Some(time) = summary.mean_time_to_complete() {
+                cprintln!("lists take {} to complete on average", show_duration(time));
+            }
+            return;
+        }
+        self.emit(
+            "summary",
+            SummaryRow {
+                checklists: summary.checklists.len(),
+                completed: summary.completed(),
+                checked: summary.checked(),
+                total: summary.total(),
+                percent: summary.percent(),
+                mean_seconds_to_complete: summary
+                    .mean_time_to_complete()
+                    .map(|time| time.num_seconds()),
+            },
+        );
+    }
+
+    /// How many items were checked on `day`.
+    pub(crate) fn day(&mut self, day: NaiveDate, checked: usize) {
+        if self.format == Format::Text {
+            cprintln!("{day} {checked:>4} <green>{}</green>", "■".repeat(checked));
+            return;
+        }
+        self.emit(
+            "day",
+            DayRow {
+                day: day.to_string(),
+                checked,
+            },
+        );
+    }
+
+    pub(crate) fn synced(&mut self, pulled: usize, pushed: usize) {
+        if self.format == Format::Text {
+            cprintln!("<dim>pulled {pulled} and pushed {pushed} changes</dim>");
+            return;
+        }
+        self.emit("sync", SyncRow { pulled, pushed });
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ChecklistRow<'a> {
+    id: String,
+    name: &'a str,
+    is_template: bool,
+    recurrence: Option<String>,
+    next_rollover: Option<String>,
+    archived: bool,
+    locked: bool,
+    auto_archive: bool,
+    owner: Option<String>,
+    created_at: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ItemRow<'a> {
+    id: String,
+    checklist: String,
+    parent: Option<String>,
+    /// How deeply the item is nested, where the output shows nesting.
+    depth: Option<usize>,
+    item: &'a str,
+    checked: bool,
+    checked_at: Option<String>,
+    position: i64,
+    priority: &'static str,
+    due_at: Option<String>,
+    remind_at: Option<String>,
+    assignee: Option<String>,
+    created_at: Option<String>,
+}
+
+impl<'a> ItemRow<'a> {
+    fn new(item: &'a Item, checked: bool, depth: Option<usize>) -> Self {
+        Self {
+            id: item.id.to_string(),
+            checklist: item.checklist.to_string(),
+            parent: item.parent.as_ref().map(ToString::to_string),
+            depth,
+            item: &item.item,
+            checked,
+            checked_at: item.checked_at.map(timestamp),
+            position: item.position,
+            priority: match item.priority {
+                Priority::Low => "low",
+                Priority::Normal => "normal",
+                Priority::High => "high",
+                Priority::Urgent => "urgent",
+            },
+            due_at: item.due_at.map(timestamp),
+            remind_at: item.remind_at.map(timestamp),
+            assignee: item.assignee.as_ref().map(ToString::to_string),
+            created_at: item.created_at.map(timestamp),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct RunRow {
+    checklist: String,
+    started_at: String,
+    ended_at: String,
+    completed: usize,
+    total: usize,
+}
+
+#[derive(serde::Serialize)]
+struct RunItemRow<'a> {
+    /// When the run this item belongs to started.
+    started_at: String,
+    item: &'a str,
+    checked: bool,
+}
+
+#[derive(serde::Serialize)]
+struct ShareRow<'a> {
+    user: &'a str,
+    access: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct NoteRow<'a> {
+    id: String,
+    item: String,
+    author: &'a str,
+    created_at: String,
+    text: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct AttachmentRow<'a> {
+    id: String,
+    item: String,
+    name: &'a str,
+    size: u64,
+    created_at: String,
+}
+
+#[derive(serde::Serialize)]
+struct TagRow<'a> {
+    tag: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct UserRow<'a> {
+    id: String,
+    name: &'a str,
+}
+
+#[derive(serde::Serialize)]
+struct HitRow {
+    /// "checklist" or "item".
+    target: &'static str,
+    id: String,
+    /// The checklist containing a matching item.
+    checklist: Option<String>,
+    score: f32,
+    text: String,
+}
+
+#[derive(serde::Serialize)]
+struct NextRow {
+    after: String,
+}
+
+#[derive(serde::Serialize)]
+struct ProgressRow {
+    checklist: String,
+    checked: usize,
+    total: usize,
+    percent: f64,
+    started_at: Option<String>,
+    completed_at: Option<String>,
+    seconds_to_complete: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct SummaryRow {
+    checklists: usize,
+    completed: usize,
+    checked: usize,
+    total: usize,
+    percent: f64,
+    mean_seconds_to_complete: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct DayRow {
+    day: String,
+    checked: usize,
+}
+
+#[derive(serde::Serialize)]
+struct SyncRow {
+    pulled: usize,
+    pushed: usize,
+}
+
+/// Times in records are RFC 3339, in UTC.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub(crate) fn local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+pub(crate) fn show_duration(duration: chrono::Duration) -> humantime::FormattedDuration {
+    let seconds = duration.num_seconds().max(0) as u64;
+    humantime::format_duration(std::time::Duration::from_secs(seconds))
+}
+
+fn show_checklist(checklist: &Checklist) {
+    let Checklist {
+        id,
+        name,
+        is_template,
+        recurrence,
+        archived,
+        locked,
+        ..
+    } = checklist;
+    let mut notes = Vec::new();
+    if *is_template {
+        notes.push("template".to_owned());
+    } else if let Some(recurrence) = recurrence {
+        match checklist.next_rollover() {
+            Some(next) => notes.push(format!("{recurrence}; resets {}", local(next))),
+            None => notes.push(recurrence.to_string()),
+        }
+    }
+    if *archived {
+        notes.push("archived".to_owned());
+    }
+    if *locked {
+        notes.push("locked".to_owned());
+    }
+    if notes.is_empty() {
+        cprintln!("<dim>{id:>6}:</dim> {name}");
+    } else {
+        cprintln!(
+            "<dim>{id:>6}:</dim> {name} <dim>({})</dim>",
+            notes.join(", ")
+        );
+    }
+}
+
+fn show_run(run: &Run, items: bool) {
+    cprintln!(
+        "{} – {}: {}/{}",
+        local(run.started_at),
+        local(run.ended_at),
+        run.completed(),
+        run.total()
+    );
+    if items {
+        for RunItem { item, checked } in &run.items {
+            if *checked {
+                cprintln!("    ☑ <strike>{item}</strike>");
+            } else {
+                cprintln!("    ☐ {item}");
+            }
+        }
+    }
+}
+
+fn show_item(
+    Item {
+        id,
+        item,
+        due_at,
+        priority,
+        ..
+    }: &Item,
+    checked: bool,
+    depth: usize,
+) {
+    let indent = "  ".repeat(depth);
+    let due = show_due(*due_at, checked);
+    let priority = show_priority(*priority);
+    if checked {
+        cprintln!("<dim>{id:>6}:</dim> {indent}☑ <strike>{item}</strike>{priority}{due}");
+    } else {
+        cprintln!("<dim>{id:>6}:</dim> {indent}☐ {item}{priority}{due}");
+    }
+}
+
+fn show_priority(priority: Priority) -> String {
+    match priority {
+        Priority::Low => cformat!(" <dim>(low)</dim>"),
+        Priority::Normal => String::new(),
+        Priority::High => cformat!(" <yellow>!</yellow>"),
+        Priority::Urgent => cformat!(" <red,bold>!!</red,bold>"),
+    }
+}
+
+fn show_progress(progress: &Progress) -> String {
+    const WIDTH: usize = 20;
+    let filled = (progress.checked * WIDTH)
+        .checked_div(progress.total)
+        .unwrap_or_default();
+    cformat!(
+        "<green>{}</green><dim>{}</dim> {}/{} ({:.0}%)",
+        "█".repeat(filled),
+        "░".repeat(WIDTH - filled),
+        progress.checked,
+        progress.total,
+        progress.percent()
+    )
+}
+
+fn show_due(due_at: Option<DateTime<Utc>>, checked: bool) -> String {
+    match due_at {
+        None => String::new(),
+        Some(due_at) if !checked && due_at < Utc::now() => {
+            cformat!(" <red>(due {})</red>", local(due_at))
+        }
+        Some(due_at) => cformat!(" <dim>(due {})</dim>", local(due_at)),
+    }
+}
+
+fn show_node(node: &ItemNode, depth: usize) {
+    let ItemNode { item, .. } = node;
+    let Item {
+        id,
+        item,
+        due_at,
+        priority,
+        ..
+    } = item;
+    let indent = "  ".repeat(depth);
+    let mark = if node.is_complete() { "☑" } else { "☐" };
+    let due = show_due(*due_at, node.is_complete());
+    let priority = show_priority(*priority);
+    let progress = if node.children.is_empty() {
+        String::new()
+    } else {
+        let (done, total) = node.progress();
+        cformat!(" <dim>({done}/{total})</dim>")
+    };
+    if node.is_complete() {
+        cprintln!(
+            "<dim>{id:>6}:</dim> {indent}{mark} <strike>{item}</strike>{priority}{progress}{due}"
+        );
+    } else {
+        cprintln!("<dim>{id:>6}:</dim> {indent}{mark} {item}{priority}{progress}{due}");
+    }
+}
+
+fn show_hit(hit: &SearchHit) {
+    let text = hit
+        .highlights
+        .iter()
+        .map(|fragment| {
+            if fragment.matched {
+                cformat!("<bold,underline>{}</bold,underline>", fragment.text)
+            } else {
+                fragment.text.clone()
+            }
+        })
+        .collect::<String>();
+    match &hit.target {
+        SearchTarget::Checklist(id) => cprintln!("<dim>{id:>6}:</dim> {text}"),
+        SearchTarget::Item { id, checklist } => {
+            cprintln!("<dim>{id:>6}:</dim> {text} <dim>(in {checklist})</dim>")
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Syncing over HTTP.
+//!
+//! The server exposes its database's op log:
+//!
+//! - `GET /ops?since=<cursor>&limit=<n>&skip=<node>` returns up to `n` ops after `cursor`, leaving
+//!   out those made by the database `node`, along with the cursor to ask from next and the
+//!   server's own node id;
+//! - `POST /ops` applies the ops in the body, returning how many were new.
+//!
+//! Both bodies are JSON. The client pulls, then pushes, a page at a time, recording its progress in
+//! its own database so that each sync only sends what the other side has not seen. Neither side
+//! sends the other the ops it made itself, and the client does not push back the ops it just
+//! pulled, wherever they were made.
+
+use std::{collections::HashSet, convert::Infallible, sync::Arc};
+
+use anyhow::{Context as _, bail};
+use bytes::Bytes;
+use checklist::{Db, Hlc, Op, OpCursor};
+use color_print::ceprintln;
+use http_body_util::{BodyExt as _, Full};
+use hyper::{
+    Method, Request, Response, StatusCode, body::Incoming, header, server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use reqwest::Url;
+use tokio::net::TcpListener;
+
+const OPS_PATH: &str = "ops";
+
+/// Most ops sent in one request, in either direction.
+const PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Pulled {
+    ops: Vec<Op>,
+    cursor: OpCursor,
+    /// Node id of the server's database.
+    node: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Pushed {
+    applied: usize,
+}
+
+/// How many ops a sync moved in each direction, whether or not the receiving side already had them.
+#[derive(Debug, Clone, Copy)]
+pub struct Synced {
+    pub pulled: usize,
+    pub pushed: usize,
+}
+
+/// Serve sync requests for `db` on `listener` until an error stops it.
+pub async fn serve(db: Db, listener: TcpListener) -> anyhow::Result<()> {
+    let db = Arc::new(db);
+    loop {
+        let (stream, _) = listener.accept().await.context("accepting connection")?;
+        let db = db.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| handle(db.clone(), request));
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                ceprintln!("<yellow>warning:</yellow> serving sync connection: {err}");
+            }
+        });
+    }
+}
+
+async fn handle(
+    db: Arc<Db>,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let path = request.uri().path().trim_start_matches('/');
+    let response = match (request.method(), path) {
+        (&Method::GET, OPS_PATH) => pull(&db, request.uri().query()).await,
+        (&Method::POST, OPS_PATH) => push(&db, request.into_body()).await,
+        _ => Ok(status(StatusCode::NOT_FOUND, "no such endpoint")),
+    };
+    Ok(response.unwrap_or_else(|err| {
+        ceprintln!("<red>error:</red> {err:#}");
+        status(StatusCode::INTERNAL_SERVER_ERROR, &format!("{err:#}"))
+    }))
+}
+
+async fn pull(db: &Db, query: Option<&str>) -> anyhow::Result<Response<Full<Bytes>>> {
+    let since = match form_value(query, "since").map(str::parse::<OpCursor>) {
+        None => OpCursor::default(),
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return Ok(status(StatusCode::BAD_REQUEST, "invalid cursor")),
+    };
+    let limit = match form_value(query, "limit").map(str::parse::<u32>) {
+        None => PAGE_SIZE,
+        Some(Ok(limit)) => limit.min(PAGE_SIZE),
+        Some(Err(_)) => return Ok(status(StatusCode::BAD_REQUEST, "invalid limit")),
+    };
+    let skip = form_value(query, "skip");
+    let (mut ops, cursor) = db
+        .export_ops_since(since, limit)
+        .await
+        .context("exporting ops")?;
+    ops.retain(|op| Some(op.hlc().node.as_str()) != skip);
+    let node = db.node_id().await.context("reading node id")?;
+    json(&Pulled { ops, cursor, node })
+}
+
+async fn push(db: &Db, body: Incoming) -> anyhow::Result<Response<Full<Bytes>>> {
+    let body = body.collect().await.context("reading ops")?.to_bytes();
+    let Ok(ops) = serde_json::from_slice::<Vec<Op>>(&body) else {
+        return Ok(status(StatusCode::BAD_REQUEST, "invalid ops"));
+    };
+    let applied = db.apply_ops(ops).await.context("applying ops")?;
+    json(&Pushed { applied })
+}
+
+/// The value of `key` in a url query string, if present.
+fn form_value<'q>(query: Option<&'q str>, key: &str) -> Option<&'q str> {
+    query?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+fn json(body: &impl serde::Serialize) -> anyhow::Result<Response<Full<Bytes>>> {
+    let body = serde_json::to_vec(body).context("encoding response")?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)))
+        .expect("response is valid"))
+}
+
+fn status(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::new(Bytes::from(message.to_owned())))
+        .expect("response is valid")
+}
+
+/// Pull changes from the server at `remote`, then push local changes to it.
+pub async fn sync(db: &Db, remote: &Url) -> anyhow::Result<Synced> {
+    let client = reqwest::Client::new();
+    let ops_url = remote.join(OPS_PATH).context("building sync url")?;
+    let peer = remote.as_str();
+    let node = db.node_id().await.context("reading node id")?;
+    let mut state = db.sync_state(peer).await.context("loading sync state")?;
+
+    let mut pulled = HashSet::<Hlc>::new();
+    let peer_node = loop {
+        let response = client
+            .get(ops_url.clone())
+            .query(&[
+                ("since", state.pulled.to_string()),
+                ("limit", PAGE_SIZE.to_string()),
+                ("skip", node.clone()),
+            ])
+            .send()
+            .await
+            .context("pulling ops")?;
+        let response = check(response).await.context("pulling ops")?;
+        let Pulled {
+            ops,
+            cursor,
+            node: peer_node,
+        } = response.json().await.context("decoding pulled ops")?;
+        if cursor == state.pulled {
+            break peer_node;
+        }
+        pulled.extend(ops.iter().map(|op| op.hlc().clone()));
+        db.apply_ops(ops).await.context("applying pulled ops")?;
+        state.pulled = cursor;
+        db.set_sync_state(peer, state)
+            .await
+            .context("saving sync state")?;
+    };
+
+    let mut pushed = 0;
+    loop {
+        let (mut ops, cursor) = db
+            .export_ops_since(state.pushed, PAGE_SIZE)
+            .await
+            .context("exporting ops")?;
+        if cursor == state.pushed {
+            break;
+        }
+        ops.retain(|op| op.hlc().node != peer_node && !pulled.contains(op.hlc()));
+        if !ops.is_empty() {
+            let response = client
+                .post(ops_url.clone())
+                .json(&ops)
+                .send()
+                .await
+                .context("pushing ops")?;
+            check(response).await.context("pushing ops")?;
+            pushed += ops.len();
+        }
+        state.pushed = cursor;
+        db.set_sync_state(peer, state)
+            .await
+            .context("saving sync state")?;
+    }
+
+    Ok(Synced {
+        pulled: pulled.len(),
+        pushed,
+    })
+}
+
+/// Fail with the server's message unless `response` succeeded.
+async fn check(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let code = response.status();
+    if code.is_success() {
+        return Ok(response);
+    }
+    let message = response.text().await.unwrap_or_default();
+    bail!("server responded {code}: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use checklist::{Checklist, Db};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    async fn open(dir: &TempDir) -> Db {
+        Db::new(dir.path(), b"sync test key")
+            .await
+            .unwrap()
+            .with_full_access()
+    }
+
+    /// Create a checklist named `name` with more items than fit in one page.
+    async fn fill(db: &Db, name: &'static str) {
+        db.transaction(|batch| {
+            let checklist = batch.new_checklist(name);
+            for n in 0..PAGE_SIZE {
+                batch.new_item(checklist.clone(), n.to_string());
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn names(db: &Db) -> Vec<String> {
+        let mut names = Checklist::all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|checklist| checklist.name.into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn syncing_over_http_moves_each_op_once() {
+        let dirs = [(); 3].map(|_| TempDir::new().unwrap());
+        let server = open(&dirs[0]).await;
+        let client = open(&dirs[1]).await;
+        let other = open(&dirs[2]).await;
+        fill(&server, "server").await;
+        fill(&client, "client").await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(serve(server, listener));
+
+        // a checklist and its items, each one op
+        let ops = PAGE_SIZE as usize + 1;
+        let synced = sync(&client, &remote).await.unwrap();
+        assert_eq!((synced.pulled, synced.pushed), (ops, ops));
+        let synced = sync(&client, &remote).await.unwrap();
+        assert_eq!((synced.pulled, synced.pushed), (0, 0));
+        assert_eq!(names(&client).await, ["client", "server"]);
+
+        let synced = sync(&other, &remote).await.unwrap();
+        assert_eq!((synced.pulled, synced.pushed), (2 * ops, 0));
+        assert_eq!(names(&other).await, ["client", "server"]);
+    }
+}
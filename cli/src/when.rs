@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Error, Result, anyhow};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+/// A point in time given on the command line.
+///
+/// Accepts, in order of preference:
+///
+/// - a relative duration from now, such as `2h`, `+3d` or `1week 2days`
+/// - an RFC 3339 timestamp, such as `2025-03-01T09:00:00Z`
+/// - a local date and time, such as `2025-03-01 09:00`
+/// - a local date, such as `2025-03-01`, meaning the end of that day
+#[derive(Debug, Clone, Copy)]
+pub struct When(pub DateTime<Utc>);
+
+impl FromStr for When {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        if let Ok(duration) = humantime::parse_duration(s.strip_prefix('+').unwrap_or(s)) {
+            let duration = chrono::Duration::from_std(duration).context("duration out of range")?;
+            return Ok(Self(Utc::now() + duration));
+        }
+
+        if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Self(time.to_utc()));
+        }
+
+        let local = if let Ok(time) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") {
+            time
+        } else if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            date.and_time(NaiveTime::from_hms_opt(23, 59, 59).expect("valid time"))
+        } else {
+            return Err(anyhow!(
+                "expected a duration like \"2h\", a date like \"2025-03-01\", or a date and time like \"2025-03-01 09:00\""
+            ));
+        };
+        local
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|time| Self(time.to_utc()))
+            .context("that local time does not exist")
+    }
+}
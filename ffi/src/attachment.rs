@@ -0,0 +1,69 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Db, ItemId, Result,
+    marc::{Marc, marc},
+};
+
+pub type AttachmentId = String;
+
+/// A file attached to an item.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Attachment {
+    inner: checklist::Attachment,
+}
+
+impl Attachment {
+    pub(crate) fn marc(inner: checklist::Attachment) -> Marc<Self> {
+        marc(Self { inner })
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Attachment {
+    pub fn id(&self) -> AttachmentId {
+        self.inner.id.to_string()
+    }
+
+    pub fn item_id(&self) -> ItemId {
+        self.inner.item.to_string()
+    }
+
+    /// The file name it was attached under.
+    pub fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    /// Id of the user who attached the file, if any.
+    pub fn uploader_id(&self) -> Option<String> {
+        self.inner.uploader.as_ref().map(ToString::to_string)
+    }
+
+    /// Size of the contents in bytes.
+    pub fn size(&self) -> u64 {
+        self.inner.size
+    }
+
+    /// When this file was attached, in milliseconds since the Unix epoch.
+    pub fn created_at(&self) -> i64 {
+        self.inner.created_at.timestamp_millis()
+    }
+
+    /// The contents of this attachment.
+    pub async fn read(&self, db: &Db) -> Result<Vec<u8>> {
+        self.inner.read(db).await.map_err(Into::into)
+    }
+}
+
+/// Remove the attachment `attachment_id` from its item.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn attachment_remove(db: &Db, attachment_id: AttachmentId) -> Result<()> {
+    let attachment_id = attachment_id.parse()?;
+    checklist::Attachment::remove(db, attachment_id)
+        .await
+        .map_err(Into::into)
+}
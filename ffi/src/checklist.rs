@@ -1,13 +1,21 @@
+use std::collections::HashMap;
+
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+use checklist::{Tag, Taggable};
+use chrono::Utc;
+
 use crate::{
-    Db, Item, Result,
+    Access, Db, Grant, Item, ItemOrder, ItemPage, Progress, Result, Run, UserId,
+    due::from_millis,
     marc::{Marc, marc},
+    page::ChecklistPage,
 };
 
 pub type ChecklistId = String;
 
+#[derive(Clone)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Checklist {
@@ -66,6 +74,109 @@ pub async fn checklist_all(db: &Db) -> Result<Vec<Marc<Checklist>>> {
     all_impl(db).await
 }
 
+async fn page_impl(
+    db: &Db,
+    archived: bool,
+    contains: Option<String>,
+    limit: u32,
+    after: Option<String>,
+) -> Result<ChecklistPage> {
+    let mut query = checklist::ChecklistQuery::new()
+        .archived(archived)
+        .limit(limit as _);
+    if let Some(contains) = contains {
+        query = query.containing(contains);
+    }
+    if let Some(after) = after {
+        query = query.after(after.parse()?);
+    }
+    query.fetch(db).await.map(Into::into).map_err(Into::into)
+}
+
+/// Fetch one page of checklists; only archived ones when `archived` is set, else only the rest.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn checklist_page(
+    db: &Db,
+    archived: bool,
+    contains: Option<String>,
+    limit: u32,
+    after: Option<String>,
+) -> Result<ChecklistPage> {
+    page_impl(db, archived, contains, limit, after).await
+}
+
+async fn instantiate_impl(
+    db: &Db,
+    template_id: ChecklistId,
+    vars: Vec<String>,
+) -> Result<Checklist> {
+    let template_id = template_id.parse()?;
+    let vars = vars
+        .iter()
+        .map(|var| {
+            let (name, value) = var
+                .split_once('=')
+                .ok_or_else(|| checklist::Error::InvalidTemplateVariable { var: var.clone() })?;
+            Ok((name.trim().to_owned(), value.to_owned()))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    checklist::Checklist::instantiate(db, template_id, &vars)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Create a new checklist from a template; `vars` are placeholder values as `name=value`.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn checklist_instantiate(
+    db: &Db,
+    template_id: ChecklistId,
+    vars: Vec<String>,
+) -> Result<Checklist> {
+    instantiate_impl(db, template_id, vars).await
+}
+
+async fn duplicate_impl(db: &Db, id: ChecklistId, reset_checked: bool) -> Result<Checklist> {
+    let id = id.parse()?;
+    checklist::Checklist::duplicate(db, id, reset_checked)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Copy a checklist with all its items; when `reset_checked` is set, no item of the copy is checked.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn checklist_duplicate(
+    db: &Db,
+    id: ChecklistId,
+    reset_checked: bool,
+) -> Result<Checklist> {
+    duplicate_impl(db, id, reset_checked).await
+}
+
+async fn merge_into_impl(db: &Db, source: ChecklistId, target: ChecklistId) -> Result<Checklist> {
+    let source = source.parse()?;
+    let target = target.parse()?;
+    checklist::Checklist::merge_into(db, source, target)
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+/// Move every item of `source` into `target`, then delete `source`. Returns the target.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn checklist_merge_into(
+    db: &Db,
+    source: ChecklistId,
+    target: ChecklistId,
+) -> Result<Checklist> {
+    merge_into_impl(db, source, target).await
+}
+
 async fn delete_impl(db: &Db, id: ChecklistId) -> Result<()> {
     let id = id.parse()?;
     checklist::Checklist::delete(db, id)
@@ -96,6 +207,40 @@ impl Checklist {
         all_impl(db).await
     }
 
+    /// Fetch one page of checklists; only archived ones when `archived` is set, else only the rest.
+    pub async fn page(
+        db: &Db,
+        archived: bool,
+        contains: Option<String>,
+        limit: u32,
+        after: Option<String>,
+    ) -> Result<ChecklistPage> {
+        page_impl(db, archived, contains, limit, after).await
+    }
+
+    /// Create a new checklist from a template; `vars` are placeholder values as `name=value`.
+    pub async fn instantiate(
+        db: &Db,
+        template_id: ChecklistId,
+        vars: Vec<String>,
+    ) -> Result<Checklist> {
+        instantiate_impl(db, template_id, vars).await
+    }
+
+    /// Copy a checklist with all its items; when `reset_checked` is set, no item of the copy is checked.
+    pub async fn duplicate(db: &Db, id: ChecklistId, reset_checked: bool) -> Result<Checklist> {
+        duplicate_impl(db, id, reset_checked).await
+    }
+
+    /// Move every item of `source` into `target`, then delete `source`. Returns the target.
+    pub async fn merge_into(
+        db: &Db,
+        source: ChecklistId,
+        target: ChecklistId,
+    ) -> Result<Checklist> {
+        merge_into_impl(db, source, target).await
+    }
+
     pub async fn delete(db: &Db, id: ChecklistId) -> Result<()> {
         delete_impl(db, id).await
     }
@@ -112,6 +257,181 @@ impl Checklist {
             .map_err(Into::into)
     }
 
+    /// All of this checklist's items, in the given order.
+    pub async fn items_by(&self, db: &Db, order: ItemOrder) -> Result<Vec<Marc<Item>>> {
+        self.inner
+            .items_by(db, order.into())
+            .await
+            .map(|items| items.into_iter().map(Item::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Fetch one page of this checklist's items.
+    ///
+    /// `after` is the cursor returned by the previous page, if any.
+    pub async fn items_page(
+        &self,
+        db: &Db,
+        unchecked_only: bool,
+        contains: Option<String>,
+        limit: u32,
+        after: Option<String>,
+    ) -> Result<ItemPage> {
+        let mut query = checklist::ItemQuery::in_checklist(self.inner.id.clone()).limit(limit as _);
+        if unchecked_only {
+            query = query.checked(false);
+        }
+        if let Some(contains) = contains {
+            query = query.containing(contains);
+        }
+        if let Some(after) = after {
+            query = query.after(after.parse()?);
+        }
+        query.fetch(db).await.map(Into::into).map_err(Into::into)
+    }
+
+    /// Rename this checklist, failing with a conflict if it has changed since it was loaded.
+    ///
+    /// Returns the renamed checklist.
+    pub async fn rename(&self, db: &Db, name: String) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.rename(db, name).await?;
+        Ok(inner.into())
+    }
+
+    /// Hide this checklist from listings. Returns the updated checklist.
+    pub async fn archive(&self, db: &Db) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.archive(db).await?;
+        Ok(inner.into())
+    }
+
+    /// List this checklist again. Returns the updated checklist.
+    pub async fn unarchive(&self, db: &Db) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.unarchive(db).await?;
+        Ok(inner.into())
+    }
+
+    /// Make this checklist and its items read-only. Returns the updated checklist.
+    pub async fn lock(&self, db: &Db) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.lock(db).await?;
+        Ok(inner.into())
+    }
+
+    /// Allow changes to this checklist again. Returns the updated checklist.
+    pub async fn unlock(&self, db: &Db) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.unlock(db).await?;
+        Ok(inner.into())
+    }
+
+    /// Whether to archive this checklist once all its items are checked. Returns the updated
+    /// checklist.
+    pub async fn set_auto_archive(&self, db: &Db, auto_archive: bool) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.set_auto_archive(db, auto_archive).await?;
+        Ok(inner.into())
+    }
+
+    /// Mark this checklist as a template, or not. Returns the updated checklist.
+    pub async fn set_template(&self, db: &Db, is_template: bool) -> Result<Checklist> {
+        let mut inner = self.inner.clone();
+        inner.set_template(db, is_template).await?;
+        Ok(inner.into())
+    }
+
+    /// Names of the `{{placeholders}}` in this checklist, in alphabetical order.
+    pub async fn placeholders(&self, db: &Db) -> Result<Vec<String>> {
+        self.inner.placeholders(db).await.map_err(Into::into)
+    }
+
+    /// Make this checklist reset on a schedule, or stop it recurring when `rule` is nothing.
+    ///
+    /// `rule` is "daily", "weekly", "every <n><m|h|d|w>" or "cron <expression>". The first period
+    /// starts at `starting_millis` since the Unix epoch, or now. Returns the updated checklist.
+    pub async fn set_recurrence(
+        &self,
+        db: &Db,
+        rule: Option<String>,
+        starting_millis: Option<i64>,
+    ) -> Result<Checklist> {
+        let recurrence = rule.map(|rule| rule.parse()).transpose()?;
+        let starting = starting_millis.map_or_else(Utc::now, from_millis);
+        let mut inner = self.inner.clone();
+        inner.set_recurrence(db, recurrence, starting).await?;
+        Ok(inner.into())
+    }
+
+    /// How far along this checklist is.
+    pub async fn progress(&self, db: &Db) -> Result<Progress> {
+        self.inner
+            .progress(db)
+            .await
+            .map(Into::into)
+            .map_err(Into::into)
+    }
+
+    /// Archived runs of this checklist, most recent first.
+    pub async fn history(&self, db: &Db) -> Result<Vec<Marc<Run>>> {
+        self.inner
+            .history(db)
+            .await
+            .map(|runs| runs.into_iter().map(Run::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Tag this checklist.
+    pub async fn add_tag(&self, db: &Db, tag: &str) -> Result<()> {
+        Tag::new(tag)?
+            .add(db, Taggable::Checklist(self.inner.id.clone()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Remove a tag from this checklist.
+    pub async fn remove_tag(&self, db: &Db, tag: &str) -> Result<()> {
+        Tag::new(tag)?
+            .remove(db, Taggable::Checklist(self.inner.id.clone()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Give the user `user_id` `access` to this checklist, replacing any access they had before.
+    ///
+    /// Only the owner may share a checklist.
+    pub async fn share(&self, db: &Db, user_id: UserId, access: Access) -> Result<()> {
+        let user_id = user_id.parse()?;
+        checklist::Checklist::share(db, self.inner.id.clone(), user_id, access.into())
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Take away any access the user `user_id` was granted to this checklist.
+    pub async fn unshare(&self, db: &Db, user_id: UserId) -> Result<()> {
+        let user_id = user_id.parse()?;
+        checklist::Checklist::unshare(db, self.inner.id.clone(), user_id)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Everyone other than the owner who has access to this checklist.
+    pub async fn grants(&self, db: &Db) -> Result<Vec<Marc<Grant>>> {
+        checklist::Checklist::grants(db, self.inner.id.clone())
+            .await
+            .map(|grants| grants.into_iter().map(Grant::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Tags on this checklist, in alphabetical order.
+    pub async fn tags(&self, db: &Db) -> Result<Vec<String>> {
+        Tag::of(db, Taggable::Checklist(self.inner.id.clone()))
+            .await
+            .map(|tags| tags.into_iter().map(|tag| tag.to_string()).collect())
+            .map_err(Into::into)
+    }
+
     pub fn id(&self) -> ChecklistId {
         self.inner.id.to_string()
     }
@@ -119,4 +439,41 @@ impl Checklist {
     pub fn name(&self) -> String {
         self.inner.name.clone().into_owned()
     }
+
+    /// Id of the user this checklist belongs to, if any.
+    pub fn owner_id(&self) -> Option<UserId> {
+        self.inner.owner.as_ref().map(ToString::to_string)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.inner.revision
+    }
+
+    pub fn is_template(&self) -> bool {
+        self.inner.is_template
+    }
+
+    pub fn archived(&self) -> bool {
+        self.inner.archived
+    }
+
+    pub fn locked(&self) -> bool {
+        self.inner.locked
+    }
+
+    pub fn auto_archive(&self) -> bool {
+        self.inner.auto_archive
+    }
+
+    /// How often this checklist resets, if it does.
+    pub fn recurrence(&self) -> Option<String> {
+        self.inner.recurrence.as_ref().map(ToString::to_string)
+    }
+
+    /// When this checklist next resets, in milliseconds since the Unix epoch.
+    pub fn next_rollover(&self) -> Option<i64> {
+        self.inner
+            .next_rollover()
+            .map(|next| next.timestamp_millis())
+    }
 }
@@ -0,0 +1,37 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use chrono::{DateTime, Utc};
+
+use crate::{Db, Item, Result, marc::Marc};
+
+/// Times cross the FFI boundary as milliseconds since the Unix epoch.
+pub(crate) fn from_millis(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or(if millis < 0 {
+        DateTime::<Utc>::MIN_UTC
+    } else {
+        DateTime::<Utc>::MAX_UTC
+    })
+}
+
+/// Unchecked items across all checklists which are past due, most overdue first.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn items_overdue(db: &Db) -> Result<Vec<Marc<Item>>> {
+    checklist::Item::overdue(db, Utc::now())
+        .await
+        .map(|items| items.into_iter().map(Item::marc).collect())
+        .map_err(Into::into)
+}
+
+/// Unchecked items across all checklists which fall due within `within_millis` from now, soonest first.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn items_upcoming(db: &Db, within_millis: i64) -> Result<Vec<Marc<Item>>> {
+    let now = Utc::now();
+    let until = from_millis(now.timestamp_millis().saturating_add(within_millis));
+    checklist::Item::upcoming(db, now, until)
+        .await
+        .map(|items| items.into_iter().map(Item::marc).collect())
+        .map_err(Into::into)
+}
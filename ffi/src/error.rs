@@ -4,8 +4,21 @@
 #[cfg(not(feature = "wasm"))]
 // errors in uniffi must be enums
 pub enum Error {
+    /// The record changed since it was loaded; reload it and try again.
     #[error(transparent)]
-    Inner(#[from] checklist::Error),
+    Conflict(checklist::Error),
+    #[error(transparent)]
+    Inner(checklist::Error),
+}
+
+#[cfg(not(feature = "wasm"))]
+impl From<checklist::Error> for Error {
+    fn from(err: checklist::Error) -> Self {
+        match err {
+            checklist::Error::Conflict { .. } => Self::Conflict(err),
+            err => Self::Inner(err),
+        }
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -17,4 +30,13 @@ use wasm_bindgen::prelude::wasm_bindgen;
 #[cfg(feature = "wasm")]
 pub struct Error(#[from] checklist::Error);
 
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+impl Error {
+    /// Whether the record changed since it was loaded, so that it should be reloaded and the change retried.
+    pub fn is_conflict(&self) -> bool {
+        matches!(self.0, checklist::Error::Conflict { .. })
+    }
+}
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1,13 +1,17 @@
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+use checklist::{Tag, Taggable};
+
 use crate::{
-    ChecklistId, Db, Result,
+    Attachment, ChecklistId, Db, Note, Priority, Result,
+    due::from_millis,
     marc::{Marc, marc},
 };
 
 pub type ItemId = String;
 
+#[derive(Clone)]
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Item {
@@ -40,6 +44,20 @@ pub async fn item_new(db: &Db, checklist_id: ChecklistId, item: &str) -> Result<
     new_impl(db, checklist_id, item).await
 }
 
+async fn new_child_impl(db: &Db, parent_id: ItemId, item: &str) -> Result<Item> {
+    let parent_id = parent_id.parse()?;
+    checklist::Item::new_child(db, parent_id, item.to_owned())
+        .await
+        .map(Into::into)
+        .map_err(Into::into)
+}
+
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn item_new_child(db: &Db, parent_id: ItemId, item: &str) -> Result<Item> {
+    new_child_impl(db, parent_id, item).await
+}
+
 async fn load_impl(db: &Db, item_id: ItemId) -> Result<Option<Marc<Item>>> {
     let item_id = item_id.parse()?;
     checklist::Item::load(db, item_id)
@@ -67,6 +85,46 @@ pub async fn item_delete(db: &Db, item_id: ItemId) -> Result<()> {
     delete_impl(db, item_id).await
 }
 
+async fn move_many_to_checklist_impl(
+    db: &Db,
+    item_ids: Vec<ItemId>,
+    checklist_id: ChecklistId,
+) -> Result<Vec<Marc<Item>>> {
+    let item_ids = item_ids
+        .iter()
+        .map(|id| id.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+    let checklist_id = checklist_id.parse()?;
+    checklist::Item::move_many_to_checklist(db, item_ids, checklist_id)
+        .await
+        .map(|items| items.into_iter().map(Item::marc).collect())
+        .map_err(Into::into)
+}
+
+/// Move items, with everything nested under them, to the end of another checklist, all at once.
+///
+/// Returns every moved item, including nested ones, in their new order.
+#[cfg(feature = "uniffi")]
+#[uniffi::export]
+pub async fn item_move_many_to_checklist(
+    db: &Db,
+    item_ids: Vec<ItemId>,
+    checklist_id: ChecklistId,
+) -> Result<Vec<Marc<Item>>> {
+    move_many_to_checklist_impl(db, item_ids, checklist_id).await
+}
+
+/// Unchecked items across all checklists which are assigned to the user `user_id`, oldest first.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn items_assigned_to(db: &Db, user_id: String) -> Result<Vec<Marc<Item>>> {
+    let user_id = user_id.parse()?;
+    checklist::Item::assigned_to(db, user_id)
+        .await
+        .map(|items| items.into_iter().map(Item::marc).collect())
+        .map_err(Into::into)
+}
+
 // associated functions cannot be exported via uniffi
 #[cfg(not(feature = "uniffi"))]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
@@ -76,6 +134,11 @@ impl Item {
         new_impl(db, checklist_id, item).await
     }
 
+    /// Create a new item nested under the item `parent_id`.
+    pub async fn new_child(db: &Db, parent_id: ItemId, item: &str) -> Result<Self> {
+        new_child_impl(db, parent_id, item).await
+    }
+
     pub async fn load(db: &Db, item_id: ItemId) -> Result<Option<Marc<Item>>> {
         load_impl(db, item_id).await
     }
@@ -83,6 +146,17 @@ impl Item {
     pub async fn delete(db: &Db, item_id: ItemId) -> Result<()> {
         delete_impl(db, item_id).await
     }
+
+    /// Move items, with everything nested under them, to the end of another checklist, all at once.
+    ///
+    /// Returns every moved item, including nested ones, in their new order.
+    pub async fn move_many_to_checklist(
+        db: &Db,
+        item_ids: Vec<ItemId>,
+        checklist_id: ChecklistId,
+    ) -> Result<Vec<Marc<Item>>> {
+        move_many_to_checklist_impl(db, item_ids, checklist_id).await
+    }
 }
 
 #[cfg_attr(feature = "uniffi", uniffi::export)]
@@ -92,10 +166,155 @@ impl Item {
         self.inner.is_set(db).await.map_err(Into::into)
     }
 
-    pub async fn set_checked(&self, db: &Db, checked: bool) -> Result<()> {
+    /// Items nested directly under this one.
+    pub async fn children(&self, db: &Db) -> Result<Vec<Marc<Item>>> {
         self.inner
-            .set_checked(db, checked)
+            .children(db)
             .await
+            .map(|items| items.into_iter().map(Item::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Whether this item is complete: checked if it has no children, or all children complete if it has.
+    pub async fn is_complete(&self, db: &Db) -> Result<bool> {
+        self.inner.is_complete(db).await.map_err(Into::into)
+    }
+
+    /// Change the text of this item, failing with a conflict if it has changed since it was loaded.
+    ///
+    /// Returns the updated item.
+    pub async fn set_text(&self, db: &Db, text: String) -> Result<Item> {
+        let mut inner = self.inner.clone();
+        inner.set_text(db, text).await?;
+        Ok(inner.into())
+    }
+
+    /// Check or uncheck this item, failing with a conflict if it has changed since it was loaded.
+    ///
+    /// Returns the updated item.
+    pub async fn set_checked(&self, db: &Db, checked: bool) -> Result<Item> {
+        let mut inner = self.inner.clone();
+        inner.set_checked(db, checked).await?;
+        Ok(inner.into())
+    }
+
+    /// Move this item and everything nested under it under `parent_id`, or to the top level.
+    ///
+    /// Returns the moved item.
+    pub async fn move_under(&self, db: &Db, parent_id: Option<ItemId>) -> Result<Item> {
+        let parent_id = parent_id.map(|id| id.parse()).transpose()?;
+        let mut inner = self.inner.clone();
+        inner.move_under(db, parent_id).await?;
+        Ok(inner.into())
+    }
+
+    /// Move this item and everything nested under it to the end of another checklist.
+    ///
+    /// Returns the moved item.
+    pub async fn move_to_checklist(&self, db: &Db, checklist_id: ChecklistId) -> Result<Item> {
+        let checklist_id = checklist_id.parse()?;
+        let mut inner = self.inner.clone();
+        inner.move_to_checklist(db, checklist_id).await?;
+        Ok(inner.into())
+    }
+
+    /// Set or clear when this item is due, in milliseconds since the Unix epoch.
+    ///
+    /// Returns the updated item.
+    pub async fn set_due(&self, db: &Db, due_at_millis: Option<i64>) -> Result<Item> {
+        let mut inner = self.inner.clone();
+        inner.set_due(db, due_at_millis.map(from_millis)).await?;
+        Ok(inner.into())
+    }
+
+    /// Set or clear when to be reminded of this item, in milliseconds since the Unix epoch.
+    ///
+    /// Returns the updated item.
+    pub async fn set_reminder(&self, db: &Db, remind_at_millis: Option<i64>) -> Result<Item> {
+        let mut inner = self.inner.clone();
+        inner
+            .set_reminder(db, remind_at_millis.map(from_millis))
+            .await?;
+        Ok(inner.into())
+    }
+
+    /// Assign this item to the user `assignee_id`, or unassign it.
+    ///
+    /// Returns the updated item.
+    pub async fn assign(&self, db: &Db, assignee_id: Option<String>) -> Result<Item> {
+        let assignee_id = assignee_id.map(|id| id.parse()).transpose()?;
+        let mut inner = self.inner.clone();
+        inner.assign(db, assignee_id).await?;
+        Ok(inner.into())
+    }
+
+    /// Add a note to this item, such as why it was skipped.
+    pub async fn add_note(&self, db: &Db, text: String) -> Result<Marc<Note>> {
+        checklist::Note::add(db, self.inner.id.clone(), text)
+            .await
+            .map(Note::marc)
+            .map_err(Into::into)
+    }
+
+    /// Notes on this item, oldest first.
+    pub async fn notes(&self, db: &Db) -> Result<Vec<Marc<Note>>> {
+        checklist::Note::of(db, self.inner.id.clone())
+            .await
+            .map(|notes| notes.into_iter().map(Note::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Change how urgent this item is.
+    ///
+    /// Returns the updated item.
+    pub async fn set_priority(&self, db: &Db, priority: Priority) -> Result<Item> {
+        let mut inner = self.inner.clone();
+        inner.set_priority(db, priority.into()).await?;
+        Ok(inner.into())
+    }
+
+    /// Attach a small file called `name` to this item.
+    pub async fn attach(
+        &self,
+        db: &Db,
+        name: String,
+        contents: Vec<u8>,
+    ) -> Result<Marc<Attachment>> {
+        checklist::Attachment::add(db, self.inner.id.clone(), name, &contents)
+            .await
+            .map(Attachment::marc)
+            .map_err(Into::into)
+    }
+
+    /// Files attached to this item, oldest first.
+    pub async fn attachments(&self, db: &Db) -> Result<Vec<Marc<Attachment>>> {
+        checklist::Attachment::of(db, self.inner.id.clone())
+            .await
+            .map(|attachments| attachments.into_iter().map(Attachment::marc).collect())
+            .map_err(Into::into)
+    }
+
+    /// Tag this item.
+    pub async fn add_tag(&self, db: &Db, tag: &str) -> Result<()> {
+        Tag::new(tag)?
+            .add(db, Taggable::Item(self.inner.id.clone()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Remove a tag from this item.
+    pub async fn remove_tag(&self, db: &Db, tag: &str) -> Result<()> {
+        Tag::new(tag)?
+            .remove(db, Taggable::Item(self.inner.id.clone()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Tags on this item, in alphabetical order.
+    pub async fn tags(&self, db: &Db) -> Result<Vec<String>> {
+        Tag::of(db, Taggable::Item(self.inner.id.clone()))
+            .await
+            .map(|tags| tags.into_iter().map(|tag| tag.to_string()).collect())
             .map_err(Into::into)
     }
 
@@ -107,7 +326,43 @@ impl Item {
         self.inner.checklist.to_string()
     }
 
+    pub fn revision(&self) -> u64 {
+        self.inner.revision
+    }
+
+    pub fn parent_id(&self) -> Option<ItemId> {
+        self.inner.parent.as_ref().map(ToString::to_string)
+    }
+
     pub fn item(&self) -> String {
         self.inner.item.clone().into_owned()
     }
+
+    /// When this item is due, in milliseconds since the Unix epoch.
+    pub fn due_at(&self) -> Option<i64> {
+        self.inner.due_at.map(|due_at| due_at.timestamp_millis())
+    }
+
+    /// When to be reminded of this item, in milliseconds since the Unix epoch.
+    pub fn remind_at(&self) -> Option<i64> {
+        self.inner
+            .remind_at
+            .map(|remind_at| remind_at.timestamp_millis())
+    }
+
+    /// When this item was checked, in milliseconds since the Unix epoch, if it is and that is known.
+    pub fn checked_at(&self) -> Option<i64> {
+        self.inner
+            .checked_at
+            .map(|checked_at| checked_at.timestamp_millis())
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.inner.priority.into()
+    }
+
+    /// Id of the user this item is assigned to, if any.
+    pub fn assignee_id(&self) -> Option<String> {
+        self.inner.assignee.as_ref().map(ToString::to_string)
+    }
 }
@@ -3,30 +3,55 @@ compile_error!(
     "can't build this crate for uniffi and wasm simultaneously; their Error types are incompatible"
 );
 
+mod attachment;
 mod checklist;
+mod due;
 mod error;
 mod item;
 pub(crate) mod marc;
+mod note;
+mod page;
+mod priority;
+mod recurrence;
+mod stats;
+mod tag;
+mod user;
 
 use ::checklist as libchecklist;
 use std::ops::Deref;
 
+pub use attachment::{Attachment, AttachmentId, attachment_remove};
 pub use checklist::{Checklist, ChecklistId};
+pub use due::{items_overdue, items_upcoming};
 pub use error::{Error, Result};
-pub use item::{Item, ItemId};
+pub use item::{Item, ItemId, items_assigned_to};
+pub use note::{Note, NoteId, note_delete};
+pub use page::{ChecklistPage, ItemPage};
+pub use priority::{ItemOrder, Priority};
+pub use recurrence::{Run, checklists_roll_over};
+pub use stats::{Progress, Summary, stats_checks_per_period, stats_summary};
+pub use tag::{tagged_items, tags_all};
+pub use user::{Access, Grant, User, UserId, user_by_name, user_new, users_all};
 
 #[cfg(feature = "uniffi")]
 uniffi::setup_scaffolding!("checklist_ffi");
 
 #[cfg(feature = "uniffi")]
-pub use checklist::{checklist_all, checklist_delete, checklist_load, checklist_new};
+pub use checklist::{
+    checklist_all, checklist_delete, checklist_duplicate, checklist_instantiate, checklist_load,
+    checklist_merge_into, checklist_new, checklist_page,
+};
 
 #[cfg(feature = "uniffi")]
-pub use item::{item_delete, item_load, item_new};
+pub use item::{item_delete, item_load, item_move_many_to_checklist, item_new, item_new_child};
 
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+/// A handle to a database, through which every call acts.
+///
+/// A newly opened handle acts as nobody in particular, and so may only use checklists which belong
+/// to nobody; see [`Db::as_user`] and [`Db::with_full_access`] for handles which may do more.
 #[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Db {
@@ -49,6 +74,24 @@ impl Db {
     pub async fn new(path: &str, encryption_key: Vec<u8>) -> Result<Db> {
         db_new(path, encryption_key).await
     }
+
+    /// A handle to the same database which acts as the user `user_id`.
+    ///
+    /// Checklists created through it belong to that user, and it may only use checklists which
+    /// belong to nobody, to the user, or were shared with them.
+    pub fn as_user(&self, user_id: UserId) -> Result<Db> {
+        let user_id = user_id.parse()?;
+        Ok(Db {
+            inner: self.inner.as_user(user_id),
+        })
+    }
+
+    /// A handle to the same database which may do anything, whoever owns what.
+    pub fn with_full_access(&self) -> Db {
+        Db {
+            inner: self.inner.with_full_access(),
+        }
+    }
 }
 
 impl Deref for Db {
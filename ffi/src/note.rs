@@ -0,0 +1,58 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Db, ItemId, Result,
+    marc::{Marc, marc},
+};
+
+pub type NoteId = String;
+
+/// A comment on an item.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Note {
+    inner: checklist::Note,
+}
+
+impl Note {
+    pub(crate) fn marc(inner: checklist::Note) -> Marc<Self> {
+        marc(Self { inner })
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Note {
+    pub fn id(&self) -> NoteId {
+        self.inner.id.to_string()
+    }
+
+    pub fn item_id(&self) -> ItemId {
+        self.inner.item.to_string()
+    }
+
+    /// Id of the user who wrote this note, if any.
+    pub fn author_id(&self) -> Option<String> {
+        self.inner.author.as_ref().map(ToString::to_string)
+    }
+
+    pub fn text(&self) -> String {
+        self.inner.text.clone()
+    }
+
+    /// When this note was written, in milliseconds since the Unix epoch.
+    pub fn created_at(&self) -> i64 {
+        self.inner.created_at.timestamp_millis()
+    }
+}
+
+/// Delete the note `note_id`.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn note_delete(db: &Db, note_id: NoteId) -> Result<()> {
+    let note_id = note_id.parse()?;
+    checklist::Note::delete(db, note_id)
+        .await
+        .map_err(Into::into)
+}
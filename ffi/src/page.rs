@@ -0,0 +1,64 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::{Checklist, Item, marc::Marc};
+
+/// A page of checklists.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct ChecklistPage {
+    checklists: Vec<Marc<Checklist>>,
+    next: Option<String>,
+}
+
+impl From<checklist::Page<checklist::Checklist>> for ChecklistPage {
+    fn from(page: checklist::Page<checklist::Checklist>) -> Self {
+        Self {
+            checklists: page.items.into_iter().map(Checklist::marc).collect(),
+            next: page.next.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ChecklistPage {
+    pub fn checklists(&self) -> Vec<Marc<Checklist>> {
+        self.checklists.clone()
+    }
+
+    /// Cursor from which the next page starts, or nothing if this is the last page.
+    pub fn next(&self) -> Option<String> {
+        self.next.clone()
+    }
+}
+
+/// A page of items.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct ItemPage {
+    items: Vec<Marc<Item>>,
+    next: Option<String>,
+}
+
+impl From<checklist::Page<checklist::Item>> for ItemPage {
+    fn from(page: checklist::Page<checklist::Item>) -> Self {
+        Self {
+            items: page.items.into_iter().map(Item::marc).collect(),
+            next: page.next.map(|cursor| cursor.to_string()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl ItemPage {
+    pub fn items(&self) -> Vec<Marc<Item>> {
+        self.items.clone()
+    }
+
+    /// Cursor from which the next page starts, or nothing if this is the last page.
+    pub fn next(&self) -> Option<String> {
+        self.next.clone()
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// How urgent an item is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+    Urgent,
+}
+
+impl From<checklist::Priority> for Priority {
+    fn from(priority: checklist::Priority) -> Self {
+        match priority {
+            checklist::Priority::Low => Self::Low,
+            checklist::Priority::Normal => Self::Normal,
+            checklist::Priority::High => Self::High,
+            checklist::Priority::Urgent => Self::Urgent,
+        }
+    }
+}
+
+impl From<Priority> for checklist::Priority {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::Low => Self::Low,
+            Priority::Normal => Self::Normal,
+            Priority::High => Self::High,
+            Priority::Urgent => Self::Urgent,
+        }
+    }
+}
+
+/// Order in which to list a checklist's items; ties are broken by manual order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum ItemOrder {
+    Manual,
+    /// Most urgent first.
+    Priority,
+    /// Soonest due first; items without a due date come last.
+    Due,
+    /// Items which are not yet checked before those which are.
+    UncheckedFirst,
+}
+
+impl From<ItemOrder> for checklist::ItemOrder {
+    fn from(order: ItemOrder) -> Self {
+        match order {
+            ItemOrder::Manual => Self::Manual,
+            ItemOrder::Priority => Self::Priority,
+            ItemOrder::Due => Self::Due,
+            ItemOrder::UncheckedFirst => Self::UncheckedFirst,
+        }
+    }
+}
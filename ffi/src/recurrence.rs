@@ -0,0 +1,74 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use chrono::Utc;
+
+use crate::{
+    Checklist, Db, Result,
+    marc::{Marc, marc},
+};
+
+/// One archived period of a recurring checklist.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Run {
+    inner: checklist::Run,
+}
+
+impl Run {
+    pub(crate) fn marc(inner: checklist::Run) -> Marc<Self> {
+        marc(Self { inner })
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Run {
+    /// When this run started, in milliseconds since the Unix epoch.
+    pub fn started_at(&self) -> i64 {
+        self.inner.started_at.timestamp_millis()
+    }
+
+    /// When this run ended, in milliseconds since the Unix epoch.
+    pub fn ended_at(&self) -> i64 {
+        self.inner.ended_at.timestamp_millis()
+    }
+
+    /// Item text, in checklist order.
+    pub fn items(&self) -> Vec<String> {
+        self.inner
+            .items
+            .iter()
+            .map(|item| item.item.clone())
+            .collect()
+    }
+
+    /// For each item in `items`, 1 if it was checked when the run ended and 0 otherwise.
+    ///
+    /// Bytes rather than bools because wasm-bindgen cannot return `Vec<bool>`.
+    pub fn checked(&self) -> Vec<u8> {
+        self.inner
+            .items
+            .iter()
+            .map(|item| u8::from(item.checked))
+            .collect()
+    }
+
+    pub fn completed(&self) -> u32 {
+        self.inner.completed() as _
+    }
+
+    pub fn total(&self) -> u32 {
+        self.inner.total() as _
+    }
+}
+
+/// Reset every recurring checklist whose period has ended, returning those which were reset.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn checklists_roll_over(db: &Db) -> Result<Vec<Marc<Checklist>>> {
+    checklist::Checklist::roll_over_due(db, Utc::now())
+        .await
+        .map(|checklists| checklists.into_iter().map(Checklist::marc).collect())
+        .map_err(Into::into)
+}
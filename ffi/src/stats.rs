@@ -0,0 +1,148 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use chrono::Duration;
+
+use crate::{
+    Checklist, Db, Result,
+    due::from_millis,
+    marc::{Marc, marc},
+};
+
+/// How far along one checklist is, counting leaf items.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Progress {
+    inner: checklist::Progress,
+}
+
+impl From<checklist::Progress> for Progress {
+    fn from(inner: checklist::Progress) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Progress {
+    /// Leaf items, those with nothing nested under them, which are checked.
+    pub fn checked(&self) -> u32 {
+        self.inner.checked as _
+    }
+
+    /// All leaf items.
+    pub fn total(&self) -> u32 {
+        self.inner.total as _
+    }
+
+    /// Percentage of leaf items checked; 0 for a checklist without items.
+    pub fn percent(&self) -> f64 {
+        self.inner.percent()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+
+    /// When the current period began, or else when the checklist was created, in milliseconds
+    /// since the Unix epoch.
+    pub fn started_at(&self) -> Option<i64> {
+        self.inner.started_at.map(|time| time.timestamp_millis())
+    }
+
+    /// When the last item was checked, if all are, in milliseconds since the Unix epoch.
+    pub fn completed_at(&self) -> Option<i64> {
+        self.inner.completed_at.map(|time| time.timestamp_millis())
+    }
+
+    /// How long the checklist took to complete, in milliseconds, if known.
+    pub fn time_to_complete_millis(&self) -> Option<i64> {
+        self.inner
+            .time_to_complete()
+            .map(|time| time.num_milliseconds())
+    }
+}
+
+/// Progress of every readable checklist other than templates.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Summary {
+    inner: checklist::Summary,
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Summary {
+    /// Checklists, oldest first.
+    pub fn checklists(&self) -> Vec<Marc<Checklist>> {
+        self.inner
+            .checklists
+            .iter()
+            .map(|(checklist, _)| Checklist::marc(checklist.clone()))
+            .collect()
+    }
+
+    /// Progress of each checklist in `checklists`.
+    pub fn progress(&self) -> Vec<Marc<Progress>> {
+        self.inner
+            .checklists
+            .iter()
+            .map(|(_, progress)| marc((*progress).into()))
+            .collect()
+    }
+
+    /// How many checklists have every item checked.
+    pub fn completed(&self) -> u32 {
+        self.inner.completed() as _
+    }
+
+    pub fn checked(&self) -> u32 {
+        self.inner.checked() as _
+    }
+
+    pub fn total(&self) -> u32 {
+        self.inner.total() as _
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.inner.percent()
+    }
+
+    /// Mean time to complete a checklist, in milliseconds, if known.
+    pub fn mean_time_to_complete_millis(&self) -> Option<i64> {
+        self.inner
+            .mean_time_to_complete()
+            .map(|time| time.num_milliseconds())
+    }
+}
+
+/// Progress of every checklist the acting user can read, archived ones included.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn stats_summary(db: &Db) -> Result<Summary> {
+    checklist::Summary::gather(db)
+        .await
+        .map(|inner| Summary { inner })
+        .map_err(Into::into)
+}
+
+/// How many items were checked in each of `periods` consecutive periods of `period_millis`, the
+/// first starting at `since_millis` since the Unix epoch.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn stats_checks_per_period(
+    db: &Db,
+    since_millis: i64,
+    period_millis: i64,
+    periods: u32,
+) -> Result<Vec<u32>> {
+    checklist::checks_per_period(
+        db,
+        from_millis(since_millis),
+        Duration::milliseconds(period_millis),
+        periods as _,
+    )
+    .await
+    .map(|counts| counts.into_iter().map(|count| count as _).collect())
+    .map_err(Into::into)
+}
@@ -0,0 +1,27 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use checklist::Tag;
+
+use crate::{Db, Item, Result, marc::Marc};
+
+/// All tags in use, in alphabetical order.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn tags_all(db: &Db) -> Result<Vec<String>> {
+    Tag::all(db)
+        .await
+        .map(|tags| tags.into_iter().map(|tag| tag.to_string()).collect())
+        .map_err(Into::into)
+}
+
+/// All items with this tag, across all checklists.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn tagged_items(db: &Db, tag: &str) -> Result<Vec<Marc<Item>>> {
+    Tag::new(tag)?
+        .items(db)
+        .await
+        .map(|items| items.into_iter().map(Item::marc).collect())
+        .map_err(Into::into)
+}
@@ -0,0 +1,125 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    Db, Result,
+    marc::{Marc, marc},
+};
+
+pub type UserId = String;
+
+/// Someone who owns checklists and can be granted access to others'.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct User {
+    inner: checklist::User,
+}
+
+impl User {
+    pub(crate) fn marc(inner: checklist::User) -> Marc<Self> {
+        marc(Self { inner })
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl User {
+    pub fn id(&self) -> UserId {
+        self.inner.id.to_string()
+    }
+
+    /// Unique within the database.
+    pub fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+}
+
+/// What a user other than the owner may do with a shared checklist.
+///
+/// Each level includes those before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Enum))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub enum Access {
+    /// See the checklist and its items.
+    Read,
+    /// Also check and uncheck items.
+    Check,
+    /// Also change the checklist and add, change, and delete items.
+    Edit,
+}
+
+impl From<checklist::Access> for Access {
+    fn from(access: checklist::Access) -> Self {
+        match access {
+            checklist::Access::Read => Self::Read,
+            checklist::Access::Check => Self::Check,
+            checklist::Access::Edit => Self::Edit,
+        }
+    }
+}
+
+impl From<Access> for checklist::Access {
+    fn from(access: Access) -> Self {
+        match access {
+            Access::Read => Self::Read,
+            Access::Check => Self::Check,
+            Access::Edit => Self::Edit,
+        }
+    }
+}
+
+/// Access to a checklist granted to someone other than its owner.
+#[cfg_attr(feature = "uniffi", derive(uniffi::Object))]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct Grant {
+    inner: checklist::Grant,
+}
+
+impl Grant {
+    pub(crate) fn marc(inner: checklist::Grant) -> Marc<Self> {
+        marc(Self { inner })
+    }
+}
+
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Grant {
+    pub fn user_id(&self) -> UserId {
+        self.inner.user.to_string()
+    }
+
+    pub fn access(&self) -> Access {
+        self.inner.access.into()
+    }
+}
+
+/// Create a user; fails if one with this name already exists.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn user_new(db: &Db, name: String) -> Result<Marc<User>> {
+    checklist::User::new(db, name)
+        .await
+        .map(User::marc)
+        .map_err(Into::into)
+}
+
+/// The user named `name`, if there is one.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn user_by_name(db: &Db, name: String) -> Result<Option<Marc<User>>> {
+    checklist::User::by_name(db, &name)
+        .await
+        .map(|user| user.map(User::marc))
+        .map_err(Into::into)
+}
+
+/// All users, in alphabetical order.
+#[cfg_attr(feature = "uniffi", uniffi::export)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub async fn users_all(db: &Db) -> Result<Vec<Marc<User>>> {
+    checklist::User::all(db)
+        .await
+        .map(|users| users.into_iter().map(User::marc).collect())
+        .map_err(Into::into)
+}
@@ -0,0 +1,449 @@
+//! Routes, handlers, and the JSON types they exchange.
+//!
+//! Each request acts as the user named by its [`USER`] header, or as nobody in particular without
+//! one, in which case only checklists which belong to nobody can be used. Anyone could send the
+//! header, so it is only accepted along with the key of the trusted proxy which authenticates
+//! users, in the [`PROXY_KEY`] header.
+//!
+//! Each request and response type derives its JSON schema, from which [`openapi::document`]
+//! assembles the API description; doc comments on the types and their fields become descriptions.
+
+use std::{convert::Infallible, sync::Arc};
+
+use bytes::Bytes;
+use checklist::{Checklist, ChecklistId, ChecklistQuery, Db, Item, ItemId, ItemNode, User};
+use chrono::{DateTime, Utc};
+use http_body_util::{BodyExt as _, Empty, Full, combinators::BoxBody};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::Incoming,
+    header::{self, HeaderMap},
+};
+use schemars::JsonSchema;
+
+use crate::{events, openapi};
+
+pub(crate) type Body = BoxBody<Bytes, Infallible>;
+
+/// Header naming the user a request acts as.
+pub(crate) const USER: &str = "checklist-user";
+
+/// Header by which the trusted proxy proves that it named the user.
+pub(crate) const PROXY_KEY: &str = "checklist-proxy-key";
+
+/// A checklist and how far through it its items are.
+#[derive(Debug, serde::Serialize, JsonSchema)]
+#[schemars(rename = "Checklist")]
+pub(crate) struct ChecklistView {
+    id: String,
+    name: String,
+    created_at: Option<DateTime<Utc>>,
+    is_template: bool,
+    /// How often the checklist resets, such as "daily"
+    recurrence: Option<String>,
+    /// Left out of listings unless archived checklists are asked for
+    archived: bool,
+    /// Neither the checklist nor its items may be changed until it is unlocked
+    locked: bool,
+    /// Incremented on every change; send it back to detect conflicting edits
+    revision: u64,
+    /// Leaf items which are checked
+    done: usize,
+    /// All leaf items
+    total: usize,
+}
+
+impl ChecklistView {
+    pub(crate) async fn load(db: &Db, checklist: Checklist) -> Result<Self, ApiError> {
+        let (done, total) = checklist
+            .tree(db)
+            .await?
+            .iter()
+            .map(ItemNode::progress)
+            .fold((0, 0), |(done, total), (d, t)| (done + d, total + t));
+        Ok(Self {
+            id: checklist.id.to_string(),
+            name: checklist.name.into_owned(),
+            created_at: checklist.created_at,
+            is_template: checklist.is_template,
+            recurrence: checklist
+                .recurrence
+                .map(|recurrence| recurrence.to_string()),
+            archived: checklist.archived,
+            locked: checklist.locked,
+            revision: checklist.revision,
+            done,
+            total,
+        })
+    }
+}
+
+/// One page of checklists.
+#[derive(Debug, serde::Serialize, JsonSchema)]
+pub(crate) struct ChecklistPage {
+    checklists: Vec<ChecklistView>,
+    /// Pass as `after` to fetch the next page; null on the last page
+    next: Option<String>,
+}
+
+/// An item of a checklist.
+#[derive(Debug, serde::Serialize, JsonSchema)]
+#[schemars(rename = "Item")]
+pub(crate) struct ItemView {
+    id: String,
+    checklist: String,
+    /// The item this one is nested under
+    parent: Option<String>,
+    text: String,
+    checked: bool,
+    position: i64,
+    created_at: Option<DateTime<Utc>>,
+    due_at: Option<DateTime<Utc>>,
+    remind_at: Option<DateTime<Utc>>,
+    /// Incremented on every change; send it back to detect conflicting edits
+    revision: u64,
+}
+
+impl ItemView {
+    fn new(item: Item, checked: bool) -> Self {
+        Self {
+            id: item.id.to_string(),
+            checklist: item.checklist.to_string(),
+            parent: item.parent.map(|parent| parent.to_string()),
+            text: item.item.into_owned(),
+            checked,
+            position: item.position,
+            created_at: item.created_at,
+            due_at: item.due_at,
+            remind_at: item.remind_at,
+            revision: item.revision,
+        }
+    }
+
+    pub(crate) async fn load(db: &Db, item: Item) -> Result<Self, ApiError> {
+        let checked = item.is_set(db).await?;
+        Ok(Self::new(item, checked))
+    }
+}
+
+#[derive(Debug, serde::Deserialize, JsonSchema)]
+pub(crate) struct NewChecklist {
+    name: String,
+}
+
+#[derive(Debug, serde::Deserialize, JsonSchema)]
+pub(crate) struct RenameChecklist {
+    name: String,
+    /// Only apply if the checklist is still at this revision
+    revision: Option<u64>,
+}
+
+#[derive(Debug, serde::Deserialize, JsonSchema)]
+pub(crate) struct NewItem {
+    text: String,
+    /// Nest the new item under this one, which must be in the same checklist
+    parent: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, JsonSchema)]
+pub(crate) struct UpdateItem {
+    text: String,
+    /// Only apply if the item is still at this revision
+    revision: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize, JsonSchema)]
+#[schemars(rename = "Error")]
+pub(crate) struct ErrorView {
+    error: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    BadRequest(String),
+    NotFound(&'static str),
+    /// The request named a user who does not exist.
+    UnknownUser(String),
+    UntrustedUser,
+    Checklist(checklist::Error),
+}
+
+impl From<checklist::Error> for ApiError {
+    fn from(err: checklist::Error) -> Self {
+        Self::Checklist(err)
+    }
+}
+
+impl ApiError {
+    pub(crate) fn into_response(self) -> Response<Body> {
+        use checklist::Error as E;
+
+        let (status, error) = match self {
+            Self::BadRequest(error) => (StatusCode::BAD_REQUEST, error),
+            Self::NotFound(what) => (StatusCode::NOT_FOUND, format!("no such {what}")),
+            Self::UnknownUser(name) => (StatusCode::FORBIDDEN, format!("no user named {name:?}")),
+            Self::UntrustedUser => (
+                StatusCode::FORBIDDEN,
+                "only the trusted proxy may name the acting user".to_owned(),
+            ),
+            Self::Checklist(err) => {
+                let status = match &err {
+                    E::Conflict { .. } | E::Locked => StatusCode::CONFLICT,
+                    E::Forbidden => StatusCode::FORBIDDEN,
+                    E::MissingItem => StatusCode::NOT_FOUND,
+                    E::InvalidCursor
+                    | E::WrongRecordId { .. }
+                    | E::ParentCycle
+                    | E::ParentInOtherChecklist => StatusCode::BAD_REQUEST,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                if status == StatusCode::INTERNAL_SERVER_ERROR {
+                    eprintln!("error: {err}");
+                }
+                (status, err.to_string())
+            }
+        };
+        json(status, &ErrorView { error })
+    }
+}
+
+/// Route `request` to its handler.
+pub(crate) async fn handle(
+    db: Arc<Db>,
+    proxy_key: Option<Arc<[u8]>>,
+    request: Request<Incoming>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let query = request.uri().query().map(str::to_owned);
+    let query = query.as_deref();
+    let last_event_id = request
+        .headers()
+        .get(events::LAST_EVENT_ID)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_owned);
+    let db = match acting(db, proxy_key.as_deref(), request.headers()).await {
+        Ok(db) => db,
+        Err(err) => return Ok(err.into_response()),
+    };
+    let body = request.into_body();
+
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let response = match (method, segments.as_slice()) {
+        (Method::GET, ["openapi.json"]) => Ok(json(StatusCode::OK, &openapi::document())),
+        (Method::GET, ["checklists"]) => list_checklists(&db, query).await,
+        (Method::POST, ["checklists"]) => create_checklist(&db, body).await,
+        (Method::GET, ["checklists", id]) => get_checklist(&db, id).await,
+        (Method::PATCH, ["checklists", id]) => rename_checklist(&db, id, body).await,
+        (Method::DELETE, ["checklists", id]) => delete_checklist(&db, id).await,
+        (Method::GET, ["checklists", id, "items"]) => list_items(&db, id).await,
+        (Method::POST, ["checklists", id, "items"]) => create_item(&db, id, body).await,
+        (Method::GET, ["items", id]) => get_item(&db, id).await,
+        (Method::PATCH, ["items", id]) => update_item(&db, id, body).await,
+        (Method::DELETE, ["items", id]) => delete_item(&db, id).await,
+        (Method::POST, ["items", id, "check"]) => set_checked(&db, id, true).await,
+        (Method::POST, ["items", id, "uncheck"]) => set_checked(&db, id, false).await,
+        (Method::GET, ["events"]) => events::subscribe(db, query, last_event_id.as_deref()).await,
+        _ => Err(ApiError::NotFound("endpoint")),
+    };
+    Ok(response.unwrap_or_else(ApiError::into_response))
+}
+
+/// The handle to act through for a request with `headers`, given the key of the trusted proxy.
+async fn acting(
+    db: Arc<Db>,
+    proxy_key: Option<&[u8]>,
+    headers: &HeaderMap,
+) -> Result<Arc<Db>, ApiError> {
+    let Some(user) = headers.get(USER) else {
+        return Ok(db);
+    };
+    let trusted = proxy_key
+        .zip(headers.get(PROXY_KEY))
+        .is_some_and(|(key, sent)| {
+            // compare every byte, so that the time taken gives nothing away
+            key.len() == sent.len()
+                && key
+                    .iter()
+                    .zip(sent.as_bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0
+        });
+    if !trusted {
+        return Err(ApiError::UntrustedUser);
+    }
+    let name = String::from_utf8_lossy(user.as_bytes());
+    match User::by_name(&db, &name).await? {
+        Some(user) => Ok(Arc::new(db.as_user(user.id))),
+        None => Err(ApiError::UnknownUser(name.into_owned())),
+    }
+}
+
+fn json(status: StatusCode, body: &impl serde::Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(body).expect("api types serialize to json");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)).boxed())
+        .expect("response is valid")
+}
+
+fn no_content() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Empty::new().boxed())
+        .expect("response is valid")
+}
+
+async fn read_json<T: serde::de::DeserializeOwned>(body: Incoming) -> Result<T, ApiError> {
+    let body = body
+        .collect()
+        .await
+        .map_err(|err| ApiError::BadRequest(format!("reading body: {err}")))?
+        .to_bytes();
+    serde_json::from_slice(&body)
+        .map_err(|err| ApiError::BadRequest(format!("invalid body: {err}")))
+}
+
+/// The value of `key` in a url query string, if present.
+pub(crate) fn query_value<'q>(query: Option<&'q str>, key: &str) -> Option<&'q str> {
+    query?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+}
+
+pub(crate) async fn load_checklist(db: &Db, id: &str) -> Result<Checklist, ApiError> {
+    let id = id.parse::<ChecklistId>()?;
+    Checklist::load(db, id)
+        .await?
+        .ok_or(ApiError::NotFound("checklist"))
+}
+
+async fn load_item(db: &Db, id: &str) -> Result<Item, ApiError> {
+    let id = id.parse::<ItemId>()?;
+    Item::load(db, id).await?.ok_or(ApiError::NotFound("item"))
+}
+
+async fn list_checklists(db: &Db, query: Option<&str>) -> Result<Response<Body>, ApiError> {
+    let mut checklists = ChecklistQuery::new();
+    if let Some(limit) = query_value(query, "limit") {
+        let limit = limit
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("invalid limit {limit:?}")))?;
+        checklists = checklists.limit(limit);
+    }
+    if let Some(after) = query_value(query, "after") {
+        checklists = checklists.after(after.parse()?);
+    }
+    if let Some(template) = query_value(query, "template") {
+        let template = template
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("invalid template flag {template:?}")))?;
+        checklists = checklists.template(template);
+    }
+    if let Some(archived) = query_value(query, "archived") {
+        let archived = archived
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("invalid archived flag {archived:?}")))?;
+        checklists = checklists.archived(archived);
+    }
+
+    let page = checklists.fetch(db).await?;
+    let mut views = Vec::with_capacity(page.items.len());
+    for checklist in page.items {
+        views.push(ChecklistView::load(db, checklist).await?);
+    }
+    let page = ChecklistPage {
+        checklists: views,
+        next: page.next.map(|next| next.to_string()),
+    };
+    Ok(json(StatusCode::OK, &page))
+}
+
+async fn create_checklist(db: &Db, body: Incoming) -> Result<Response<Body>, ApiError> {
+    let NewChecklist { name } = read_json(body).await?;
+    let checklist = Checklist::new(db, name).await?;
+    Ok(json(
+        StatusCode::CREATED,
+        &ChecklistView::load(db, checklist).await?,
+    ))
+}
+
+async fn get_checklist(db: &Db, id: &str) -> Result<Response<Body>, ApiError> {
+    let checklist = load_checklist(db, id).await?;
+    Ok(json(
+        StatusCode::OK,
+        &ChecklistView::load(db, checklist).await?,
+    ))
+}
+
+async fn rename_checklist(db: &Db, id: &str, body: Incoming) -> Result<Response<Body>, ApiError> {
+    let RenameChecklist { name, revision } = read_json(body).await?;
+    let mut checklist = load_checklist(db, id).await?;
+    if let Some(revision) = revision {
+        checklist.revision = revision;
+    }
+    checklist.rename(db, name).await?;
+    Ok(json(
+        StatusCode::OK,
+        &ChecklistView::load(db, checklist).await?,
+    ))
+}
+
+async fn delete_checklist(db: &Db, id: &str) -> Result<Response<Body>, ApiError> {
+    let checklist = load_checklist(db, id).await?;
+    Checklist::delete(db, checklist.id).await?;
+    Ok(no_content())
+}
+
+async fn list_items(db: &Db, id: &str) -> Result<Response<Body>, ApiError> {
+    let checklist = load_checklist(db, id).await?;
+    let mut items = Vec::new();
+    for root in checklist.tree(db).await? {
+        root.walk(&mut |node, _| items.push(ItemView::new(node.item.clone(), node.checked)));
+    }
+    Ok(json(StatusCode::OK, &items))
+}
+
+async fn create_item(db: &Db, id: &str, body: Incoming) -> Result<Response<Body>, ApiError> {
+    let NewItem { text, parent } = read_json(body).await?;
+    let checklist = load_checklist(db, id).await?;
+    let item = match parent {
+        None => Item::new(db, checklist.id, text).await?,
+        Some(parent) => {
+            let parent = load_item(db, &parent).await?;
+            if parent.checklist != checklist.id {
+                return Err(checklist::Error::ParentInOtherChecklist.into());
+            }
+            Item::new_child(db, parent.id, text).await?
+        }
+    };
+    Ok(json(StatusCode::CREATED, &ItemView::new(item, false)))
+}
+
+async fn get_item(db: &Db, id: &str) -> Result<Response<Body>, ApiError> {
+    let item = load_item(db, id).await?;
+    Ok(json(StatusCode::OK, &ItemView::load(db, item).await?))
+}
+
+async fn update_item(db: &Db, id: &str, body: Incoming) -> Result<Response<Body>, ApiError> {
+    let UpdateItem { text, revision } = read_json(body).await?;
+    let mut item = load_item(db, id).await?;
+    if let Some(revision) = revision {
+        item.revision = revision;
+    }
+    item.set_text(db, text).await?;
+    Ok(json(StatusCode::OK, &ItemView::load(db, item).await?))
+}
+
+async fn delete_item(db: &Db, id: &str) -> Result<Response<Body>, ApiError> {
+    let item = load_item(db, id).await?;
+    Item::delete(db, item.id).await?;
+    Ok(no_content())
+}
+
+async fn set_checked(db: &Db, id: &str, checked: bool) -> Result<Response<Body>, ApiError> {
+    let mut item = load_item(db, id).await?;
+    item.set_checked(db, checked).await?;
+    Ok(json(StatusCode::OK, &ItemView::new(item, checked)))
+}
@@ -0,0 +1,305 @@
+//! Server-sent events reporting changes to checklists and items as they happen.
+//!
+//! The id of each event is a cursor into the database's op log, so a client which reconnects with
+//! the `Last-Event-ID` header, as browsers do, misses no change which is synced between devices.
+//! Changes which are not, such as to assignees, are only reported as they happen; see
+//! [`Db::changes`].
+
+use std::{pin::pin, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use checklist::{Change, Changed, ChecklistId, Db, OpCursor};
+use futures::StreamExt as _;
+use http_body_util::{BodyExt, StreamBody};
+use hyper::{Response, StatusCode, body::Frame, header};
+use schemars::JsonSchema;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::api::{ApiError, Body, ChecklistView, ItemView, load_checklist, query_value};
+
+pub(crate) const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Without any changes for this long, send a comment so that a closed connection is noticed.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The data of a `checklist` or `item` event.
+#[derive(Debug, serde::Serialize, JsonSchema)]
+#[schemars(rename = "Change")]
+pub(crate) struct ChangeView {
+    /// Id of the checklist or item which changed
+    id: String,
+    /// The checklists affected; for an item which moved, both old and new
+    checklists: Vec<String>,
+    deleted: bool,
+    /// The checklist as it is now, for a `checklist` event
+    checklist: Option<ChecklistView>,
+    /// The item as it is now, for an `item` event
+    item: Option<ItemView>,
+}
+
+impl ChangeView {
+    /// The event name and data reporting `change`.
+    async fn load(db: &Db, change: Change) -> Result<(&'static str, Self), ApiError> {
+        let checklists = change.checklists.iter().map(ToString::to_string).collect();
+        let (event, id, checklist, item) = match change.record {
+            Changed::Checklist(id, checklist) => {
+                let checklist = match checklist {
+                    Some(checklist) => Some(ChecklistView::load(db, checklist).await?),
+                    None => None,
+                };
+                ("checklist", id.to_string(), checklist, None)
+            }
+            Changed::Item(id, item) => {
+                let item = match item {
+                    Some(item) => Some(ItemView::load(db, item).await?),
+                    None => None,
+                };
+                ("item", id.to_string(), None, item)
+            }
+        };
+        let view = Self {
+            id,
+            checklists,
+            deleted: checklist.is_none() && item.is_none(),
+            checklist,
+            item,
+        };
+        Ok((event, view))
+    }
+}
+
+/// Respond with a stream of events for the changes selected by `query`.
+pub(crate) async fn subscribe(
+    db: Arc<Db>,
+    query: Option<&str>,
+    last_event_id: Option<&str>,
+) -> Result<Response<Body>, ApiError> {
+    let checklist = match query_value(query, "checklist") {
+        Some(id) => Some(load_checklist(&db, id).await?.id),
+        None => None,
+    };
+    let since = last_event_id
+        .or(query_value(query, "since"))
+        .map(|since| {
+            since
+                .parse::<OpCursor>()
+                .map_err(|_| ApiError::BadRequest(format!("invalid cursor {since:?}")))
+        })
+        .transpose()?;
+
+    let (started_sender, started) = oneshot::channel();
+    let (sender, mut events) = mpsc::channel(16);
+    tokio::spawn(forward(db, checklist, since, started_sender, sender));
+    started.await.expect("forward reports whether it started")?;
+
+    let events = futures::stream::poll_fn(move |cx| events.poll_recv(cx))
+        .map(|event| Ok(Frame::data(event)));
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(BodyExt::boxed(StreamBody::new(events)))
+        .expect("response is valid"))
+}
+
+/// Encode changes as events and send them on until the client goes away.
+async fn forward(
+    db: Arc<Db>,
+    checklist: Option<ChecklistId>,
+    since: Option<OpCursor>,
+    started: oneshot::Sender<Result<(), ApiError>>,
+    events: mpsc::Sender<Bytes>,
+) {
+    let changes = match db.changes(checklist, since).await {
+        Ok(changes) => changes,
+        Err(err) => {
+            let _ = started.send(Err(err.into()));
+            return;
+        }
+    };
+    let _ = started.send(Ok(()));
+
+    let mut changes = pin!(changes);
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE);
+    loop {
+        let event = tokio::select! {
+            change = changes.next() => {
+                let Some(change) = change else { return };
+                let change = match change {
+                    Ok(change) => change,
+                    Err(err) => {
+                        eprintln!("error: watching changes: {err}");
+                        return;
+                    }
+                };
+                let cursor = change.cursor;
+                match ChangeView::load(&db, change).await {
+                    Ok((event, data)) => encode(cursor, event, &data),
+                    Err(err) => {
+                        eprintln!("error: loading change: {err:?}");
+                        return;
+                    }
+                }
+            }
+            _ = keep_alive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+        };
+        keep_alive.reset();
+        if events.send(event).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn encode(cursor: OpCursor, event: &str, data: &ChangeView) -> Bytes {
+    let data = serde_json::to_string(data).expect("api types serialize to json");
+    Bytes::from(format!("id: {cursor}\nevent: {event}\ndata: {data}\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use checklist::Db;
+    use serde_json::{Value, json};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    /// A server-sent event.
+    #[derive(Debug)]
+    struct Event {
+        id: String,
+        event: String,
+        data: Value,
+    }
+
+    /// Reads events from a response, as a browser's `EventSource` would.
+    struct Events {
+        response: reqwest::Response,
+        buffer: String,
+    }
+
+    impl Events {
+        async fn next(&mut self) -> Event {
+            loop {
+                if let Some(end) = self.buffer.find("\n\n") {
+                    let block = self.buffer[..end].to_owned();
+                    self.buffer.drain(..end + 2);
+                    let field = |name: &str| {
+                        block
+                            .lines()
+                            .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                            .map(str::to_owned)
+                    };
+                    // comments, such as keep-alives, have no fields
+                    let (Some(id), Some(event), Some(data)) =
+                        (field("id"), field("event"), field("data"))
+                    else {
+                        continue;
+                    };
+                    let data = serde_json::from_str(&data).unwrap();
+                    return Event { id, event, data };
+                }
+                let chunk = tokio::time::timeout(Duration::from_secs(5), self.response.chunk())
+                    .await
+                    .expect("an event is sent")
+                    .unwrap()
+                    .expect("the stream goes on");
+                self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        }
+    }
+
+    struct Client {
+        client: reqwest::Client,
+        base: String,
+    }
+
+    impl Client {
+        async fn send(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Value {
+            let mut request = self.client.request(method, format!("{}{path}", self.base));
+            if let Some(body) = body {
+                request = request.json(&body);
+            }
+            let response = request.send().await.unwrap().error_for_status().unwrap();
+            response.json().await.unwrap()
+        }
+
+        /// Create a checklist or item, returning its id.
+        async fn create(&self, path: &str, body: Value) -> String {
+            let created = self.send(reqwest::Method::POST, path, Some(body)).await;
+            created["id"].as_str().unwrap().to_owned()
+        }
+
+        async fn events(&self, checklist: &str, last_event_id: Option<&str>) -> Events {
+            let mut request = self
+                .client
+                .get(format!("{}/events?checklist={checklist}", self.base));
+            if let Some(id) = last_event_id {
+                request = request.header(super::LAST_EVENT_ID, id);
+            }
+            let response = request.send().await.unwrap().error_for_status().unwrap();
+            assert_eq!(
+                response.headers()[reqwest::header::CONTENT_TYPE],
+                "text/event-stream"
+            );
+            Events {
+                response,
+                buffer: String::new(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn a_client_follows_a_checklist_and_resumes_after_reconnecting() {
+        let dir = TempDir::new().unwrap();
+        let db = Db::new(dir.path(), b"events test key").await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(crate::serve(db, None, listener));
+        let api = Client {
+            client: reqwest::Client::new(),
+            base,
+        };
+
+        let groceries = api
+            .create("/checklists", json!({ "name": "groceries" }))
+            .await;
+        let chores = api.create("/checklists", json!({ "name": "chores" })).await;
+        let milk = api
+            .create(
+                &format!("/checklists/{groceries}/items"),
+                json!({ "text": "milk" }),
+            )
+            .await;
+
+        // the response arrives once the server is watching, so no change can be missed
+        let mut events = api.events(&groceries, None).await;
+        api.send(reqwest::Method::POST, &format!("/items/{milk}/check"), None)
+            .await;
+        let checked = events.next().await;
+        assert_eq!(checked.event, "item");
+        assert_eq!(checked.data["id"], milk);
+        assert_eq!(checked.data["checklists"], json!([groceries]));
+        assert_eq!(checked.data["item"]["checked"], true);
+
+        api.create(
+            &format!("/checklists/{chores}/items"),
+            json!({ "text": "dishes" }),
+        )
+        .await;
+        api.send(
+            reqwest::Method::PATCH,
+            &format!("/items/{milk}"),
+            Some(json!({ "text": "oat milk" })),
+        )
+        .await;
+        let renamed = events.next().await;
+        assert_eq!(renamed.data["item"]["text"], "oat milk");
+        drop(events);
+
+        let mut events = api.events(&groceries, Some(&checked.id)).await;
+        let resumed = events.next().await;
+        assert_eq!(resumed.id, renamed.id);
+        assert_eq!(resumed.data, renamed.data);
+    }
+}
@@ -0,0 +1,123 @@
+mod api;
+mod events;
+mod openapi;
+
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Context as _;
+use checklist::{Checklist, Db};
+use clap::Parser;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+/// How often to reset recurring checklists whose period has ended; periods are whole minutes.
+const ROLLOVER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Serve checklists over a JSON HTTP API
+///
+/// The API is described by the OpenAPI document at `/openapi.json`.
+///
+/// Each request acts as the user named by its `Checklist-User` header, so put the server behind a
+/// proxy which sets it for authenticated users, along with the proxy key in `Checklist-Proxy-Key`.
+/// Without the header, only checklists which belong to nobody can be used.
+#[derive(Debug, Parser)]
+struct Args {
+    /// Path to the database
+    ///
+    /// Default: "$XDG_DATA_HOME" if set or "$HOME/.local/share", then "checklist/surrealdb"
+    #[arg(short, long, env = "CHECKLIST_PATH")]
+    path: Option<PathBuf>,
+
+    /// Path to file containing encryption key for data at rest
+    #[arg(short = 'E', long, env = "CHECKLIST_ENCRYPTION_KEY_FILE")]
+    encryption_key_file: PathBuf,
+
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Path to file containing the key by which the trusted proxy proves that it named the user
+    ///
+    /// Requests may only name a user if they also carry this key. Without it, no request may.
+    #[arg(long, env = "CHECKLIST_PROXY_KEY_FILE")]
+    proxy_key_file: Option<PathBuf>,
+}
+
+impl Args {
+    fn path(&self) -> anyhow::Result<PathBuf> {
+        if let Some(path) = &self.path {
+            return Ok(path.clone());
+        }
+
+        Ok(dirs::data_local_dir()
+            .context("data local dir must exist on this system")?
+            .join("checklist/surrealdb"))
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let path = args.path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("creating checklist data directory")?;
+    }
+    let encryption_key =
+        std::fs::read(&args.encryption_key_file).context("reading encryption key from file")?;
+    let proxy_key = match &args.proxy_key_file {
+        Some(file) => {
+            let key = std::fs::read(file).context("reading proxy key from file")?;
+            Some(key.trim_ascii().to_vec())
+        }
+        None => None,
+    };
+
+    let db = Db::new(path, &encryption_key)
+        .await
+        .context("connecting to database")?;
+    // requests act as whichever user they name, but every recurring checklist is due its reset
+    Checklist::roll_over_due(&db.with_full_access(), chrono::Utc::now())
+        .await
+        .context("resetting recurring checklists")?;
+    tokio::spawn(roll_over(db.with_full_access()));
+
+    let listener = TcpListener::bind(args.listen)
+        .await
+        .with_context(|| format!("listening on {}", args.listen))?;
+    eprintln!("serving checklists on http://{}", args.listen);
+    serve(db, proxy_key, listener).await
+}
+
+/// Reset recurring checklists as their periods end, for as long as the server runs.
+async fn roll_over(db: Db) {
+    loop {
+        tokio::time::sleep(ROLLOVER_INTERVAL).await;
+        if let Err(err) = Checklist::roll_over_due(&db, chrono::Utc::now()).await {
+            eprintln!("warning: resetting recurring checklists: {err}");
+        }
+    }
+}
+
+/// Serve the API for `db` on `listener` until an error stops it, letting requests which carry
+/// `proxy_key` name the user they act as.
+async fn serve(db: Db, proxy_key: Option<Vec<u8>>, listener: TcpListener) -> anyhow::Result<()> {
+    let db = Arc::new(db);
+    let proxy_key = proxy_key.map(Arc::<[u8]>::from);
+    loop {
+        let (stream, _) = listener.accept().await.context("accepting connection")?;
+        let (db, proxy_key) = (db.clone(), proxy_key.clone());
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| {
+                api::handle(db.clone(), proxy_key.clone(), request)
+            });
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                eprintln!("warning: serving connection: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,753 @@
+//! The OpenAPI document describing the API, served at `/openapi.json`.
+//!
+//! Paths are written out here; the schemas of request and response bodies are derived from the
+//! types which the handlers exchange, so that they cannot drift apart.
+
+use schemars::{JsonSchema, SchemaGenerator, generate::SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use crate::{
+    api::{
+        ChecklistPage, ChecklistView, ErrorView, ItemView, NewChecklist, NewItem, RenameChecklist,
+        UpdateItem,
+    },
+    events::ChangeView,
+};
+
+/// Schemas of request and response bodies, collected as components of the document.
+///
+/// Requests and responses get separate generators, since an optional field may be left out of a
+/// request but is always present in a response.
+struct Components {
+    requests: SchemaGenerator,
+    responses: SchemaGenerator,
+}
+
+impl Components {
+    fn new() -> Self {
+        let settings = || {
+            let mut settings = SchemaSettings::draft2020_12();
+            settings.definitions_path = "/components/schemas".into();
+            settings
+        };
+        Self {
+            requests: settings().into_generator(),
+            responses: settings().for_serialize().into_generator(),
+        }
+    }
+
+    fn request<T: JsonSchema>(&mut self) -> Value {
+        body(self.requests.subschema_for::<T>().into())
+    }
+
+    fn response<T: JsonSchema>(&mut self, description: &str) -> Value {
+        let mut response = body(self.responses.subschema_for::<T>().into());
+        response["description"] = description.into();
+        response
+    }
+
+    fn error(&mut self, description: &str) -> Value {
+        self.response::<ErrorView>(description)
+    }
+
+    /// Add the schema of a type which is sent, but not as the body of any response.
+    fn include<T: JsonSchema>(&mut self) {
+        self.responses.subschema_for::<T>();
+    }
+
+    fn into_schemas(mut self) -> Map<String, Value> {
+        let mut schemas = self.requests.take_definitions(true);
+        schemas.extend(self.responses.take_definitions(true));
+        schemas
+    }
+}
+
+fn body(schema: Value) -> Value {
+    json!({ "content": { "application/json": { "schema": schema } } })
+}
+
+/// Parameters of every operation on a path, with an `id` described by `id` if the path has one.
+fn path_parameters(id: Option<&str>) -> Value {
+    let mut parameters = vec![
+        json!({ "$ref": "#/components/parameters/User" }),
+        json!({ "$ref": "#/components/parameters/ProxyKey" }),
+    ];
+    if let Some(description) = id {
+        parameters.push(json!({
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": { "type": "string" },
+        }));
+    }
+    parameters.into()
+}
+
+pub(crate) fn document() -> Value {
+    let mut c = Components::new();
+    c.include::<ChangeView>();
+    let no_content = json!({ "description": "Done" });
+    let forbidden = json!({ "$ref": "#/components/responses/Forbidden" });
+    let checklist_id = path_parameters(Some("Id of the checklist"));
+    let item_id = path_parameters(Some("Id of the item"));
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Checklists",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Requests act as the user named in the `Checklist-User` header. The \
+                server cannot tell who sent a request, so it only accepts that header from the \
+                trusted proxy in front of it, which authenticates users and proves itself with \
+                the `Checklist-Proxy-Key` header.",
+        },
+        "paths": {
+            "/checklists": {
+                "parameters": path_parameters(None),
+                "get": {
+                    "summary": "List checklists, oldest first",
+                    "parameters": [
+                        {
+                            "name": "limit",
+                            "in": "query",
+                            "description": "Return at most this many checklists",
+                            "schema": { "type": "integer", "minimum": 0 },
+                        },
+                        {
+                            "name": "after",
+                            "in": "query",
+                            "description": "Start after this cursor, from `next` of the previous page",
+                            "schema": { "type": "string" },
+                        },
+                        {
+                            "name": "template",
+                            "in": "query",
+                            "description": "Only return templates, or only checklists which are not",
+                            "schema": { "type": "boolean" },
+                        },
+                        {
+                            "name": "archived",
+                            "in": "query",
+                            "description": "Return only archived checklists, instead of only those which are not",
+                            "schema": { "type": "boolean", "default": false },
+                        },
+                    ],
+                    "responses": {
+                        "200": c.response::<ChecklistPage>("A page of checklists"),
+                        "400": c.error("Invalid parameters"),
+                        "403": forbidden,
+                    },
+                },
+                "post": {
+                    "summary": "Create a checklist",
+                    "requestBody": c.request::<NewChecklist>(),
+                    "responses": {
+                        "201": c.response::<ChecklistView>("The new checklist"),
+                        "400": c.error("Invalid body"),
+                        "403": forbidden,
+                    },
+                },
+            },
+            "/checklists/{id}": {
+                "parameters": checklist_id,
+                "get": {
+                    "summary": "Get a checklist",
+                    "responses": {
+                        "200": c.response::<ChecklistView>("The checklist"),
+                        "403": forbidden,
+                        "404": c.error("No such checklist"),
+                    },
+                },
+                "patch": {
+                    "summary": "Rename a checklist",
+                    "requestBody": c.request::<RenameChecklist>(),
+                    "responses": {
+                        "200": c.response::<ChecklistView>("The renamed checklist"),
+                        "400": c.error("Invalid body"),
+                        "403": forbidden,
+                        "404": c.error("No such checklist"),
+                        "409": c.error("The checklist is locked, or no longer at the given revision"),
+                    },
+                },
+                "delete": {
+                    "summary": "Delete a checklist and its items",
+                    "responses": {
+                        "204": no_content,
+                        "403": forbidden,
+                        "404": c.error("No such checklist"),
+                        "409": c.error("The checklist is locked"),
+                    },
+                },
+            },
+            "/checklists/{id}/items": {
+                "parameters": checklist_id,
+                "get": {
+                    "summary": "List the items of a checklist, each followed by those nested under it",
+                    "responses": {
+                        "200": c.response::<Vec<ItemView>>("The items"),
+                        "403": forbidden,
+                        "404": c.error("No such checklist"),
+                    },
+                },
+                "post": {
+                    "summary": "Add an item to the end of a checklist",
+                    "requestBody": c.request::<NewItem>(),
+                    "responses": {
+                        "201": c.response::<ItemView>("The new item"),
+                        "400": c.error("Invalid body, or a parent in another checklist"),
+                        "403": forbidden,
+                        "404": c.error("No such checklist or parent"),
+                        "409": c.error("The checklist is locked"),
+                    },
+                },
+            },
+            "/items/{id}": {
+                "parameters": item_id,
+                "get": {
+                    "summary": "Get an item",
+                    "responses": {
+                        "200": c.response::<ItemView>("The item"),
+                        "403": forbidden,
+                        "404": c.error("No such item"),
+                    },
+                },
+                "patch": {
+                    "summary": "Change the text of an item",
+                    "requestBody": c.request::<UpdateItem>(),
+                    "responses": {
+                        "200": c.response::<ItemView>("The changed item"),
+                        "400": c.error("Invalid body"),
+                        "403": forbidden,
+                        "404": c.error("No such item"),
+                        "409": c.error("The checklist is locked, or the item is no longer at the given revision"),
+                    },
+                },
+                "delete": {
+                    "summary": "Delete an item and the items nested under it",
+                    "responses": {
+                        "204": no_content,
+                        "403": forbidden,
+                        "404": c.error("No such item"),
+                        "409": c.error("The checklist is locked"),
+                    },
+                },
+            },
+            "/items/{id}/check": {
+                "parameters": item_id,
+                "post": {
+                    "summary": "Check an item",
+                    "responses": {
+                        "200": c.response::<ItemView>("The checked item"),
+                        "403": forbidden,
+                        "404": c.error("No such item"),
+                        "409": c.error("The checklist is locked, or the item changed while being checked"),
+                    },
+                },
+            },
+            "/items/{id}/uncheck": {
+                "parameters": item_id,
+                "post": {
+                    "summary": "Uncheck an item",
+                    "responses": {
+                        "200": c.response::<ItemView>("The unchecked item"),
+                        "403": forbidden,
+                        "404": c.error("No such item"),
+                        "409": c.error("The checklist is locked, or the item changed while being unchecked"),
+                    },
+                },
+            },
+            "/events": {
+                "parameters": path_parameters(None),
+                "get": {
+                    "summary": "Follow changes to checklists and items as server-sent events",
+                    "description": "Each event is named `checklist` or `item`, carries a `Change` \
+                        as its data, and has a cursor as its id. A client which reconnects \
+                        with that id in `Last-Event-ID` misses no change which is synced between \
+                        devices, but changes which are not, such as to assignees, are only \
+                        reported as they happen.",
+                    "parameters": [
+                        {
+                            "name": "checklist",
+                            "in": "query",
+                            "description": "Only report changes to this checklist and its items",
+                            "schema": { "type": "string" },
+                        },
+                        {
+                            "name": "since",
+                            "in": "query",
+                            "description": "First report the changes after this cursor; otherwise only changes from now on",
+                            "schema": { "type": "string" },
+                        },
+                        {
+                            "name": "Last-Event-ID",
+                            "in": "header",
+                            "description": "Overrides `since`, as sent by a reconnecting client",
+                            "schema": { "type": "string" },
+                        },
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "The stream of events",
+                            "content": { "text/event-stream": { "schema": { "type": "string" } } },
+                        },
+                        "400": c.error("Invalid cursor"),
+                        "403": forbidden,
+                        "404": c.error("No such checklist"),
+                    },
+                },
+            },
+        },
+        "components": {
+            "parameters": {
+                "User": {
+                    "name": "Checklist-User",
+                    "in": "header",
+                    "description": "Name of the user to act as; without it, only checklists which \
+                        belong to nobody can be used. Only accepted from the trusted proxy.",
+                    "schema": { "type": "string" },
+                },
+                "ProxyKey": {
+                    "name": "Checklist-Proxy-Key",
+                    "in": "header",
+                    "description": "The key the server was started with, by which the trusted \
+                        proxy proves that it named the user",
+                    "schema": { "type": "string" },
+                },
+            },
+            "responses": {
+                "Forbidden": c.error(
+                    "The acting user may not do this, or does not exist, or was not named by \
+                        the trusted proxy",
+                ),
+            },
+            "schemas": c.into_schemas(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use checklist::{Checklist, Db, Item, User};
+    use reqwest::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+
+    /// Fail unless `value` matches `schema`, resolving references within `doc`.
+    ///
+    /// Understands just the parts of JSON schema which the document uses, and is stricter than
+    /// JSON schema in one way: objects may only have the properties which are described.
+    fn validate(doc: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let target = doc
+                .pointer(reference.trim_start_matches('#'))
+                .ok_or_else(|| format!("{at}: dangling reference {reference}"))?;
+            return validate(doc, target, value, at);
+        }
+        if let Some(options) = schema["anyOf"].as_array().or(schema["oneOf"].as_array()) {
+            return match options
+                .iter()
+                .any(|option| validate(doc, option, value, at).is_ok())
+            {
+                true => Ok(()),
+                false => Err(format!("{at}: {value} matches none of {options:?}")),
+            };
+        }
+        if let Some(types) = schema.get("type") {
+            let types = types
+                .as_array()
+                .cloned()
+                .unwrap_or_else(|| vec![types.clone()]);
+            let is = |kind: &str| match kind {
+                "null" => value.is_null(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "string" => value.is_string(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => false,
+            };
+            if !types.iter().any(|kind| kind.as_str().is_some_and(is)) {
+                return Err(format!("{at}: {value} is not of type {types:?}"));
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let required = required.as_str().unwrap_or_default();
+                if !object.contains_key(required) {
+                    return Err(format!("{at}: missing required {required:?}"));
+                }
+            }
+            for (key, value) in object {
+                let property = schema["properties"]
+                    .get(key)
+                    .ok_or_else(|| format!("{at}: undocumented property {key:?}"))?;
+                validate(doc, property, value, &format!("{at}.{key}"))?;
+            }
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            for (index, value) in values.iter().enumerate() {
+                validate(doc, items, value, &format!("{at}[{index}]"))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[derive(Clone)]
+    struct Api {
+        client: reqwest::Client,
+        base: String,
+        doc: Value,
+        /// Name of the user requests act as.
+        user: Option<&'static str>,
+        /// Proxy key sent along with the user.
+        proxy_key: &'static str,
+    }
+
+    const PROXY_KEY: &str = "openapi test proxy key";
+
+    impl Api {
+        /// Serve `db` on a loopback port, and fetch the document from it.
+        async fn start(db: Db) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(crate::serve(db, Some(PROXY_KEY.into()), listener));
+            let client = reqwest::Client::new();
+            let doc = client
+                .get(format!("{base}/openapi.json"))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            Self {
+                client,
+                base,
+                doc,
+                user: None,
+                proxy_key: PROXY_KEY,
+            }
+        }
+
+        fn as_user(&self, user: &'static str) -> Self {
+            Self {
+                user: Some(user),
+                ..self.clone()
+            }
+        }
+
+        /// Send a request, check that the status and body are as documented for `template`, and
+        /// return the body.
+        async fn call(
+            &self,
+            method: Method,
+            path: &str,
+            template: &str,
+            body: Option<Value>,
+            expected: StatusCode,
+        ) -> Value {
+            let mut request = self
+                .client
+                .request(method.clone(), format!("{}{path}", self.base));
+            if let Some(user) = self.user {
+                request = request
+                    .header(crate::api::USER, user)
+                    .header(crate::api::PROXY_KEY, self.proxy_key);
+            }
+            if let Some(body) = body {
+                request = request.json(&body);
+            }
+            let response = request.send().await.unwrap();
+            let status = response.status();
+            let text = response.text().await.unwrap();
+            assert_eq!(status, expected, "{method} {path}: {text}");
+
+            let mut documented = &self.doc["paths"][template][method.as_str().to_lowercase()]["responses"]
+                [status.as_str()];
+            if let Some(reference) = documented["$ref"].as_str() {
+                documented = self
+                    .doc
+                    .pointer(reference.trim_start_matches('#'))
+                    .unwrap_or(&Value::Null);
+            }
+            assert!(
+                documented.is_object(),
+                "{method} {template} does not document {status}"
+            );
+            let Some(schema) = documented.pointer("/content/application~1json/schema") else {
+                assert!(text.is_empty(), "{method} {path}: undocumented body {text}");
+                return Value::Null;
+            };
+            let body = serde_json::from_str(&text).unwrap();
+            if let Err(err) = validate(&self.doc, schema, &body, "body") {
+                panic!("{method} {path}: {err}");
+            }
+            body
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_match_the_document() {
+        let dir = TempDir::new().unwrap();
+        let db = Db::new(dir.path(), b"openapi test key").await.unwrap();
+        let api = Api::start(db).await;
+
+        let checklist = api
+            .call(
+                Method::POST,
+                "/checklists",
+                "/checklists",
+                Some(json!({ "name": "trip" })),
+                StatusCode::CREATED,
+            )
+            .await;
+        let checklist_id = checklist["id"].as_str().unwrap();
+        let items = format!("/checklists/{checklist_id}/items");
+        let item = api
+            .call(
+                Method::POST,
+                &items,
+                "/checklists/{id}/items",
+                Some(json!({ "text": "pack" })),
+                StatusCode::CREATED,
+            )
+            .await;
+        let item_id = item["id"].as_str().unwrap();
+        let child = api
+            .call(
+                Method::POST,
+                &items,
+                "/checklists/{id}/items",
+                Some(json!({ "text": "socks", "parent": item_id })),
+                StatusCode::CREATED,
+            )
+            .await;
+        let child_id = child["id"].as_str().unwrap();
+
+        let checklist_path = format!("/checklists/{checklist_id}");
+        let item_path = format!("/items/{item_id}");
+        let page = api
+            .call(
+                Method::GET,
+                "/checklists?limit=1",
+                "/checklists",
+                None,
+                StatusCode::OK,
+            )
+            .await;
+        assert_eq!(page["checklists"][0]["id"], checklist_id);
+        api.call(
+            Method::GET,
+            &checklist_path,
+            "/checklists/{id}",
+            None,
+            StatusCode::OK,
+        )
+        .await;
+        api.call(
+            Method::GET,
+            &items,
+            "/checklists/{id}/items",
+            None,
+            StatusCode::OK,
+        )
+        .await;
+        api.call(
+            Method::PATCH,
+            &checklist_path,
+            "/checklists/{id}",
+            Some(json!({ "name": "holiday", "revision": 99 })),
+            StatusCode::CONFLICT,
+        )
+        .await;
+        api.call(
+            Method::PATCH,
+            &checklist_path,
+            "/checklists/{id}",
+            Some(json!({ "name": "holiday" })),
+            StatusCode::OK,
+        )
+        .await;
+        api.call(
+            Method::POST,
+            &format!("/items/{child_id}/check"),
+            "/items/{id}/check",
+            None,
+            StatusCode::OK,
+        )
+        .await;
+        api.call(
+            Method::PATCH,
+            &item_path,
+            "/items/{id}",
+            Some(json!({ "text": "pack bags" })),
+            StatusCode::OK,
+        )
+        .await;
+        api.call(Method::GET, &item_path, "/items/{id}", None, StatusCode::OK)
+            .await;
+        api.call(
+            Method::DELETE,
+            &format!("/items/{child_id}"),
+            "/items/{id}",
+            None,
+            StatusCode::NO_CONTENT,
+        )
+        .await;
+        api.call(
+            Method::GET,
+            &format!("/items/{child_id}"),
+            "/items/{id}",
+            None,
+            StatusCode::NOT_FOUND,
+        )
+        .await;
+        api.call(
+            Method::POST,
+            "/checklists",
+            "/checklists",
+            Some(json!({})),
+            StatusCode::BAD_REQUEST,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn requests_act_as_the_named_user() {
+        let dir = TempDir::new().unwrap();
+        let db = Db::new(dir.path(), b"openapi test key").await.unwrap();
+        User::new(&db, "alice").await.unwrap();
+        User::new(&db, "bob").await.unwrap();
+        let anonymous = Api::start(db).await;
+        let (alice, bob) = (anonymous.as_user("alice"), anonymous.as_user("bob"));
+
+        let owned = alice
+            .call(
+                Method::POST,
+                "/checklists",
+                "/checklists",
+                Some(json!({ "name": "diary" })),
+                StatusCode::CREATED,
+            )
+            .await;
+        let owned = format!("/checklists/{}", owned["id"].as_str().unwrap());
+        let open = anonymous
+            .call(
+                Method::POST,
+                "/checklists",
+                "/checklists",
+                Some(json!({ "name": "shopping" })),
+                StatusCode::CREATED,
+            )
+            .await;
+
+        alice
+            .call(
+                Method::GET,
+                &owned,
+                "/checklists/{id}",
+                None,
+                StatusCode::OK,
+            )
+            .await;
+        for api in [&anonymous, &bob] {
+            api.call(
+                Method::GET,
+                &owned,
+                "/checklists/{id}",
+                None,
+                StatusCode::FORBIDDEN,
+            )
+            .await;
+            let page = api
+                .call(
+                    Method::GET,
+                    "/checklists",
+                    "/checklists",
+                    None,
+                    StatusCode::OK,
+                )
+                .await;
+            assert_eq!(page["checklists"], json!([open]));
+        }
+        anonymous
+            .as_user("carol")
+            .call(
+                Method::GET,
+                "/checklists",
+                "/checklists",
+                None,
+                StatusCode::FORBIDDEN,
+            )
+            .await;
+        let impostor = Api {
+            proxy_key: "guessed key",
+            ..alice
+        };
+        impostor
+            .call(
+                Method::GET,
+                &owned,
+                "/checklists/{id}",
+                None,
+                StatusCode::FORBIDDEN,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn archived_and_locked_checklists_are_described() {
+        let dir = TempDir::new().unwrap();
+        let db = Db::new(dir.path(), b"openapi test key").await.unwrap();
+        let mut old = Checklist::new(&db, "old").await.unwrap();
+        old.archive(&db).await.unwrap();
+        let mut frozen = Checklist::new(&db, "frozen").await.unwrap();
+        let item = Item::new(&db, frozen.id.clone(), "thaw").await.unwrap();
+        frozen.lock(&db).await.unwrap();
+        let api = Api::start(db).await;
+
+        let page = api
+            .call(
+                Method::GET,
+                "/checklists",
+                "/checklists",
+                None,
+                StatusCode::OK,
+            )
+            .await;
+        assert_eq!(page["checklists"][0]["name"], "frozen");
+        assert_eq!(page["checklists"][0]["locked"], true);
+        assert_eq!(page["checklists"][0]["archived"], false);
+        let page = api
+            .call(
+                Method::GET,
+                "/checklists?archived=true",
+                "/checklists",
+                None,
+                StatusCode::OK,
+            )
+            .await;
+        assert_eq!(page["checklists"][0]["name"], "old");
+        assert_eq!(page["checklists"][0]["archived"], true);
+
+        api.call(
+            Method::POST,
+            &format!("/checklists/{}/items", frozen.id),
+            "/checklists/{id}/items",
+            Some(json!({ "text": "melt" })),
+            StatusCode::CONFLICT,
+        )
+        .await;
+        api.call(
+            Method::POST,
+            &format!("/items/{}/check", item.id),
+            "/items/{id}/check",
+            None,
+            StatusCode::CONFLICT,
+        )
+        .await;
+    }
+}